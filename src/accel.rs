@@ -0,0 +1,1498 @@
+use evdev_rs::TimeVal;
+
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use crate::{
+    args::{Args, Curve, SpeedMetric},
+    filter::{InputSmoother, OneEuroFilter, OutputAverage},
+};
+
+pub fn factor(sens_multiplier: f64, accel: f64, cap: f64, offset: f64, speed: f64) -> f64 {
+    if speed < offset {
+        sens_multiplier
+    } else {
+        sens_multiplier * f64::min(accel.mul_add(speed - offset, 1.0), cap)
+    }
+}
+
+// a step from no acceleration to the cap at the threshold, blended over blend counts per ms past
+// it; once broken away, it takes dropping hysteresis below the threshold to fall back, so speed
+// hovering around it doesn't flap between the two
+pub fn breakaway(
+    sens_mult: f64,
+    threshold: f64,
+    cap: f64,
+    blend: f64,
+    hysteresis: f64,
+    speed: f64,
+    broken_away: &mut bool,
+) -> f64 {
+    let threshold = if *broken_away {
+        threshold - hysteresis
+    } else {
+        threshold
+    };
+    *broken_away = speed >= threshold;
+    if !*broken_away {
+        return sens_mult;
+    }
+    let blended = if blend > 0.0 {
+        f64::min((speed - threshold) / blend, 1.0)
+    } else {
+        1.0
+    };
+    sens_mult * (1.0 + (cap - 1.0) * blended)
+}
+
+// broken_away is the breakaway curve's state, left alone by the others
+pub fn sensitivity(sens_mult: f64, curve: &Curve, speed: f64, broken_away: &mut bool) -> f64 {
+    match *curve {
+        Curve::Linear { accel, cap, offset } => factor(sens_mult, accel, cap, offset, speed),
+        Curve::Breakaway {
+            threshold,
+            cap,
+            blend,
+            hysteresis,
+        } => breakaway(
+            sens_mult,
+            threshold,
+            cap,
+            blend,
+            hysteresis,
+            speed,
+            broken_away,
+        ),
+        // nothing stops an expression from blowing up (1/s standing still), and scaling by
+        // infinity or nan would poison the accumulators
+        Curve::Expr { ref expr, .. } => match expr.eval(speed) {
+            sensitivity if sensitivity.is_finite() => sens_mult * sensitivity,
+            _ => sens_mult,
+        },
+        Curve::VelocityMap(ref map) => sens_mult * map.multiplier(speed),
+        Curve::Segmented(ref segments) => {
+            let (_, curve) = segments
+                .iter()
+                .rev()
+                .find(|(from, _)| speed >= *from)
+                .unwrap_or(&segments[0]);
+            sensitivity(sens_mult, curve, speed, broken_away)
+        }
+    }
+}
+
+// how far apart adjacent segments can be where they meet and still count as meeting
+const SEAM_TOLERANCE: f64 = 1e-3;
+
+// where a segmented curve jumps from one segment to the next: the speed, and the sensitivity just
+// below and at it
+pub fn discontinuities(segments: &[(f64, Curve)]) -> Vec<(f64, f64, f64)> {
+    segments
+        .windows(2)
+        .filter_map(|pair| {
+            let from = pair[1].0;
+            let below = sensitivity(1.0, &pair[0].1, from, &mut false);
+            let above = sensitivity(1.0, &pair[1].1, from, &mut false);
+            ((below - above).abs() > SEAM_TOLERANCE).then_some((from, below, above))
+        })
+        .collect()
+}
+
+// exact to the microsecond however large the timestamps get, since it's only turned into a float
+// once the seconds have cancelled out
+// time_t and suseconds_t are only 32 bits on some platforms
+#[allow(clippy::useless_conversion)]
+pub fn elapsed_ms(from: TimeVal, to: TimeVal) -> f64 {
+    let usec = (i64::from(to.tv_sec) - i64::from(from.tv_sec)) * 1_000_000
+        + (i64::from(to.tv_usec) - i64::from(from.tv_usec));
+    usec as f64 / 1000.0
+}
+
+// the speed is the same either way, so swapping first leaves the acceleration alone while every
+// per-axis option and the accumulators work on the axes written out
+fn swapped(args: &Args, x: f64, y: f64) -> (f64, f64) {
+    if args.swap_xy {
+        (y, x)
+    } else {
+        (x, y)
+    }
+}
+
+// keeps accelerated movement within the per-axis speed limits over a frame change_ms long, giving
+// what's let through and what's cut off
+fn limit_speed(args: &Args, x: f64, y: f64, change_ms: f64) -> ((f64, f64), (f64, f64)) {
+    let x_limit = args
+        .max_x_speed
+        .map_or(f64::INFINITY, |max| max * change_ms);
+    let y_limit = args
+        .max_y_speed
+        .map_or(f64::INFINITY, |max| max * change_ms);
+    if args.preserve_direction {
+        // both axes scaled by whichever is furthest over
+        let scale = f64::min(x_limit / x.abs(), y_limit / y.abs()).min(1.0);
+        ((x * scale, y * scale), (x - x * scale, y - y * scale))
+    } else {
+        let (x_limited, y_limited) = (x.clamp(-x_limit, x_limit), y.clamp(-y_limit, y_limit));
+        ((x_limited, y_limited), (x - x_limited, y - y_limited))
+    }
+}
+
+// a gap between frames this long ends a movement, for static friction and --accel-after
+const IDLE_GAP_MS: f64 = 100.0;
+
+// 1 along the axes, 1 + compensation at 45 degrees and smoothly in between (as sin^2 of twice the
+// angle)
+fn diagonal_mult(compensation: f64, x: f64, y: f64) -> f64 {
+    let dist_squared = x * x + y * y;
+    if compensation == 0.0 || dist_squared == 0.0 {
+        return 1.0;
+    }
+    let sin_doubled = 2.0 * x * y / dist_squared;
+    1.0 + compensation * sin_doubled * sin_doubled
+}
+
+fn direction_mult(pos: f64, neg: f64, delta: f64) -> f64 {
+    if delta < 0.0 {
+        neg
+    } else {
+        pos
+    }
+}
+
+// whether delta goes against the last direction, which it then becomes
+fn turned(direction: &mut f64, delta: f64) -> bool {
+    if delta == 0.0 {
+        return false;
+    }
+    let turned = *direction != 0.0 && delta.signum() != *direction;
+    *direction = delta.signum();
+    turned
+}
+
+// a value another program writes to a file, parsed from the trimmed contents
+struct WatchedFile<T> {
+    path: PathBuf,
+    // of the contents value came from
+    modified: Option<SystemTime>,
+    value: T,
+    parse: fn(&str) -> Option<T>,
+}
+
+impl<T: Copy> WatchedFile<T> {
+    fn new(path: PathBuf, value: T, parse: fn(&str) -> Option<T>) -> WatchedFile<T> {
+        WatchedFile {
+            path,
+            modified: None,
+            value,
+            parse,
+        }
+    }
+
+    // only rereads the file once it's changed; a missing file or one that doesn't parse (likely
+    // caught partway through being written) keeps the last value, and gets tried again next time
+    fn value(&mut self) -> T {
+        let Ok(modified) = fs::metadata(&self.path).and_then(|metadata| metadata.modified()) else {
+            return self.value;
+        };
+        if self.modified == Some(modified) {
+            return self.value;
+        }
+        let value = fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|contents| (self.parse)(contents.trim()));
+        if let Some(value) = value {
+            self.value = value;
+            self.modified = Some(modified);
+        }
+        self.value
+    }
+}
+
+fn parse_mult(contents: &str) -> Option<f64> {
+    contents
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite() && *value >= 0.0)
+}
+
+// the gain the --observed-gain-file has the desktop adding on top, from "MOVED OBSERVED": the
+// counts a calibration moved and the pixels the pointer went
+pub fn parse_gain(contents: &str) -> Option<f64> {
+    let numbers: Vec<f64> = contents
+        .split_whitespace()
+        .map(|number| number.parse().ok())
+        .collect::<Option<_>>()?;
+    let [moved, observed] = numbers[..] else {
+        return None;
+    };
+    let gain = observed / moved;
+    (moved > 0.0 && observed > 0.0 && gain.is_finite()).then_some(gain)
+}
+
+// what the --focus-file says, whether the game has focus
+fn parse_focus(contents: &str) -> Option<bool> {
+    match contents {
+        "focused" | "1" => Some(true),
+        "unfocused" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+// state carried between frames
+pub struct Accelerator {
+    pub x_accum: f64,
+    pub y_accum: f64,
+    // what the last frame was processed with
+    pub speed: f64,
+    pub sensitivity: f64,
+    // the curve is replaced by a flat lock_mult, for comparing against
+    pub locked: bool,
+    // index into the modifier map of the profile used instead of the main curve
+    pub profile: Option<usize>,
+    // raw counts left of click stabilization while a button is held
+    pub click_budget: Option<f64>,
+    // the frame's movement on the --axes beyond X and Y, set before process_frame, and what it
+    // turned it into
+    pub axes_in: Vec<f64>,
+    pub axes_out: Vec<i32>,
+    axes_accum: Vec<f64>,
+    // opened on first use, so a reload picks up a new path
+    dynamic_mult: Option<WatchedFile<f64>>,
+    fov_mult: Option<WatchedFile<f64>>,
+    focus: Option<WatchedFile<bool>>,
+    observed_gain: Option<WatchedFile<f64>>,
+    frame_last: TimeVal,
+    // movement of the current frame already written early, which still counts toward its speed
+    flushed: (f64, f64),
+    flushed_axes: Vec<f64>,
+    // sign of the last nonzero movement on each axis
+    x_direction: f64,
+    y_direction: f64,
+    // unit vector of the last frame that moved
+    heading: Option<(f64, f64)>,
+    clamp_warned: bool,
+    // the speed the curve was last looked up at, with speed hysteresis
+    held_speed: Option<f64>,
+    // how long movement has gone on without an idle gap
+    moving_ms: f64,
+    // raw movement held back by static friction since the last idle gap, None once it's broken
+    // through
+    stuck: Option<(f64, f64)>,
+    // whether a breakaway curve is past its threshold
+    broken_away: bool,
+    // counts acceleration can still add, None for a full budget
+    budget_left: Option<f64>,
+    // the sensitivity --max-mult-slew last let through, None after an idle gap
+    slewed: Option<f64>,
+    // the offset --adaptive-offset has come to, None until the first frame
+    adapted_offset: Option<f64>,
+    // when the last frame over the flick threshold was, and whether the frames since have all been
+    // over it too
+    flick_last: Option<TimeVal>,
+    flicking: bool,
+    x_filter: OneEuroFilter,
+    y_filter: OneEuroFilter,
+    x_smoother: InputSmoother,
+    y_smoother: InputSmoother,
+    output_average: OutputAverage,
+}
+
+impl Accelerator {
+    pub fn new() -> Accelerator {
+        Accelerator {
+            x_accum: 0.0,
+            y_accum: 0.0,
+            speed: 0.0,
+            sensitivity: 0.0,
+            locked: false,
+            profile: None,
+            click_budget: None,
+            axes_in: Vec::new(),
+            axes_out: Vec::new(),
+            axes_accum: Vec::new(),
+            dynamic_mult: None,
+            fov_mult: None,
+            focus: None,
+            observed_gain: None,
+            frame_last: TimeVal::new(0, 0),
+            flushed: (0.0, 0.0),
+            flushed_axes: Vec::new(),
+            x_direction: 0.0,
+            y_direction: 0.0,
+            heading: None,
+            clamp_warned: false,
+            held_speed: None,
+            broken_away: false,
+            moving_ms: 0.0,
+            stuck: Some((0.0, 0.0)),
+            budget_left: None,
+            slewed: None,
+            adapted_offset: None,
+            flick_last: None,
+            flicking: false,
+            x_filter: OneEuroFilter::new(),
+            y_filter: OneEuroFilter::new(),
+            x_smoother: InputSmoother::default(),
+            y_smoother: InputSmoother::default(),
+            output_average: OutputAverage::default(),
+        }
+    }
+
+    // takes the raw movement of a frame ending at time and returns the movement to write out
+    // rel_events is how many REL_X and REL_Y events the frame was made of, and path how far its
+    // reports moved between them when several were coalesced, None for a straight line
+    pub fn process_frame(
+        &mut self,
+        args: &Args,
+        x: f64,
+        y: f64,
+        rel_events: u32,
+        path: Option<f64>,
+        time: TimeVal,
+    ) -> (i32, i32) {
+        let (mut x, mut y) = swapped(args, x, y);
+        if let Some(alpha) = args.input_smooth {
+            x = self.x_smoother.smooth(x, alpha);
+            y = self.y_smoother.smooth(y, alpha);
+        }
+        let change_ms = elapsed_ms(self.frame_last, time);
+        let (x_total, y_total) = (x + self.flushed.0, y + self.flushed.1);
+        self.flushed = (0.0, 0.0);
+        self.flushed_axes.resize(self.axes_in.len(), 0.0);
+        let axes_squared: f64 = self
+            .axes_in
+            .iter()
+            .zip(&self.flushed_axes)
+            .map(|(value, flushed)| (value + flushed) * (value + flushed))
+            .sum();
+        self.flushed_axes.fill(0.0);
+        let dist = (x_total * x_total + y_total * y_total + axes_squared).sqrt();
+        // the straight line from start to end cuts the corners of a curve
+        let path_dist = match path {
+            Some(path) if args.path_length_speed => path.max(dist),
+            _ => dist,
+        };
+        // frames sharing a timestamp (or going back in time) say nothing about speed, and the
+        // input scale only changes what the curve sees, not the movement it's applied to
+        let mut speed = match args.speed_metric {
+            SpeedMetric::CountsPerMs if change_ms > 0.0 => path_dist * args.input_scale / change_ms,
+            SpeedMetric::CountsPerFrame => path_dist * args.input_scale,
+            SpeedMetric::EventsPerSec if change_ms > 0.0 => rel_events as f64 * 1000.0 / change_ms,
+            SpeedMetric::CountsPerMs | SpeedMetric::EventsPerSec => 0.0,
+        };
+        // small changes keep last frame's speed, so noise doesn't dither the multiplier
+        if let Some(delta) = args.speed_hysteresis {
+            match self.held_speed {
+                Some(held) if (speed - held).abs() <= delta => speed = held,
+                _ => self.held_speed = Some(speed),
+            }
+        }
+        if args.directional_accel && self.reversed(args, x_total, y_total) {
+            speed = 0.0;
+        }
+        self.moving_ms = if change_ms > IDLE_GAP_MS {
+            0.0
+        } else {
+            self.moving_ms + change_ms
+        };
+        // a short bump, however fast, isn't accelerated
+        if args.accel_after.is_some_and(|after| self.moving_ms < after) {
+            speed = 0.0;
+        }
+        // movement while clicking isn't accelerated and is scaled down, until the budget's used up
+        let stabilizing = self.click_budget.is_some_and(|left| left > 0.0);
+        if let Some(ref mut left) = self.click_budget {
+            *left -= dist;
+        }
+        if stabilizing {
+            speed = 0.0;
+        }
+        if args.adaptive_offset {
+            self.adapt_offset(args, speed, change_ms);
+        }
+        let mut sensitivity = self.sensitivity_at(args, speed);
+        if self.cooling_down(args, speed, time) {
+            sensitivity = self.base_sensitivity(args);
+        }
+        if stabilizing {
+            sensitivity *= args.click_stabilize_mult;
+        }
+        sensitivity *= diagonal_mult(args.diagonal_compensation, x_total, y_total);
+        if let Some(budget) = args.accel_budget {
+            sensitivity = self.budgeted(args, budget, sensitivity, dist, change_ms);
+        }
+        if let Some(slew) = args.max_mult_slew {
+            sensitivity = self.slewed(slew, sensitivity, change_ms);
+        }
+        self.speed = speed;
+        self.sensitivity = sensitivity;
+        self.round_axes(args, sensitivity);
+        // the speed is still this frame's alone, so breaking through isn't taken for a flick
+        let Some((x, y)) = self.unstuck(args, x, y, change_ms) else {
+            self.frame_last = time;
+            return (0, 0);
+        };
+        let (mut x, mut y) = self.directed(args, x, y, sensitivity);
+        if args.one_euro {
+            let dt = change_ms / 1000.0;
+            x = self.x_filter.filter(x, dt, args.min_cutoff, args.beta);
+            y = self.y_filter.filter(y, dt, args.min_cutoff, args.beta);
+        }
+        if let Some(window) = args.output_average {
+            let ms = (change_ms <= IDLE_GAP_MS).then_some(change_ms);
+            (x, y) = self.output_average.average(x, y, ms, window);
+        }
+        // the limits need a frame time to turn into a distance; what they cut off goes into the
+        // accumulators, which count towards the limits with the frame, so it comes out over the
+        // frames after no faster than the limits
+        let mut cut = (0.0, 0.0);
+        if change_ms > 0.0 {
+            let limited;
+            (limited, cut) = limit_speed(args, x + self.x_accum, y + self.y_accum, change_ms);
+            (x, y) = (limited.0 - self.x_accum, limited.1 - self.y_accum);
+        }
+        self.frame_last = time;
+        let rounded = self.round(args, x, y, dist > 0.0);
+        if !args.no_accum {
+            self.x_accum += cut.0;
+            self.y_accum += cut.1;
+        }
+        rounded
+    }
+
+    // zeroes every remainder, returning the largest there was
+    pub fn clear_remainders(&mut self) -> f64 {
+        let largest = [self.x_accum, self.y_accum]
+            .iter()
+            .chain(&self.axes_accum)
+            .fold(0.0, |largest: f64, accum| largest.max(accum.abs()));
+        self.x_accum = 0.0;
+        self.y_accum = 0.0;
+        self.axes_accum.fill(0.0);
+        largest
+    }
+
+    // whether --input-smooth or --output-average is holding back movement, which an empty frame
+    // lets out
+    pub fn smoothing(&self) -> bool {
+        self.x_smoother.holding() || self.y_smoother.holding() || self.output_average.holding()
+    }
+
+    // holds back the raw movement starting out after an idle gap until it adds up to the static
+    // friction, then lets it all through at once; None while it's held
+    fn unstuck(&mut self, args: &Args, x: f64, y: f64, change_ms: f64) -> Option<(f64, f64)> {
+        let Some(friction) = args.static_friction else {
+            return Some((x, y));
+        };
+        if change_ms > IDLE_GAP_MS {
+            self.stuck = Some((0.0, 0.0));
+        }
+        let Some(held) = self.stuck else {
+            return Some((x, y));
+        };
+        let held = (held.0 + x, held.1 + y);
+        if held.0.hypot(held.1) < friction {
+            self.stuck = Some(held);
+            return None;
+        }
+        self.stuck = None;
+        Some(held)
+    }
+
+    // whether movement turns away from the heading by more than the reversal angle, which starts
+    // over what built up moving the other way: the frame gets no acceleration, and the smoothing
+    // forgets the movement before it
+    fn reversed(&mut self, args: &Args, x: f64, y: f64) -> bool {
+        let dist = (x * x + y * y).sqrt();
+        if dist == 0.0 {
+            return false;
+        }
+        let heading = (x / dist, y / dist);
+        let reversed = self.heading.is_some_and(|last| {
+            let cos = last.0 * heading.0 + last.1 * heading.1;
+            cos < args.reversal_angle.to_radians().cos()
+        });
+        self.heading = Some(heading);
+        if reversed {
+            self.x_filter = OneEuroFilter::new();
+            self.y_filter = OneEuroFilter::new();
+        }
+        reversed
+    }
+
+    // writes out part of a frame before it ends, at the last frame's speed since this one's isn't
+    // known yet, the other axes set as for process_frame
+    pub fn process_partial(&mut self, args: &Args, x: f64, y: f64) -> (i32, i32) {
+        let (x, y) = swapped(args, x, y);
+        let sensitivity = self.sensitivity_at(args, self.speed);
+        self.round_axes(args, sensitivity);
+        self.flushed_axes.resize(self.axes_in.len(), 0.0);
+        for (flushed, value) in self.flushed_axes.iter_mut().zip(&self.axes_in) {
+            *flushed += value;
+        }
+        if args.static_friction.is_some() {
+            if let Some(ref mut held) = self.stuck {
+                held.0 += x;
+                held.1 += y;
+                return (0, 0);
+            }
+        }
+        self.flushed.0 += x;
+        self.flushed.1 += y;
+        let (x_out, y_out) = self.directed(args, x, y, sensitivity);
+        // the frame is being cut short, so whatever the averaging owes goes with it
+        let owed = self.output_average.release();
+        self.round(args, x_out + owed.0, y_out + owed.1, x != 0.0 || y != 0.0)
+    }
+
+    fn sensitivity_at(&mut self, args: &Args, speed: f64) -> f64 {
+        if !self.focused(args) {
+            return self.base_sensitivity(args);
+        }
+        let sensitivity = if self.locked {
+            args.lock_mult
+        } else if let Some(profile) = self.profile.and_then(|i| args.modifier_map.get(i)) {
+            sensitivity(
+                profile.1.sens_mult,
+                &profile.1.curve,
+                speed,
+                &mut self.broken_away,
+            )
+        } else if let (Some(offset), &Curve::Linear { accel, cap, .. }) =
+            (self.adapted_offset, &args.curve)
+        {
+            factor(args.sens_mult, accel, cap, offset, speed)
+        } else {
+            sensitivity(args.sens_mult, &args.curve, speed, &mut self.broken_away)
+        };
+        sensitivity * self.file_mults(args)
+    }
+
+    pub fn adapted_offset(&self) -> Option<f64> {
+        self.adapted_offset
+    }
+
+    // whether acceleration is off for the frame at time, being too soon after a flick ended; a
+    // flick during the cooldown doesn't start it over, so it always ends when it was going to
+    fn cooling_down(&mut self, args: &Args, speed: f64, time: TimeVal) -> bool {
+        let (Some(threshold), Some(cooldown)) = (args.flick_threshold, args.flick_cooldown) else {
+            return false;
+        };
+        let cooling = |accel: &Accelerator| {
+            !accel.flicking
+                && accel
+                    .flick_last
+                    .is_some_and(|last| elapsed_ms(last, time) < cooldown)
+        };
+        if speed <= threshold {
+            self.flicking = false;
+            return cooling(self);
+        }
+        if cooling(self) {
+            return true;
+        }
+        self.flicking = true;
+        self.flick_last = Some(time);
+        false
+    }
+
+    // moves the offset toward the percentile of speeds by a step for each frame, up for speeds
+    // above it and down for those below, sized so it settles where the two even out; a step is the
+    // frame's share of a second at the rate, so an outlier only moves it so far
+    fn adapt_offset(&mut self, args: &Args, speed: f64, change_ms: f64) {
+        let Curve::Linear { offset, .. } = args.curve else {
+            return;
+        };
+        let adapted = self.adapted_offset.get_or_insert(offset);
+        // standing still, or the first frame after it, isn't tracking at any speed
+        if speed == 0.0 || !(change_ms > 0.0 && change_ms <= IDLE_GAP_MS) {
+            return;
+        }
+        let step = args.adaptive_offset_rate * change_ms / 1000.0;
+        let percentile = args.adaptive_offset_percentile / 100.0;
+        *adapted = if speed > *adapted {
+            *adapted + step * percentile
+        } else {
+            *adapted - step * (1.0 - percentile)
+        }
+        .max(0.0);
+    }
+
+    // what a frame moving at speed gets, as measured with the speed metric before the input scale
+    pub fn sensitivity_for(&mut self, args: &Args, speed: f64) -> f64 {
+        let speed = match args.speed_metric {
+            SpeedMetric::CountsPerMs | SpeedMetric::CountsPerFrame => speed * args.input_scale,
+            SpeedMetric::EventsPerSec => speed,
+        };
+        self.sensitivity_at(args, speed)
+    }
+
+    // what sensitivity_at comes to without any acceleration
+    fn base_sensitivity(&mut self, args: &Args) -> f64 {
+        let base = if self.locked {
+            args.lock_mult
+        } else if let Some(profile) = self.profile.and_then(|i| args.modifier_map.get(i)) {
+            profile.1.sens_mult
+        } else {
+            args.sens_mult
+        };
+        base * self.file_mults(args)
+    }
+
+    // the multipliers other programs write to files
+    fn file_mults(&mut self, args: &Args) -> f64 {
+        let mut mults = 1.0;
+        for (path, mult) in [
+            (&args.dynamic_mult_file, &mut self.dynamic_mult),
+            (&args.fov_file, &mut self.fov_mult),
+        ] {
+            if let Some(path) = path {
+                let mult =
+                    mult.get_or_insert_with(|| WatchedFile::new(path.clone(), 1.0, parse_mult));
+                mults *= mult.value();
+            }
+        }
+        // whatever the desktop still multiplies movement by is divided back out
+        if let Some(ref path) = args.observed_gain_file {
+            mults /= self
+                .observed_gain
+                .get_or_insert_with(|| WatchedFile::new(path.clone(), 1.0, parse_gain))
+                .value();
+        }
+        mults
+    }
+
+    // whether the --focus-file says the game has focus, which it does until the file says
+    // otherwise; without it, acceleration is left off
+    fn focused(&mut self, args: &Args) -> bool {
+        let Some(ref path) = args.focus_file else {
+            return true;
+        };
+        self.focus
+            .get_or_insert_with(|| WatchedFile::new(path.clone(), true, parse_focus))
+            .value()
+    }
+
+    // takes what acceleration adds to the frame's dist raw counts out of the budget, which refills
+    // at budget counts a second up to a second's worth, and tapers the sensitivity down to the
+    // base for what's left once it runs out
+    fn budgeted(
+        &mut self,
+        args: &Args,
+        budget: f64,
+        sensitivity: f64,
+        dist: f64,
+        change_ms: f64,
+    ) -> f64 {
+        let base = self.base_sensitivity(args);
+        let left = self.budget_left.get_or_insert(budget);
+        *left = (*left + budget * change_ms.max(0.0) / 1000.0).min(budget);
+        // a curve dipping below the base doesn't pay anything back
+        let added = dist * (sensitivity - base).max(0.0);
+        if added <= *left {
+            *left -= added;
+            return sensitivity;
+        }
+        let tapered = base + *left / dist;
+        *left = 0.0;
+        tapered
+    }
+
+    // moves toward the sensitivity by at most slew a ms since the last frame, starting over from
+    // wherever it is after an idle gap
+    fn slewed(&mut self, slew: f64, sensitivity: f64, change_ms: f64) -> f64 {
+        let slewed = match self.slewed {
+            Some(last) if change_ms <= IDLE_GAP_MS => {
+                let step = slew * change_ms.max(0.0);
+                sensitivity.clamp(last - step, last + step)
+            }
+            _ => sensitivity,
+        };
+        self.slewed = Some(slewed);
+        slewed
+    }
+
+    // scales raw movement by sensitivity and the multiplier for the direction it's going in
+    fn directed(&mut self, args: &Args, x: f64, y: f64, sensitivity: f64) -> (f64, f64) {
+        let x_mult = direction_mult(args.x_pos_mult, args.x_neg_mult, x);
+        let y_mult = direction_mult(args.y_pos_mult, args.y_neg_mult, y);
+        // a remainder built up at one direction's multiplier shouldn't be paid out at the other's
+        if args.x_pos_mult != args.x_neg_mult && turned(&mut self.x_direction, x) {
+            self.x_accum = 0.0;
+        }
+        if args.y_pos_mult != args.y_neg_mult && turned(&mut self.y_direction, y) {
+            self.y_accum = 0.0;
+        }
+        (x * sensitivity * x_mult, y * sensitivity * y_mult)
+    }
+
+    // the other axes just get the sensitivity, nothing else here knows about them
+    fn round_axes(&mut self, args: &Args, sensitivity: f64) {
+        self.axes_accum.resize(self.axes_in.len(), 0.0);
+        self.axes_out = self
+            .axes_in
+            .iter()
+            .zip(&mut self.axes_accum)
+            .map(|(value, accum)| {
+                let moved = value * sensitivity + *accum;
+                let rounded = moved.round();
+                *accum = if args.no_accum { 0.0 } else { moved - rounded };
+                rounded as i32
+            })
+            .collect();
+    }
+
+    // turns accelerated movement into whole counts, carrying the rest to the next frame
+    fn round(&mut self, args: &Args, x: f64, y: f64, moved: bool) -> (i32, i32) {
+        let (frame_x, frame_y) = (x, y);
+        let x = x + self.x_accum;
+        let y = y + self.y_accum;
+
+        let mut x_rounded = x.round() as i32;
+        let mut y_rounded = y.round() as i32;
+        if args.min_move {
+            // the accumulator takes the overshoot, so it's paid back by later frames holding still
+            // rather than by moving back the other way
+            if x_rounded != 0 && x_rounded as f64 * frame_x <= 0.0 {
+                x_rounded = 0;
+            }
+            if y_rounded != 0 && y_rounded as f64 * frame_y <= 0.0 {
+                y_rounded = 0;
+            }
+            // an axis still paying it back isn't nudged again
+            let (x_free, y_free) = (x * frame_x > 0.0, y * frame_y > 0.0);
+            if moved && x_rounded == 0 && y_rounded == 0 {
+                if x_free && (x.abs() >= y.abs() || !y_free) {
+                    x_rounded = x.signum() as i32;
+                } else if y_free {
+                    y_rounded = y.signum() as i32;
+                }
+            }
+        }
+        // without carrying, slow movement shows up right away but fractions are lost for good
+        if args.no_accum {
+            return (x_rounded, y_rounded);
+        }
+        self.x_accum = self.clamp_accum(x - x_rounded as f64, args.accum_bound);
+        self.y_accum = self.clamp_accum(y - y_rounded as f64, args.accum_bound);
+
+        (x_rounded, y_rounded)
+    }
+
+    // rounding keeps the accumulators within half a count (a whole one with min-move), so going
+    // past the bound means something fed them without rounding
+    fn clamp_accum(&mut self, accum: f64, bound: f64) -> f64 {
+        if accum.abs() <= bound {
+            return accum;
+        }
+        if !self.clamp_warned {
+            eprintln!(
+                "Warning: clamped accumulator at {} to {}, this is a bug",
+                accum, bound
+            );
+            self.clamp_warned = true;
+        }
+        accum.clamp(-bound, bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        fs::{self, File},
+        path::Path,
+        time::{Duration, SystemTime},
+    };
+
+    use evdev_rs::TimeVal;
+
+    use super::{breakaway, discontinuities, parse_gain, Accelerator};
+    use crate::{
+        args::{parse_args, Curve},
+        curve_file,
+        testing::{accelerated, mock_args, smoothed_frames},
+    };
+
+    // frames of uneven movement and timing come out at the same total averaged as not, once the
+    // averaging's let out what it owes, and a steady movement with every other frame noisy comes
+    // out steadier
+    #[test]
+    fn output_averaging() -> Result<(), String> {
+        let written = |average: &str| {
+            let args = mock_args(&format!("-m 1 -a 0.1 {}", average))?;
+            let mut accel = Accelerator::new();
+            accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+            let mut total = (0, 0);
+            let mut us = 0;
+            for i in 0..500 {
+                us += (1 + i % 3) * 1000;
+                let time = TimeVal::new(1, us);
+                let (x, y) = ((i * 7 % 11 - 3) as f64, (i * 5 % 4) as f64);
+                let (x, y) = accel.process_frame(&args, x, y, 2, None, time);
+                total = (total.0 + x, total.1 + y);
+            }
+            let (x, y) = accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, us));
+            Ok::<_, String>((total.0 + x, total.1 + y))
+        };
+        let plain = written("")?;
+        let averaged = written("--output-average 4")?;
+        // the rounding remainders can end up a count apart
+        if (plain.0 - averaged.0).abs() > 1 || (plain.1 - averaged.1).abs() > 1 {
+            return Err(format!(
+                "{:?} written averaged, {:?} without",
+                averaged, plain
+            ));
+        }
+
+        let noisy: Vec<_> = (0..40).map(|i| if i % 2 == 0 { 2 } else { 8 }).collect();
+        let averaged = smoothed_frames("-m 1 -a 0 --output-average 4", &noisy)?;
+        if averaged[10..].iter().any(|&x| x != 5) {
+            return Err(format!("noisy movement came out as {:?}", &averaged[10..]));
+        }
+        Ok(())
+    }
+
+    // slow wandering movement held back by static friction again after every pause, rounded with
+    // and without min-move and at uneven direction multipliers, never leaves the accumulators past
+    // their bound or has them clamped, and going past it is clamped
+    #[test]
+    fn accum_bound() -> Result<(), String> {
+        let option_sets = [
+            "-m 0.3 -a 0.05 --static-friction 4",
+            "-m 0.3 -a 0.05 --static-friction 4 --min-move",
+            "-m 0.7 -a 0 --x-pos-mult 1.3 --x-neg-mult 0.6 --accum-bound 0.5",
+        ];
+        for options in option_sets {
+            let args = mock_args(options)?;
+            let mut accel = Accelerator::new();
+            let (mut seed, mut ms) = (1u64, 0);
+            for frame in 0..2000 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let x = (seed >> 33) as f64 % 5.0 - 2.0;
+                let y = (seed >> 45) as f64 % 3.0 - 1.0;
+                ms += if frame % 50 == 0 { 300 } else { 1 };
+                let time = TimeVal::new(1 + ms / 1000, ms % 1000 * 1000);
+                accel.process_frame(&args, x, y, 2, None, time);
+                let largest = accel.x_accum.abs().max(accel.y_accum.abs());
+                if largest > args.accum_bound || accel.clamp_warned {
+                    return Err(format!(
+                        "{:?}: accumulator at {} after frame {}",
+                        options, largest, frame
+                    ));
+                }
+            }
+        }
+        let mut accel = Accelerator::new();
+        if accel.clamp_accum(-3.0, 1.0) != -1.0 || !accel.clamp_warned {
+            return Err("-3 wasn't clamped to -1".to_string());
+        }
+        Ok(())
+    }
+
+    // right gets 1.4x and left 0.5x, the 0.4 left over going right not carried into moving left,
+    // where the halves add up to exactly what 0.5x makes of it; up and down get theirs the same
+    #[test]
+    fn direction_mults() -> Result<(), String> {
+        let options = "-m 1 -a 0 --x-pos-mult 1.4 --x-neg-mult 0.5";
+        let written = accelerated(
+            options,
+            &[(1.0, 0.0), (-1.0, 0.0), (-1.0, 0.0), (-2.0, 0.0)],
+        )?;
+        let expected = [(1, 0), (-1, 0), (0, 0), (-1, 0)];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        let options = "-m 1 -a 0 --y-pos-mult 2 --y-neg-mult 3";
+        let written = accelerated(options, &[(0.0, 2.0), (0.0, -2.0)])?;
+        if written != [(0, 4), (0, -6)] {
+            return Err(format!("expected [(0, 4), (0, -6)], got {:?}", written));
+        }
+        Ok(())
+    }
+
+    // 0.6 of a count a frame rounds up every frame on its own and 0.4 never moves, where carrying
+    // the remainder moves them 3 and 2 counts over five frames, and the accumulators stay at 0
+    #[test]
+    fn no_accum() -> Result<(), String> {
+        for (mult, each, carried) in [("0.6", 1, [1, 0, 1, 0, 1]), ("0.4", 0, [0, 1, 0, 1, 0])] {
+            let options = format!("-m {} -a 0", mult);
+            let written = accelerated(&options, &[(1.0, 0.0); 5])?;
+            let written: Vec<_> = written.iter().map(|&(x, _)| x).collect();
+            if written != carried {
+                return Err(format!(
+                    "expected {:?} at {}x, got {:?}",
+                    carried, mult, written
+                ));
+            }
+            let args = mock_args(&format!("{} --no-accum", options))?;
+            let mut accel = Accelerator::new();
+            for ms in 1..=5 {
+                let time = TimeVal::new(1, ms * 1000);
+                let written = accel.process_frame(&args, 1.0, -1.0, 2, None, time);
+                if written != (each, -each) || accel.x_accum != 0.0 || accel.y_accum != 0.0 {
+                    return Err(format!(
+                        "{} at {}x without accumulating, accumulators at {} {}",
+                        written.0, mult, accel.x_accum, accel.y_accum
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 3 and 4 counts in two events over 2ms is 5 counts, 2.5 a ms or 1000 events a second, a
+    // frame at the same time as the last has no speed unless it's counted per frame, and the input
+    // scale scales the counts
+    #[test]
+    fn speed_metrics() -> Result<(), String> {
+        let cases = [
+            ("counts-per-ms", 2.5, 0.0),
+            ("counts-per-frame", 5.0, 5.0),
+            ("events-per-sec", 1000.0, 0.0),
+            ("counts-per-ms --input-scale 2", 5.0, 0.0),
+        ];
+        for (metric, expected, same_time) in cases {
+            let args = mock_args(&format!("-m 1 -a 0 --speed-metric {}", metric))?;
+            let mut accel = Accelerator::new();
+            accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+            accel.process_frame(&args, 3.0, 4.0, 2, None, TimeVal::new(1, 2000));
+            let speed = accel.speed;
+            accel.process_frame(&args, 3.0, 4.0, 2, None, TimeVal::new(1, 2000));
+            if speed != expected || accel.speed != same_time {
+                return Err(format!(
+                    "{}: expected {} then {}, got {} then {}",
+                    metric, expected, same_time, speed, accel.speed
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // speed hovering a count a ms either side of 10 makes the multiplier flip between 1.9 and 2.1,
+    // which a hysteresis of 2 holds at 2 until the speed really moves; either way every count is
+    // written scaled by the multiplier it got, 137.9 and 137.5 in all
+    #[test]
+    fn speed_hysteresis() -> Result<(), String> {
+        let hovering = [10.0, 11.0, 9.0, 11.0, 9.0, 15.0];
+        let cases = [
+            ("", [2.0, 2.1, 1.9, 2.1, 1.9, 2.5], 138),
+            ("--speed-hysteresis 2", [2.0, 2.0, 2.0, 2.0, 2.0, 2.5], 138),
+        ];
+        for (options, expected, expected_total) in cases {
+            let args = mock_args(&format!("-m 1 -a 0.1 {}", options))?;
+            let mut accel = Accelerator::new();
+            accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+            let mut sensitivities = Vec::new();
+            let mut total = 0;
+            for (ms, &x) in (1..).zip(&hovering) {
+                let (moved, _) =
+                    accel.process_frame(&args, x, 0.0, 1, None, TimeVal::new(1, ms * 1000));
+                sensitivities.push((accel.sensitivity * 1000.0).round() / 1000.0);
+                total += moved;
+            }
+            if sensitivities != expected {
+                return Err(format!(
+                    "{}: expected {:?}, got {:?}",
+                    options, expected, sensitivities
+                ));
+            }
+            if total != expected_total {
+                return Err(format!(
+                    "{}: expected {} moved, got {}",
+                    options, expected_total, total
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // with 3 counts of friction a count a ms is held for two frames and comes through together with
+    // the third, then passes as it is; 200ms without movement holds the next movement back again
+    #[test]
+    fn static_friction() -> Result<(), String> {
+        let args = mock_args("-m 1 -a 0 --static-friction 3")?;
+        let mut accel = Accelerator::new();
+        accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+        let steps = [1, 2, 3, 4, 204, 205, 206, 207];
+        let written: Vec<_> = steps
+            .iter()
+            .map(|&ms| {
+                accel
+                    .process_frame(&args, 1.0, 0.0, 1, None, TimeVal::new(1, ms * 1000))
+                    .0
+            })
+            .collect();
+        let expected = [0, 0, 3, 1, 0, 0, 3, 1];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        Ok(())
+    }
+
+    // a sweep right at 10 counts a ms builds up 1 + 0.1 * 10, turning back gets no acceleration
+    // for the frame turning and builds it up again after, and a turn of 53 degrees only counts as
+    // reversing against a reversal angle below it
+    #[test]
+    fn directional_accel() -> Result<(), String> {
+        let sweep = [(10.0, 0.0), (10.0, 0.0), (-10.0, 0.0), (-10.0, 0.0)];
+        let cases = [
+            (
+                "-m 1 -a 0.1",
+                &sweep,
+                [(20, 0), (20, 0), (-20, 0), (-20, 0)],
+            ),
+            (
+                "-m 1 -a 0.1 --directional-accel",
+                &sweep,
+                [(20, 0), (20, 0), (-10, 0), (-20, 0)],
+            ),
+        ];
+        for (options, frames, expected) in cases {
+            let written = accelerated(options, frames)?;
+            if written != expected {
+                return Err(format!(
+                    "{}: expected {:?}, got {:?}",
+                    options, expected, written
+                ));
+            }
+        }
+        let turn = [(10.0, 0.0), (6.0, 8.0)];
+        let cases = [
+            ("-m 1 -a 0.1 --directional-accel", [(20, 0), (12, 16)]),
+            (
+                "-m 1 -a 0.1 --directional-accel --reversal-angle 45",
+                [(20, 0), (6, 8)],
+            ),
+        ];
+        for (options, expected) in cases {
+            let written = accelerated(options, &turn)?;
+            if written != expected {
+                return Err(format!(
+                    "{}: expected {:?}, got {:?}",
+                    options, expected, written
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // halving the input scale halves the speed the curve sees, so 20 counts a ms past an offset of 5
+    // gets 1 + 0.1 * (10 - 5) rather than 1 + 0.1 * (20 - 5), while without acceleration the 20
+    // come out the same whatever the scale
+    #[test]
+    fn input_scale() -> Result<(), String> {
+        let cases = [
+            ("-m 1 -a 0.1 -o 5", 50),
+            ("-m 1 -a 0.1 -o 5 --input-scale 0.5", 30),
+            ("-m 1 -a 0", 20),
+            ("-m 1 -a 0 --input-scale 0.5", 20),
+            ("-m 1 -a 0 --input-scale 8", 20),
+        ];
+        for (options, expected) in cases {
+            let written = accelerated(options, &[(20.0, 0.0)])?;
+            if written != [(expected, 0)] {
+                return Err(format!(
+                    "{}: expected {}, got {:?}",
+                    options, expected, written
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // a quarter of a count a frame moves right away with --min-move, then holds still while the
+    // overshoot's paid back, instead of waiting for half a count to build up first
+    #[test]
+    fn min_move() -> Result<(), String> {
+        let slow = |x: f64| [(x, 0.0); 8];
+        let expected = [(1, 0), (0, 0), (0, 0), (0, 0)].repeat(2);
+        let written = accelerated("-m 0.25 -a 0 --min-move", &slow(1.0))?;
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        let backward: Vec<_> = expected.iter().map(|&(x, y)| (-x, y)).collect();
+        let written = accelerated("-m 0.25 -a 0 --min-move", &slow(-1.0))?;
+        if written != backward {
+            return Err(format!(
+                "expected {:?} moving back, got {:?}",
+                backward, written
+            ));
+        }
+        let expected = [(0, 0), (1, 0), (0, 0), (0, 0)].repeat(2);
+        let written = accelerated("-m 0.25 -a 0", &slow(1.0))?;
+        if written != expected {
+            return Err(format!(
+                "expected {:?} without it, got {:?}",
+                expected, written
+            ));
+        }
+        Ok(())
+    }
+
+    // a slow sensor jittering between 1 and 5 comes out at a steady 3 through the one euro filter,
+    // and a higher beta lets it catch up with a sudden fast movement sooner
+    #[test]
+    fn one_euro() -> Result<(), String> {
+        let noisy: Vec<_> = (0..2000).map(|i| if i % 2 == 0 { 1 } else { 5 }).collect();
+        let filtered = smoothed_frames("-m 1 -a 0 --one-euro", &noisy)?;
+        if filtered[1500..].iter().any(|&x| x != 3) {
+            return Err(format!("jitter came out as {:?}", &filtered[1500..1520]));
+        }
+        let step = [20; 10];
+        let slow = smoothed_frames("-m 1 -a 0 --one-euro --beta 0", &step)?;
+        let quick = smoothed_frames("-m 1 -a 0 --one-euro --beta 1", &step)?;
+        if slow.iter().sum::<i32>() > 10 || quick[4..].iter().any(|&x| x < 19) {
+            return Err(format!(
+                "20 a frame came out as {:?} with a beta of 0, {:?} with 1",
+                slow, quick
+            ));
+        }
+        Ok(())
+    }
+
+    // 10 counts a ms on each axis against limits of 5 and 2 comes out at the limits, flatter than it
+    // went in, and what's cut off carries on coming out at the limits until it's all been moved
+    #[test]
+    fn per_axis_speed_limit() -> Result<(), String> {
+        let mut frames = vec![(10.0, 10.0); 3];
+        frames.resize(15, (0.0, 0.0));
+        let out = accelerated("-m 1 -a 0 --max-x-speed 5 --max-y-speed 2", &frames)?;
+        if out[..3] != [(5, 2); 3] {
+            return Err(format!(
+                "expected the movement at the limits, got {:?}",
+                out
+            ));
+        }
+        let (x, y) = out
+            .iter()
+            .fold((0, 0), |(x, y), &(dx, dy)| (x + dx, y + dy));
+        if out.iter().any(|&(x, y)| x > 5 || y > 2) || (x, y) != (30, 30) {
+            return Err(format!(
+                "expected 30 on each axis within the limits, got {:?}",
+                out
+            ));
+        }
+        Ok(())
+    }
+
+    // with --preserve-direction, 10 right and 4 down a ms against a horizontal limit of 5 is scaled
+    // down on both to 5 and 2, and what's cut off comes out the same way
+    #[test]
+    fn direction_preserving_speed_limit() -> Result<(), String> {
+        let mut frames = vec![(10.0, 4.0); 3];
+        frames.resize(8, (0.0, 0.0));
+        let out = accelerated("-m 1 -a 0 --max-x-speed 5 --preserve-direction", &frames)?;
+        let expected = [
+            (5, 2),
+            (5, 2),
+            (5, 2),
+            (5, 2),
+            (5, 2),
+            (5, 2),
+            (0, 0),
+            (0, 0),
+        ];
+        if out != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, out));
+        }
+        Ok(())
+    }
+
+    // breaking away at 10 counts per ms with a hysteresis of 2: once broken away, speeds in the
+    // band from 8 to 10 keep the cap and only dropping below 8 falls back, after which the band
+    // doesn't break away again until 10
+    #[test]
+    fn breakaway_hysteresis() -> Result<(), String> {
+        let steps = [
+            (9.0, 1.0),
+            (10.5, 3.0),
+            (9.0, 3.0),
+            (8.1, 3.0),
+            (8.0, 3.0),
+            (7.9, 1.0),
+            (8.1, 1.0),
+            (9.9, 1.0),
+            (10.0, 3.0),
+        ];
+        let mut broken_away = false;
+        for (i, (speed, expected)) in steps.into_iter().enumerate() {
+            let sensitivity = breakaway(1.0, 10.0, 3.0, 0.0, 2.0, speed, &mut broken_away);
+            if sensitivity != expected {
+                return Err(format!(
+                    "step {} at {}: expected {}, got {}",
+                    i + 1,
+                    speed,
+                    expected,
+                    sensitivity
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // speed stepping from 1 to 20 counts per ms takes the sensitivity from 1.1 up to the 3 it caps
+    // at by 0.25 a ms, and after an idle gap it goes straight to what the curve gives
+    #[test]
+    fn mult_slew() -> Result<(), String> {
+        let args = mock_args("-m 1 -a 0.1 -c 3 --max-mult-slew 0.25")?;
+        let mut accel = Accelerator::new();
+        accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+        let mut sensitivities = Vec::new();
+        for ms in 1..=12 {
+            let x = if ms <= 2 { 1.0 } else { 20.0 };
+            accel.process_frame(&args, x, 0.0, 1, None, TimeVal::new(1, ms * 1000));
+            sensitivities.push(accel.sensitivity);
+        }
+        let expected = [
+            1.1, 1.1, 1.35, 1.6, 1.85, 2.1, 2.35, 2.6, 2.85, 3.0, 3.0, 3.0,
+        ];
+        if sensitivities
+            .iter()
+            .zip(expected)
+            .any(|(sensitivity, expected)| (sensitivity - expected).abs() > 1e-9)
+        {
+            return Err(format!(
+                "expected the sensitivity to go {:?}, went {:?}",
+                expected, sensitivities
+            ));
+        }
+        // 20 counts over 200ms is 0.1 counts per ms
+        accel.process_frame(&args, 20.0, 0.0, 1, None, TimeVal::new(1, 212_000));
+        if (accel.sensitivity - 1.01).abs() > 1e-9 {
+            return Err(format!(
+                "expected 1.01 after an idle gap, got {}",
+                accel.sensitivity
+            ));
+        }
+        Ok(())
+    }
+
+    // speeds spread evenly from 2 to 10 counts per ms should bring the offset from 0 to their
+    // median of 6, without it ever moving more than a step a frame or leaving the range
+    #[test]
+    fn adaptive_offset() -> Result<(), String> {
+        let args = mock_args("-m 1 -a 0.1 --adaptive-offset --adaptive-offset-rate 2")?;
+        let mut accel = Accelerator::new();
+        accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+        let mut last = 0.0;
+        for i in 1..=20_000i64 {
+            let speed = (i * 7 % 9 + 2) as f64;
+            let time = TimeVal::new(1 + i / 1000, i % 1000 * 1000);
+            accel.process_frame(&args, speed, 0.0, 1, None, time);
+            let offset = accel.adapted_offset().ok_or("no adapted offset")?;
+            // a step at 2 counts per ms a second over 1ms
+            if (offset - last).abs() > 0.002 + 1e-9 || !(0.0..=10.0).contains(&offset) {
+                return Err(format!(
+                    "frame {}: offset jumped from {} to {}",
+                    i, last, offset
+                ));
+            }
+            last = offset;
+        }
+        if (last - 6.0).abs() > 0.5 {
+            return Err(format!("expected an offset near 6, got {}", last));
+        }
+        Ok(())
+    }
+
+    // --sens-at, worked out by hand from each curve's formula
+    #[test]
+    fn sens_at() -> Result<(), String> {
+        let cases: [(&[&str], f64, f64); 6] = [
+            // 2 * min(1 + 0.1 * (speed - 2), 3)
+            (&["-m", "2", "-a", "0.1", "-c", "3", "-o", "2"], 1.0, 2.0),
+            (&["-m", "2", "-a", "0.1", "-c", "3", "-o", "2"], 12.0, 4.0),
+            (&["-m", "2", "-a", "0.1", "-c", "3", "-o", "2"], 40.0, 6.0),
+            // the input scale doubles the speed the curve sees
+            (&["-m", "1", "-a", "0.1", "--input-scale", "2"], 5.0, 2.0),
+            (&["-m", "1", "--expr", "1 + s/10"], 12.0, 2.2),
+            (&["-m", "1", "--breakaway", "5", "-c", "2"], 6.0, 2.0),
+        ];
+        for (options, speed, expected) in cases {
+            let options = options.iter().copied().chain(["selftest-device"]);
+            let args = parse_args(options.map(OsString::from).collect())
+                .map_err(|err| format!("bad options: {}", err))?;
+            let sensitivity = Accelerator::new().sensitivity_for(&args, speed);
+            if (sensitivity - expected).abs() > 1e-9 {
+                return Err(format!(
+                    "at {} expected {}, got {}",
+                    speed, expected, sensitivity
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // writes contents with the date given, so the change is seen however coarse file times are
+    fn write_dated(path: &Path, contents: &str, dated: SystemTime) -> Result<(), String> {
+        fs::write(path, contents).map_err(|err| err.to_string())?;
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(dated))
+            .map_err(|err| err.to_string())
+    }
+
+    // each file other programs write is read again as it changes, a second apart, and one caught
+    // partway through being written (empty, or not parsing yet) keeps the last value; the
+    // sensitivities are for 10 counts a ms
+    #[test]
+    fn watched_files() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-watched-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let result = (|| {
+            let doubled = dir.join("doubled");
+            fs::write(&doubled, "2").map_err(|err| err.to_string())?;
+            // what's written to the file, and the sensitivity each write brings
+            type Steps = &'static [(&'static str, f64)];
+            let cases: [(String, &str, Steps); 4] = [
+                // a flat 1x scaled by whatever the file says
+                (
+                    "-m 1 -a 0".to_string(),
+                    "--dynamic-mult-file",
+                    &[
+                        ("2", 2.0),
+                        ("0.5", 0.5),
+                        ("", 0.5),
+                        ("1.", 1.0),
+                        ("-", 1.0),
+                        ("3", 3.0),
+                    ],
+                ),
+                // 2 from the curve and 2 from the dynamic multiplier, then whatever the zoom
+                (
+                    format!("-m 1 -a 0.1 --dynamic-mult-file {}", doubled.display()),
+                    "--fov-file",
+                    &[("1", 4.0), ("0.5", 2.0), ("", 2.0), ("0.25", 1.0)],
+                ),
+                // 2 from the curve, and 1 with acceleration off while unfocused
+                (
+                    "-m 1 -a 0.1".to_string(),
+                    "--focus-file",
+                    &[("focused", 2.0), ("unfocused", 1.0), ("focused", 2.0)],
+                ),
+                // a desktop found to move the pointer 1.5 pixels a count gets two thirds of the
+                // sensitivity and one moving it half a pixel a count twice
+                (
+                    "-m 2 -a 0".to_string(),
+                    "--observed-gain-file",
+                    &[("1000 1500", 4.0 / 3.0), ("1000 500", 4.0), ("", 4.0)],
+                ),
+            ];
+            for (options, flag, steps) in cases {
+                let path = dir.join(flag.trim_start_matches('-'));
+                let args = mock_args(&format!("{} {} {}", options, flag, path.display()))?;
+                let mut accel = Accelerator::new();
+                let start = SystemTime::now();
+                for (i, &(contents, expected)) in steps.iter().enumerate() {
+                    write_dated(&path, contents, start + Duration::from_secs(i as u64))?;
+                    let sensitivity = accel.sensitivity_for(&args, 10.0);
+                    if (sensitivity - expected).abs() > 1e-9 {
+                        return Err(format!(
+                            "{} '{}': expected sensitivity {}, got {}",
+                            flag, contents, expected, sensitivity
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    // what a desktop is found to move the pointer by, pixels against counts, and nothing for what
+    // doesn't make sense as a gain
+    #[test]
+    fn observed_gain() -> Result<(), String> {
+        for (contents, expected) in [
+            ("1000 1500", Some(1.5)),
+            ("1000 500.0", Some(0.5)),
+            (" 200   200 ", Some(1.0)),
+            ("1000 0", None),
+            ("-1000 -1500", None),
+            ("1000", None),
+            ("1000 1500 2000", None),
+            ("1000 many", None),
+        ] {
+            let gain = parse_gain(contents.trim());
+            if gain != expected {
+                return Err(format!(
+                    "'{}': expected {:?}, got {:?}",
+                    contents, expected, gain
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // a linear segment up to 10 counts/ms then an expression carrying on from where it leaves off
+    // each give their own sensitivity, anything past the last segment gets the last, and a flat
+    // segment after the linear one is found not to meet it
+    #[test]
+    fn segmented_curve() -> Result<(), String> {
+        let linear = r#"{"from": 0, "type": "linear", "accel": 0.1}"#;
+        let options = [
+            "-m",
+            "2",
+            "--segment",
+            linear,
+            "--segment",
+            r#"{"from": 10, "type": "expr", "expr": "2 + (s - 10)/20"}"#,
+            "selftest-device",
+        ];
+        let args = parse_args(options.map(OsString::from).to_vec())
+            .map_err(|err| format!("bad options: {}", err))?;
+        let Curve::Segmented(ref segments) = args.curve else {
+            return Err("the curve isn't segmented".to_string());
+        };
+        if !discontinuities(segments).is_empty() {
+            return Err(format!("{:?} at the seam", discontinuities(segments)));
+        }
+        let mut accel = Accelerator::new();
+        for (speed, expected) in [(0.0, 2.0), (5.0, 3.0), (10.0, 4.0), (30.0, 6.0)] {
+            let sensitivity = accel.sensitivity_for(&args, speed);
+            if (sensitivity - expected).abs() > 1e-9 {
+                return Err(format!(
+                    "at {} counts/ms: expected sensitivity {}, got {}",
+                    speed, expected, sensitivity
+                ));
+            }
+        }
+
+        let flat = curve_file::segmented(vec![
+            (
+                0.0,
+                Curve::Linear {
+                    accel: 0.1,
+                    cap: f64::INFINITY,
+                    offset: 0.0,
+                },
+            ),
+            (
+                10.0,
+                Curve::Linear {
+                    accel: 0.0,
+                    cap: f64::INFINITY,
+                    offset: 0.0,
+                },
+            ),
+        ])?;
+        let Curve::Segmented(ref segments) = flat else {
+            return Err("the flat curve isn't segmented".to_string());
+        };
+        match discontinuities(segments)[..] {
+            [(from, below, above)]
+                if from == 10.0 && (below - 2.0).abs() < 1e-9 && (above - 1.0).abs() < 1e-9 =>
+            {
+                Ok(())
+            }
+            ref found => Err(format!(
+                "expected a jump from 2 to 1 at 10, got {:?}",
+                found
+            )),
+        }
+    }
+}
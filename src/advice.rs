@@ -0,0 +1,128 @@
+use evdev_rs::{Device, DeviceWrapper};
+
+const BUS_USB: u16 = 0x03;
+
+// what's needed to pick the virtual device out in compositor and X configs, it copies all of
+// this from the source device
+pub struct DeviceMetadata {
+    pub name: String,
+    pub bustype: u16,
+    pub vendor: u16,
+    pub product: u16,
+}
+
+impl DeviceMetadata {
+    pub fn from_device(device: &Device) -> DeviceMetadata {
+        DeviceMetadata {
+            name: device.name().unwrap_or_default().to_string(),
+            bustype: device.bustype(),
+            vendor: device.vendor_id(),
+            product: device.product_id(),
+        }
+    }
+}
+
+// config snippets for switching the virtual device to libinput's flat profile, so this is the
+// only thing accelerating it
+pub fn libinput_advice(device: &DeviceMetadata) -> String {
+    let mut advice = format!(
+        "# libinput accelerates the virtual device too unless its profile is set to flat\n\
+         # (device: \"{}\", {:04x}:{:04x})\n",
+        device.name, device.vendor, device.product
+    );
+
+    advice += "\n# X11, in /etc/X11/xorg.conf.d/99-accelerator.conf\n";
+    advice += "Section \"InputClass\"\n";
+    advice += "    Identifier \"accelerator flat profile\"\n";
+    advice += &format!("    MatchProduct \"{}\"\n", escape(&device.name, '"'));
+    if device.bustype == BUS_USB {
+        advice += &format!(
+            "    MatchUSBID \"{:04x}:{:04x}\"\n",
+            device.vendor, device.product
+        );
+    }
+    advice += "    MatchIsPointer \"on\"\n";
+    advice += "    Driver \"libinput\"\n";
+    advice += "    Option \"AccelProfile\" \"flat\"\n";
+    advice += "EndSection\n";
+
+    advice += "\n# X11, for the current session only\n";
+    advice += &format!(
+        "xinput set-prop 'pointer:{}' 'libinput Accel Profile Enabled' 0 1\n",
+        escape(&device.name, '\'')
+    );
+
+    // sway names devices vendor:product:name, in decimal with whitespace replaced
+    advice += "\n# sway, in ~/.config/sway/config\n";
+    advice += &format!(
+        "input \"{}:{}:{}\" accel_profile flat\n",
+        device.vendor,
+        device.product,
+        escape(&device.name.replace(char::is_whitespace, "_"), '"')
+    );
+
+    // gnome and kde only have a setting for all mice
+    advice += "\n# GNOME (applies to every mouse)\n";
+    advice += "gsettings set org.gnome.desktop.peripherals.mouse accel-profile 'flat'\n";
+    advice
+}
+
+fn escape(name: &str, quote: char) -> String {
+    if quote == '\'' {
+        name.replace('\'', "'\\''")
+    } else {
+        name.replace('\\', "\\\\")
+            .replace(quote, &format!("\\{}", quote))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{libinput_advice, DeviceMetadata};
+
+    // quotes in the name are escaped for each config's quoting, and only a usb device is matched
+    // by its usb id
+    #[test]
+    fn snippets() -> Result<(), String> {
+        let mut device = DeviceMetadata {
+            name: "Logi \"MX\" Master's 3".to_string(),
+            bustype: 0x03,
+            vendor: 0x046d,
+            product: 0x4082,
+        };
+        let expected = r#"# libinput accelerates the virtual device too unless its profile is set to flat
+# (device: "Logi "MX" Master's 3", 046d:4082)
+
+# X11, in /etc/X11/xorg.conf.d/99-accelerator.conf
+Section "InputClass"
+    Identifier "accelerator flat profile"
+    MatchProduct "Logi \"MX\" Master's 3"
+    MatchUSBID "046d:4082"
+    MatchIsPointer "on"
+    Driver "libinput"
+    Option "AccelProfile" "flat"
+EndSection
+
+# X11, for the current session only
+xinput set-prop 'pointer:Logi "MX" Master'\''s 3' 'libinput Accel Profile Enabled' 0 1
+
+# sway, in ~/.config/sway/config
+input "1133:16514:Logi_\"MX\"_Master's_3" accel_profile flat
+
+# GNOME (applies to every mouse)
+gsettings set org.gnome.desktop.peripherals.mouse accel-profile 'flat'
+"#;
+        let advice = libinput_advice(&device);
+        if advice != expected {
+            return Err(format!("expected:\n{}\ngot:\n{}", expected, advice));
+        }
+        // bluetooth
+        device.bustype = 0x05;
+        let expected = expected.replace("    MatchUSBID \"046d:4082\"\n", "");
+        let advice = libinput_advice(&device);
+        if advice != expected {
+            return Err(format!("expected:\n{}\ngot:\n{}", expected, advice));
+        }
+        Ok(())
+    }
+}
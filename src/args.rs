@@ -0,0 +1,1821 @@
+use std::{
+    ffi::OsString,
+    net::{SocketAddr, ToSocketAddrs},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use evdev_rs::enums::{EventCode, EventType, EV_REL};
+use pico_args::Arguments;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    accel::discontinuities,
+    backend::pattern::{parse_pattern, Pattern},
+    config,
+    curve_file::{segmented, CurveFile, SegmentDef},
+    expr::{self, Expr},
+    registry,
+    velocity::VelocityMap,
+};
+
+pub const HELP: &str = r#"
+USAGE: accelerator [OPTIONS] <device-file>
+       accelerator [OPTIONS] --fuzz SEED
+       accelerator [OPTIONS] --print-libinput-advice <device-file>
+       accelerator [OPTIONS] --export-curve PATH
+       accelerator [OPTIONS] --benchmark-curves
+       accelerator [OPTIONS] --sens-at SPEED
+       accelerator [OPTIONS] --test-pattern PATTERN
+       accelerator [OPTIONS] --demo-curve
+       accelerator [OPTIONS] --replay RECORDING
+       accelerator [OPTIONS] --calibrate-gain PATH
+       accelerator --selftest
+       accelerator --benchmark-writes
+
+OPTIONS:
+  -m SENS_MULTIPLIER    The amount graph of sensitivity is scaled by
+                        Default: 1 with --expr, --velocity-map,
+                        --segment or --curve other than linear or
+                        breakaway, otherwise required
+  -a ACCELERATION       Slope of sensitivity graph before scaling
+                        Default: 0.1 with --curve linear, otherwise
+                        required
+  -c SENS_CAP           Sets the maximum sensitivity
+                        Default: infinity
+  --cap-mode MODE       How -c caps the sensitivity: relative caps the
+                        curve before -m scales it, so the most is
+                        SENS_MULTIPLIER * SENS_CAP, absolute caps what
+                        it comes to after, so the most is SENS_CAP
+                        Default: relative
+  -o INPUT_OFFSET       Maximum cursor speed before sensitivity
+                        begins increasing
+                        Default: 0
+  --clamp-offset-to-zero
+                        Treat a negative INPUT_OFFSET as 0
+  --curve TYPE          Use the linear, breakaway, expr, velocity-map or
+                        segmented curve with defaults for whichever of
+                        its parameters aren't given, to try one out
+                        with just -m, see README.md
+  --expr EXPRESSION     Use EXPRESSION of the speed s as the graph of
+                        sensitivity instead of -a, -c and -o, e.g.
+                        "min(1 + 0.05*(s-5), 3)"; knows + - * / ^ and
+                        min, max, abs, sqrt, exp, ln, pow and clamp
+  --velocity-map FILE   Use the input_speed,output_speed rows in FILE
+                        as the graph of output speed against input
+                        speed instead of -a, -c and -o, see README.md
+  --breakaway SPEED     Instead of -a and -o, don't accelerate at all
+                        below SPEED and jump straight to SENS_CAP (which
+                        is required without --curve breakaway) above
+                        it, for flicks
+  --breakaway-blend WIDTH
+                        Ramp up to SENS_CAP over WIDTH past SPEED
+                        instead of jumping
+                        Default: 0
+  --breakaway-hysteresis WIDTH
+                        Once past SPEED, only fall back to no
+                        acceleration below SPEED - WIDTH, so speed near
+                        SPEED doesn't flap between the two
+                        Default: SPEED / 10
+  --segment SEGMENT     One of the curves of a segmented curve, used from
+                        its speed up to the next's, as the JSON of a
+                        curve file's curve with a "from" speed (the
+                        first from 0); given once for each, in place of
+                        the other options setting the curve, see
+                        README.md
+  --screen-width PIXELS Horizontal resolution of the screen in use
+  --screen-height PIXELS
+                        Vertical resolution of the screen in use
+  --normalize-to PIXELS Resolution (along the same dimension) that the
+                        parameters were tuned on; sensitivity is scaled
+                        by screen / reference to keep the same feel
+  --min-move            Move at least one pixel along the dominant axis
+                        when movement rounds to zero, then hold still
+                        until the movement has caught up
+  --flush-on-click      When a button is pressed partway through a frame,
+                        write the movement so far before the press
+  --panic-keys KEYS     Ungrab the device and exit when all of KEYS,
+                        key names joined by + (e.g.
+                        KEY_LEFTCTRL+KEY_LEFTALT+KEY_BACKSPACE), are
+                        held on the device at once
+  --modifier-map KEY:CURVE_FILE
+                        While KEY (a key name like --panic-keys takes)
+                        is held on the device, use the multiplier and
+                        curve from CURVE_FILE (see --export-curve)
+                        instead; can be given more than once, with the
+                        first one given winning when several are held
+  --accum-bound COUNTS  Largest sub-pixel remainder carried between
+                        frames on each axis; anything past it is
+                        dropped with a warning
+                        Default: 1
+  --no-accum            Round every frame on its own without carrying
+                        the sub-pixel remainder to the next; slow
+                        movement responds sooner, but the fractions are
+                        lost so the cursor covers less distance overall
+  --anti-drift MS       Zero the sub-pixel remainders once no events at
+                        all have come from the device for MS, warning if
+                        any was more than rounding leaves, so a bug
+                        building up a bias can't creep the cursor along
+  --max-event-rate N    Once the device sends more than N events in a
+                        second, hold back movement for the rest of the
+                        second and write it all as one frame, to keep a
+                        flooding device from taking up the CPU
+                        Default: no limit
+  --min-report-interval MS
+                        Write movement at most once every MS, adding up
+                        anything faster into the next frame written, for
+                        remote desktops and VMs that can't keep up with
+                        fast mice; buttons still go through right away
+                        Default: every frame
+  --path-length-speed   Work out the speed of a frame coalesced from
+                        several reports (with --max-event-rate or
+                        --min-report-interval) from how far each of them
+                        moved, so curves aren't slowed down by the frame
+                        only going from where the first started to
+                        where the last ended
+  --power-supply PATH   Check the battery of a wireless mouse, at its
+                        directory in /sys/class/power_supply, every 10s
+                        and warn when it starts or stops charging or
+                        runs low, since some mice change their report
+                        rate then
+  --strict              Warn about events from the device that break the
+                        protocol: movement left waiting for a
+                        SYN_REPORT, or SYN_REPORTs in a row with
+                        nothing between them
+  --strict-flush-after MS
+                        With --strict, end the frame of movement that's
+                        waited MS for a SYN_REPORT with a made up one
+                        Default: never, only warning after 50
+  --syn-timeout MS      End the frame of movement that's waited MS for a
+                        SYN_REPORT with a made up one, for virtual
+                        devices that don't send them reliably
+                        Default: never
+  --scroll-up-accel ACCELERATION
+                        Accelerate scrolling up by how many detents a
+                        second the wheel is turning, multiplying it by
+                        1 + ACCELERATION * detents per second
+                        Default: 0
+  --scroll-down-accel ACCELERATION
+                        The same for scrolling down
+                        Default: 0
+  --touchpad-mode       The device is a touchpad sending two finger
+                        scrolling as REL_WHEEL and REL_HWHEEL: accelerate
+                        it both ways on both axes with
+                        --touchpad-scroll-accel in place of the wheel
+                        options, its speed starting over whenever the
+                        fingers lift for 100ms
+  --touchpad-scroll-accel ACCELERATION
+                        Acceleration of touchpad scrolling, per detent a
+                        second, gentler than a wheel needs since
+                        touchpads scroll in a fast stream of small steps
+                        Default: 0.02
+  --smooth-scroll FRAMES
+                        Spread each wheel detent over FRAMES frames of
+                        hi-res scrolling 4ms apart, for smoother
+                        scrolling with wheels that only click, see
+                        README.md
+  --reset-accum-on-reload
+                        Start the sub-pixel remainder over from 0 on a
+                        reload; otherwise it carries over, scaled by the
+                        curve from before the reload and clamped to the
+                        new --accum-bound
+  --input-smooth ALPHA  Smooth the raw movement of each axis before it's
+                        accelerated, only ALPHA of it (and of what was
+                        held back before) going out each frame and the
+                        rest with later ones, for jittery sensors; lower
+                        is smoother but laggier, and anything still held
+                        back goes out 10ms after the mouse stops
+  --output-average N    Write each frame as the average of the last N
+                        frames' accelerated movement, weighted by how
+                        long each took, to smooth out a noisy frame at
+                        the cost of some lag; what it holds back still
+                        goes out 10ms after the mouse stops
+  --max-mult-slew PER_MS
+                        Change the sensitivity by at most PER_MS a ms
+                        from one frame to the next, ramping toward what
+                        the curve gives instead of jumping, for a
+                        smoother feel; it jumps straight there again
+                        after the mouse has been still for 100ms
+  --one-euro            Smooth the accelerated movement with a one euro
+                        filter, which smooths more the slower the
+                        movement changes
+  --mincutoff HZ        Cutoff frequency of the filter when movement
+                        isn't changing; lower means smoother but laggier
+                        Default: 1
+  --beta BETA           How quickly the cutoff rises as movement changes
+                        faster; higher means less lag in fast movement
+                        Default: 0.007
+  --dynamic-mult-file PATH
+                        Also scale sensitivity by the number in PATH,
+                        checked every frame and reread whenever it
+                        changes, so other programs can adjust it; while
+                        it's missing or invalid, the last value is kept
+                        Default: 1 until PATH has a valid number
+  --fov-file PATH       Like --dynamic-mult-file, for a game (or a tool
+                        watching it) to write the multiplier for its
+                        current zoom to, so aim feels the same zoomed
+                        in; both apply when both are given
+  --focus-file PATH     Leave acceleration off while PATH says
+                        "unfocused" (or 0), for a script watching window
+                        focus to write, until it says "focused" (or 1);
+                        checked every frame like --dynamic-mult-file
+  --observed-gain-file PATH
+                        Divide the output by the gain the desktop still
+                        adds when its own acceleration can't be turned
+                        off, from "MOVED OBSERVED" in PATH: counts moved
+                        and the pixels the pointer went, as written by
+                        --calibrate-gain; checked every frame like
+                        --dynamic-mult-file
+  --config FILE         Take options from the TOML FILE, see README.md;
+                        can be given more than once, later files
+                        overriding earlier ones and the command line
+                        overriding them all
+  --print-config        Print every setting in effect, after config files,
+                        the command line and defaults, as a config file
+                        for --config, then exit
+  --print-config-continue
+                        Carry on running after --print-config
+  --device-registry FILE
+                        Use the curve file the TOML registry FILE lists
+                        for the device, by name or vendor:product, in
+                        place of the curve, -m, --speed-metric and
+                        direction options, which are left for devices
+                        it doesn't list, see README.md
+  --import-curve PATH   Take the curve from a curve file written by
+                        --export-curve or a curve editor, see README.md;
+                        -m and the options it covers other than the
+                        curve still override it
+  --speed-metric METRIC What the curve takes as speed: counts-per-ms
+                        (distance over time), counts-per-frame
+                        (distance, ignoring time) or events-per-sec
+                        (how often rel events arrive, ignoring their
+                        size), see README.md
+                        Default: counts-per-ms
+  --click-stabilize COUNTS
+                        While a mouse button is held, don't accelerate
+                        the first COUNTS of movement after it went down
+                        and scale it by --click-stabilize-mult, so a
+                        wiggle while clicking isn't taken for a drag
+  --click-stabilize-mult MULT
+                        What movement is scaled by while stabilizing
+                        clicks, on top of SENS_MULTIPLIER
+                        Default: 0.5
+  --swap-xy             Write the device's horizontal movement as
+                        vertical and the other way around, for mice
+                        mounted sideways; every other option going by
+                        direction or axis is for the axes after the
+                        swap, so as seen on screen
+  --axes CODES          The relative axes, named as libevdev has them and
+                        separated by commas, that the speed is measured
+                        across and the acceleration applies to, for 3D
+                        mice; it has to include REL_X and REL_Y, and the
+                        others only get the sensitivity, each with its
+                        own remainder
+                        Default: REL_X,REL_Y
+  --max-x-speed SPEED   Fastest the output can move horizontally, in
+                        counts per ms; anything faster is held back and
+                        let out in the frames after, which changes the
+                        direction of movement that only goes over on
+                        one axis
+                        Default: no limit
+  --max-y-speed SPEED   Same as --max-x-speed, vertically
+                        Default: no limit
+  --preserve-direction  When movement goes over --max-x-speed or
+                        --max-y-speed, scale both axes down together
+                        instead, keeping its direction
+  --accel-after MS      Only accelerate movement that has gone on for MS
+                        without a 100ms gap, treating it as standing
+                        still until then, so brief bumps and glitches
+                        aren't accelerated however fast they are
+                        Default: 0
+  --flick-threshold SPEED
+                        The speed in counts per ms above which movement
+                        is a flick, for --flick-cooldown
+  --flick-cooldown MS   Leave acceleration off for MS after a flick
+                        ends, using only the sensitivity multiplier, so
+                        flicks in quick succession don't stack up;
+                        needs --flick-threshold
+                        Default: no cooldown
+  --accel-budget COUNTS Only let acceleration add up to COUNTS counts of
+                        movement a second, beyond what the sensitivity
+                        multiplier alone gives, tapering back to the
+                        multiplier once that's used up, so a long fast
+                        sweep can't run away; a second's worth can be
+                        used at once, so short flicks are unaffected
+                        Default: no limit
+  --adaptive-offset     Keep moving the linear curve's offset (starting
+                        from -o) toward a percentile of the speeds moved
+                        at, so acceleration only starts above the speed
+                        normally tracked at, see README.md
+  --adaptive-offset-percentile P
+                        The percentile of speeds the offset follows
+                        Default: 50
+  --adaptive-offset-rate RATE
+                        How far the offset can move in a second of
+                        movement, in counts per ms
+                        Default: 0.5
+  --static-friction N   Hold back the first N counts of movement after
+                        100ms without any, then let them through all at
+                        once with what follows, so small nudges starting
+                        precise movements don't move the cursor
+                        Default: 0
+  --speed-hysteresis DELTA
+                        Keep using the last speed the curve was looked
+                        up at until speed moves more than DELTA away
+                        from it, so noise doesn't make the multiplier
+                        dither; movement is still scaled exactly
+                        Default: always following the speed
+  --directional-accel   Start acceleration over whenever movement
+                        reverses: the frame turning around isn't
+                        accelerated and the smoothing forgets what came
+                        before, so quick corrections stay precise
+  --reversal-angle DEG  How far movement has to turn from the last
+                        frame's direction to count as reversing
+                        Default: 90
+  --monotonic-time      Time frames by the monotonic clock when they're
+                        processed instead of their event timestamps,
+                        which can jump if the realtime clock is set but
+                        are more exact, see README.md
+  --input-scale FACTOR  Multiply distance by FACTOR when working out the
+                        speed for the curve, leaving the movement it
+                        scales alone; to use a curve tuned at another
+                        DPI, FACTOR is tuned DPI / this mouse's DPI
+                        Default: 1
+  --x-pos-mult MULT     Extra multiplier for movement to the right
+                        Default: 1
+  --x-neg-mult MULT     Extra multiplier for movement to the left
+                        Default: 1
+  --y-pos-mult MULT     Extra multiplier for movement down
+                        Default: 1
+  --y-neg-mult MULT     Extra multiplier for movement up
+                        Default: 1
+  --diagonal-compensation AMOUNT
+                        Extra multiplier for diagonal movement, 1 +
+                        AMOUNT at 45 degrees easing to 1 along the axes,
+                        for diagonals feeling slower or faster than
+                        straight movement
+                        Default: 0
+  --backend BACKEND     Library used to read and write devices, either
+                        evdev-rs or evdev (needs the backend-evdev
+                        feature)
+                        Default: evdev-rs
+  --emit-evemu          Also print every output event to stdout in
+                        evemu's text format
+  --emit-raw            Write the output events to stdout as raw
+                        struct input_events, for another process to
+                        write to a uinput device it made, instead of
+                        creating a virtual device
+  --forward-all-syn     Pass SYN events other than SYN_REPORT and
+                        SYN_DROPPED (like SYN_MT_REPORT) on to the
+                        virtual device instead of dropping them
+  --mirror-device NAME  Also write everything to a second virtual device
+                        called NAME, for other programs to watch the
+                        output on; it only passes on movement and the
+                        usual mouse buttons and wheels, and is still
+                        created with --dry-run
+  --heartbeat-device NAME
+                        Create a virtual device called NAME writing an
+                        MSC_SERIAL event at startup and every
+                        --heartbeat-interval after, counting up from 0,
+                        and -1 at shutdown, for supervisors to watch;
+                        it's still created with --dry-run
+  --heartbeat-interval MS
+                        Time between heartbeats
+                        Default: 1000
+  --dry-run             Don't grab the device or create the virtual
+                        device, only process the events
+  --no-create-output DEVICE
+                        Write to the existing event device DEVICE (such
+                        as the virtual device of another accelerator),
+                        which nothing can have grabbed, instead of
+                        creating a virtual device, see README.md
+  --batch-writes        Write each frame to the virtual device with one
+                        syscall instead of one per event (evdev-rs
+                        backend only, evdev always does this)
+  --threaded            Read the device on a separate thread, queueing
+                        events while a frame is processed and written
+                        (evdev-rs backend only); --debug adds how long
+                        each SYN_REPORT was queued
+  --logind              Take the device from logind as the session's
+                        controller instead of opening it, so it's handed
+                        back and forth as the session is switched
+                        (evdev-rs backend only, not with --threaded,
+                        needs the logind feature); opens it directly if
+                        logind won't give it
+  --debug               Print what each frame was processed with to
+                        stderr
+  --debug-speed-min SPEED
+                        Only print frames at least SPEED fast with
+                        --debug
+                        Default: 0
+  --debug-speed-max SPEED
+                        Only print frames at most SPEED fast with
+                        --debug
+                        Default: infinity
+  --control-fifo PATH   Create a named pipe at PATH that takes the
+                        commands pause (pass movement through as is),
+                        resume, lock (use the --lock-mult multiplier
+                        at every speed), unlock, reload (reread the
+                        command line and reset) and quit, one per line
+  --lock-mult MULT      Sensitivity used while locked
+                        Default: SENS_MULTIPLIER
+  --idle-poll-interval MS
+                        How long to sleep waiting for events before
+                        checking for control commands; longer means
+                        fewer wakeups when idle but slower commands
+                        Default: 100
+  --debug-udp HOST:PORT Send the speed, multiplier and movement in and
+                        out of frames as UDP datagrams to HOST:PORT, at
+                        most 100 a second, to watch a machine remotely,
+                        see README.md
+  --preview-stream PATH Serve the speed, multiplier and output of every
+                        frame on a unix socket at PATH, see README.md
+  --emit-multiplier-to PATH
+                        Serve the multiplier of every frame and the
+                        movement it was worked out from on a unix
+                        socket at PATH, for a program to accelerate by
+                        itself, see README.md
+  --no-transform        Write the movement out as is, with
+                        --emit-multiplier-to
+  --fuzz SEED           Instead of reading a device, run a long stream
+                        of random movement generated from SEED through
+                        the acceleration and exit nonzero if any output
+                        is invalid
+  --selftest            Run frames with known results through the
+                        acceleration with a range of curves and options,
+                        and through smooth scrolling, --strict,
+                        --power-supply and SIGUSR1,
+                        ignoring any others given, and exit nonzero if
+                        any come out different
+  --export-curve PATH   Write the curve the other options describe to
+                        a curve file at PATH, then exit
+  --test-pattern PATTERN
+                        Instead of reading a device, accelerate a
+                        generated movement and write it to a new
+                        virtual mouse, to see the curve move the
+                        cursor: line (right and back), circle (one
+                        loop) or ramp (right and back, speeding up from
+                        standing still to the pattern speed)
+  --pattern-speed SPEED Speed of the test pattern in counts per ms
+                        Default: 2
+  --demo-curve          Like --test-pattern, but with strokes getting
+                        faster from where the curve starts accelerating
+                        to where it caps, printing each one's speed and
+                        sensitivity, to feel the whole curve
+  --replay RECORDING    Instead of reading a device, play back the
+                        events of an evemu recording in real time and
+                        write them accelerated to a new virtual mouse,
+                        see README.md
+  --replay-speed FACTOR How many times faster than recorded to replay
+                        Default: 1
+  --calibrate-gain PATH Move a new virtual mouse 1000 counts right at
+                        the pattern speed, unaccelerated, then ask how
+                        many pixels the pointer went and write both to
+                        PATH for --observed-gain-file, then exit
+  --sens-at SPEED       Print the sensitivity the other options give
+                        movement at SPEED (in the speed metric's units),
+                        then exit
+  --benchmark-curves    Time evaluating a sample of each kind of curve,
+                        and the one the other options describe, then
+                        exit
+  --benchmark-writes    Time writing frames one event per syscall and
+                        all in one, as --batch-writes does, then exit
+  --print-libinput-advice
+                        Print the config needed to turn off libinput's
+                        own acceleration for the virtual device, then
+                        exit"#;
+
+pub enum Backend {
+    EvdevRs,
+    #[cfg(feature = "backend-evdev")]
+    Evdev,
+}
+
+fn parse_backend(name: &str) -> Result<Backend, &'static str> {
+    match name {
+        "evdev-rs" => Ok(Backend::EvdevRs),
+        #[cfg(feature = "backend-evdev")]
+        "evdev" => Ok(Backend::Evdev),
+        #[cfg(not(feature = "backend-evdev"))]
+        "evdev" => Err("the evdev backend requires building with the backend-evdev feature"),
+        _ => Err("unknown backend"),
+    }
+}
+
+// the graph of sensitivity against speed, before it's scaled by the multiplier
+pub enum Curve {
+    Linear {
+        accel: f64,
+        cap: f64,
+        offset: f64,
+    },
+    // the source is kept for writing the curve back out
+    Expr {
+        source: String,
+        expr: Expr,
+    },
+    VelocityMap(VelocityMap),
+    // flat until threshold, then the cap, for flicks
+    Breakaway {
+        threshold: f64,
+        cap: f64,
+        blend: f64,
+        hysteresis: f64,
+    },
+    // each curve from the speed with it up to the next one's, at least one and the first from 0
+    Segmented(Vec<(f64, Curve)>),
+}
+
+// what the curve takes as speed
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpeedMetric {
+    #[default]
+    CountsPerMs,
+    // ignores timing, for devices with unreliable timestamps
+    CountsPerFrame,
+    // how many rel events arrive regardless of their size, which depends less on dpi
+    EventsPerSec,
+}
+
+fn parse_speed_metric(name: &str) -> Result<SpeedMetric, &'static str> {
+    match name {
+        "counts-per-ms" => Ok(SpeedMetric::CountsPerMs),
+        "counts-per-frame" => Ok(SpeedMetric::CountsPerFrame),
+        "events-per-sec" => Ok(SpeedMetric::EventsPerSec),
+        _ => Err("unknown speed metric"),
+    }
+}
+
+// what -c is a cap on
+#[derive(Clone, Copy)]
+enum CapMode {
+    // the curve, before -m scales it
+    Relative,
+    // the sensitivity -m and the curve come to
+    Absolute,
+}
+
+fn parse_cap_mode(name: &str) -> Result<CapMode, &'static str> {
+    match name {
+        "relative" => Ok(CapMode::Relative),
+        "absolute" => Ok(CapMode::Absolute),
+        _ => Err("expected relative or absolute"),
+    }
+}
+
+// curves --curve can name, which default what isn't given
+#[derive(Clone, Copy)]
+enum CurveType {
+    Linear,
+    Breakaway,
+    Expr,
+    VelocityMap,
+    Segmented,
+}
+
+fn parse_curve_type(name: &str) -> Result<CurveType, &'static str> {
+    match name {
+        "linear" => Ok(CurveType::Linear),
+        "breakaway" => Ok(CurveType::Breakaway),
+        "expr" => Ok(CurveType::Expr),
+        "velocity-map" => Ok(CurveType::VelocityMap),
+        "segmented" => Ok(CurveType::Segmented),
+        _ => Err("expected linear, breakaway, expr, velocity-map or segmented"),
+    }
+}
+
+// what --curve fills in for whichever of the curve's parameters aren't given, the rest are left
+// to the options' own defaults
+#[derive(Default)]
+struct CurveDefaults {
+    accel: Option<f64>,
+    breakaway: Option<f64>,
+    cap: Option<f64>,
+    expr: Option<&'static str>,
+    // there's no file to point --velocity-map at, so the map itself
+    velocity_map: Option<&'static [(f64, f64)]>,
+    segments: &'static [&'static str],
+}
+
+impl CurveType {
+    fn defaults(self) -> CurveDefaults {
+        match self {
+            CurveType::Linear => CurveDefaults {
+                accel: Some(0.1),
+                ..CurveDefaults::default()
+            },
+            CurveType::Breakaway => CurveDefaults {
+                breakaway: Some(10.0),
+                cap: Some(2.0),
+                ..CurveDefaults::default()
+            },
+            CurveType::Expr => CurveDefaults {
+                expr: Some("min(1 + 0.01*s^2, 3)"),
+                ..CurveDefaults::default()
+            },
+            // 1x up to 5 counts/ms, 2x at 20 and 3x from 50
+            CurveType::VelocityMap => CurveDefaults {
+                velocity_map: Some(&[(5.0, 5.0), (20.0, 40.0), (50.0, 150.0)]),
+                ..CurveDefaults::default()
+            },
+            // no acceleration up to 5 counts/ms, then linear up to 3x
+            CurveType::Segmented => CurveDefaults {
+                segments: &[
+                    r#"{"from": 0, "type": "linear", "accel": 0}"#,
+                    r#"{"from": 5, "type": "linear", "accel": 0.1, "cap": 3, "offset": 5}"#,
+                ],
+                ..CurveDefaults::default()
+            },
+        }
+    }
+}
+
+// each segment a curve file's curve with the speed it takes over from, in JSON; a seam the
+// segments don't meet at is allowed, but likely a mistake
+fn parse_segments(segments: &[String]) -> Result<Curve, String> {
+    let segments = segments
+        .iter()
+        .map(|segment| {
+            let segment: SegmentDef =
+                serde_json::from_str(segment).map_err(|err| format!("{}: {}", segment, err))?;
+            Ok((segment.from, segment.curve.to_curve()?))
+        })
+        .collect::<Result<_, String>>()?;
+    let curve = segmented(segments)?;
+    if let Curve::Segmented(ref segments) = curve {
+        for (from, below, above) in discontinuities(segments) {
+            eprintln!(
+                "Warning: '--segment' curves don't meet at {} counts/ms, going from {} to {}",
+                from, below, above
+            );
+        }
+    }
+    Ok(curve)
+}
+
+// --clamp-offset-to-zero, for each linear segment
+fn clamp_segments(curve: Curve, clamp: impl Fn(f64) -> f64) -> Curve {
+    let Curve::Segmented(segments) = curve else {
+        return curve;
+    };
+    Curve::Segmented(
+        segments
+            .into_iter()
+            .map(|(from, curve)| match curve {
+                Curve::Linear { accel, cap, offset } => (
+                    from,
+                    Curve::Linear {
+                        accel,
+                        cap,
+                        offset: clamp(offset),
+                    },
+                ),
+                curve => (from, curve),
+            })
+            .collect(),
+    )
+}
+
+// a cap on the sensitivity sens_mult scales the curve to, as the cap on the curve that comes to
+// the same, which is what everything else takes
+fn absolute_cap(curve: Curve, sens_mult: f64) -> Curve {
+    // -m being positive is checked along with everything else
+    if sens_mult <= 0.0 {
+        return curve;
+    }
+    match curve {
+        Curve::Linear { accel, cap, offset } => Curve::Linear {
+            accel,
+            cap: cap / sens_mult,
+            offset,
+        },
+        Curve::Breakaway {
+            threshold,
+            cap,
+            blend,
+            hysteresis,
+        } => Curve::Breakaway {
+            threshold,
+            cap: cap / sens_mult,
+            blend,
+            hysteresis,
+        },
+        curve => curve,
+    }
+}
+
+// host:port, the host looked up right away
+fn parse_socket_addr(addr: &str) -> Result<SocketAddr, String> {
+    addr.to_socket_addrs()
+        .map_err(|err| err.to_string())?
+        .next()
+        .ok_or_else(|| format!("'{}' has no address", addr))
+}
+
+// REL_X,REL_Y,REL_Z and so on, less REL_X and REL_Y, which are always accelerated
+fn parse_axes(list: &str) -> Result<Vec<EV_REL>, String> {
+    let mut axes = Vec::new();
+    for name in list.split(',').map(str::trim) {
+        let axis = match EventCode::from_str(&EventType::EV_REL, name) {
+            Some(EventCode::EV_REL(axis)) => axis,
+            _ => return Err(format!("unknown relative axis '{}'", name)),
+        };
+        if axes.contains(&axis) {
+            return Err(format!("'{}' is listed twice", name));
+        }
+        // scrolling has its own acceleration
+        if matches!(
+            axis,
+            EV_REL::REL_WHEEL
+                | EV_REL::REL_HWHEEL
+                | EV_REL::REL_WHEEL_HI_RES
+                | EV_REL::REL_HWHEEL_HI_RES
+        ) {
+            return Err(format!("'{}' is a wheel, not a movement axis", name));
+        }
+        axes.push(axis);
+    }
+    for needed in [EV_REL::REL_X, EV_REL::REL_Y] {
+        if !axes.contains(&needed) {
+            return Err("REL_X and REL_Y have to be listed".to_string());
+        }
+    }
+    axes.retain(|axis| !matches!(axis, EV_REL::REL_X | EV_REL::REL_Y));
+    Ok(axes)
+}
+
+// key names as libevdev has them, joined by +
+fn parse_panic_keys(combo: &str) -> Result<Vec<EventCode>, String> {
+    combo
+        .split('+')
+        .map(|name| {
+            EventCode::from_str(&EventType::EV_KEY, name.trim())
+                .ok_or_else(|| format!("unknown key '{}'", name.trim()))
+        })
+        .collect()
+}
+
+// a curve swapped in while its modifier is held
+pub struct Profile {
+    pub sens_mult: f64,
+    pub curve: Curve,
+}
+
+// KEY:PATH, the key named as libevdev has it and PATH a curve file
+fn parse_modifier(mapping: &str) -> Result<(EventCode, Profile), String> {
+    let (name, path) = mapping.split_once(':').ok_or("expected KEY:CURVE_FILE")?;
+    let key = EventCode::from_str(&EventType::EV_KEY, name.trim())
+        .ok_or_else(|| format!("unknown key '{}'", name.trim()))?;
+    let file = CurveFile::load(Path::new(path)).map_err(|err| format!("{}: {}", path, err))?;
+    Ok((
+        key,
+        Profile {
+            sens_mult: file.sens_mult,
+            curve: file
+                .curve
+                .to_curve()
+                .map_err(|err| format!("{}: {}", path, err))?,
+        },
+    ))
+}
+
+pub struct Args {
+    pub sens_mult: f64,
+    pub curve: Curve,
+    // flat sensitivity used instead of the curve while locked
+    pub lock_mult: f64,
+    // holds a multiplier applied on top of the curve, which is reread whenever it changes
+    pub dynamic_mult_file: Option<PathBuf>,
+    // the same, for a game's zoom
+    pub fov_file: Option<PathBuf>,
+    // the calibration the desktop's own gain is worked out from
+    pub observed_gain_file: Option<PathBuf>,
+    // says whether the game has focus, acceleration being off without it
+    pub focus_file: Option<PathBuf>,
+    pub speed_metric: SpeedMetric,
+    // distance is multiplied by this for working out speed, to move a curve between dpis
+    pub input_scale: f64,
+    pub monotonic_time: bool,
+    // acceleration restarts when movement turns by more than reversal_angle (in degrees)
+    pub directional_accel: bool,
+    pub reversal_angle: f64,
+    pub min_move: bool,
+    pub flush_on_click: bool,
+    pub panic_keys: Vec<EventCode>,
+    // earlier entries win when several are held
+    pub modifier_map: Vec<(EventCode, Profile)>,
+    pub accum_bound: f64,
+    pub no_accum: bool,
+    // how long without events before the remainders are zeroed
+    pub anti_drift: Option<Duration>,
+    // the least time between frames of movement written, the rest is coalesced
+    pub min_report_interval: Option<Duration>,
+    // input events per second past which frames are coalesced
+    pub max_event_rate: Option<u32>,
+    // the sysfs directory of the mouse's battery, watched for changes
+    pub power_supply: Option<PathBuf>,
+    // logging events from the source that break the protocol
+    pub strict: bool,
+    // how long motion waits for a SYN_REPORT before one is made up for it
+    pub strict_flush_after: Option<Duration>,
+    // the same without --strict's logging
+    pub syn_timeout: Option<Duration>,
+    // acceleration of the wheel by detents per second, each way
+    pub scroll_up_accel: Option<f64>,
+    pub scroll_down_accel: Option<f64>,
+    // the device is a touchpad scrolling with REL_WHEEL and REL_HWHEEL, accelerated on its own terms
+    pub touchpad_mode: bool,
+    pub touchpad_scroll_accel: f64,
+    // frames each wheel detent is spread over as hi-res scrolling
+    pub smooth_scroll: Option<u32>,
+    // raw counts after a button press kept from being accelerated, and what they're scaled by
+    pub click_stabilize: Option<f64>,
+    pub click_stabilize_mult: f64,
+    // device x written as y and the other way around
+    pub swap_xy: bool,
+    // the --axes beyond X and Y
+    pub extra_axes: Vec<EV_REL>,
+    // output speed limits per axis in counts per ms, after acceleration
+    pub max_x_speed: Option<f64>,
+    pub max_y_speed: Option<f64>,
+    // scales both axes down to the limit instead of clamping each on its own
+    pub preserve_direction: bool,
+    // ms a movement has to go on for before it's accelerated
+    pub accel_after: Option<f64>,
+    // speed from the lengths of the reports coalesced into a frame rather than where they ended up
+    pub path_length_speed: bool,
+    // ms after a frame faster than the threshold that acceleration is left off for
+    pub flick_threshold: Option<f64>,
+    pub flick_cooldown: Option<f64>,
+    // counts a second acceleration can add beyond the base sensitivity
+    pub accel_budget: Option<f64>,
+    // the linear curve's offset follows the percentile of recent speeds, moving at most rate
+    // counts per ms each second
+    pub adaptive_offset: bool,
+    pub adaptive_offset_percentile: f64,
+    pub adaptive_offset_rate: f64,
+    // raw counts held back at the start of each movement
+    pub static_friction: Option<f64>,
+    // how far speed has to move from the last speed used before the curve follows it
+    pub speed_hysteresis: Option<f64>,
+    // drops the sub-pixel remainder on a reload instead of carrying it over
+    pub reset_accum_on_reload: bool,
+    // how much of each frame's raw movement goes out right away
+    pub input_smooth: Option<f64>,
+    // how many frames' accelerated movement each frame writes the average of
+    pub output_average: Option<usize>,
+    // how fast the sensitivity can change, a ms
+    pub max_mult_slew: Option<f64>,
+    pub one_euro: bool,
+    pub min_cutoff: f64,
+    pub beta: f64,
+    pub x_pos_mult: f64,
+    pub x_neg_mult: f64,
+    pub y_pos_mult: f64,
+    pub y_neg_mult: f64,
+    // how much more diagonal movement is multiplied by than movement along an axis
+    pub diagonal_compensation: f64,
+    pub backend: Backend,
+    pub emit_evemu: bool,
+    // the output written to stdout as struct input_events instead of a virtual device
+    pub emit_raw: bool,
+    // passes on SYN events other than SYN_REPORT instead of dropping them
+    pub forward_all_syn: bool,
+    // name of a second virtual device getting everything written
+    pub mirror_device: Option<String>,
+    // name of a virtual device written heartbeats, and how often
+    pub heartbeat_device: Option<String>,
+    pub heartbeat_interval: Duration,
+    pub dry_run: bool,
+    // an existing device to write to instead of creating a virtual device
+    pub no_create_output: Option<PathBuf>,
+    pub batch_writes: bool,
+    // reading the device on its own thread
+    pub threaded: bool,
+    // taking the device from logind
+    pub logind: bool,
+    pub debug: bool,
+    // frames printed by debug, by speed
+    pub debug_speeds: RangeInclusive<f64>,
+    // where frames are sent as datagrams, for watching from another machine
+    pub debug_udp: Option<SocketAddr>,
+    pub control_fifo: Option<PathBuf>,
+    // how long to wait for events before checking on anything else
+    pub idle_poll_interval: Duration,
+    pub preview_stream: Option<PathBuf>,
+    // a socket for the multiplier of every frame with the movement it was worked out from
+    pub emit_multiplier_to: Option<PathBuf>,
+    // pass the movement through as is, leaving acceleration to whoever reads the multipliers
+    pub no_transform: bool,
+    // counts per ms, the top speed for the ramp
+    pub pattern_speed: f64,
+    pub replay_speed: f64,
+    // the command line with any config files' options put in front, and whether the device
+    // registry had the device, which the settings shown by --print-config and SIGUSR1 are worked
+    // out along with
+    pub raw: Vec<OsString>,
+    pub registered: bool,
+    // every setting printed as a config file before anything else is done
+    pub print_config: bool,
+    pub print_config_continue: bool,
+    pub mode: Mode,
+}
+
+pub enum Mode {
+    Device(OsString),
+    Fuzz(u64),
+    LibinputAdvice(OsString),
+    ExportCurve(PathBuf),
+    CalibrateGain(PathBuf),
+    BenchmarkCurves,
+    TestPattern(Pattern),
+    DemoCurve,
+    SensAt(f64),
+    Replay(PathBuf),
+}
+
+// the cursor should cross the same fraction of the screen for the same hand movement, so output
+// (in pixels) scales linearly with the resolution measured along the chosen dimension
+fn resolution_scale(screen: u32, reference: u32) -> f64 {
+    screen as f64 / reference as f64
+}
+
+pub enum ArgsError {
+    Parse(pico_args::Error),
+    Invalid(String),
+}
+
+impl From<pico_args::Error> for ArgsError {
+    fn from(err: pico_args::Error) -> ArgsError {
+        ArgsError::Parse(err)
+    }
+}
+
+impl std::fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ArgsError::Parse(err) => err.fmt(f),
+            ArgsError::Invalid(cause) => f.write_str(cause),
+        }
+    }
+}
+
+fn parse_resolution_scale(arguments: &mut Arguments) -> Result<f64, ArgsError> {
+    let width: Option<u32> = arguments.opt_value_from_str("--screen-width")?;
+    let height: Option<u32> = arguments.opt_value_from_str("--screen-height")?;
+    let reference: Option<u32> = arguments.opt_value_from_str("--normalize-to")?;
+    let screen = match (width, height) {
+        (Some(_), Some(_)) => {
+            return Err(ArgsError::Invalid(
+                "only one of '--screen-width' and '--screen-height' can be set".to_string(),
+            ))
+        }
+        (Some(pixels), None) | (None, Some(pixels)) => Some(pixels),
+        (None, None) => None,
+    };
+    match (screen, reference) {
+        (None, None) => Ok(1.0),
+        (Some(screen), Some(reference)) if screen > 0 && reference > 0 => {
+            Ok(resolution_scale(screen, reference))
+        }
+        (Some(_), Some(_)) => Err(ArgsError::Invalid(
+            "screen resolutions must be nonzero".to_string(),
+        )),
+        (Some(_), None) => Err(pico_args::Error::MissingOption("--normalize-to".into()).into()),
+        (None, Some(_)) => Err(ArgsError::Invalid(
+            "'--normalize-to' requires '--screen-width' or '--screen-height'".to_string(),
+        )),
+    }
+}
+
+fn validate(args: &Args) -> Result<(), String> {
+    if !(args.sens_mult.is_finite() && args.sens_mult > 0.0) {
+        return Err(format!("'-m' must be positive, got {}", args.sens_mult));
+    }
+    if !(args.lock_mult.is_finite() && args.lock_mult > 0.0) {
+        return Err(format!(
+            "'--lock-mult' must be positive, got {}",
+            args.lock_mult
+        ));
+    }
+    if let Curve::Linear { accel, cap, offset } = args.curve {
+        if !accel.is_finite() {
+            return Err(format!("'-a' must be finite, got {}", accel));
+        }
+        // infinity is the default, meaning no cap
+        if cap.is_nan() || cap < 0.0 {
+            return Err(format!("'-c' must be at least 0, got {}", cap));
+        }
+        if !offset.is_finite() {
+            return Err(format!("'-o' must be finite, got {}", offset));
+        }
+    }
+    if let Curve::Breakaway {
+        threshold,
+        cap,
+        blend,
+        hysteresis,
+    } = args.curve
+    {
+        for (name, value) in [
+            ("--breakaway", threshold),
+            ("-c", cap),
+            ("--breakaway-blend", blend),
+            ("--breakaway-hysteresis", hysteresis),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("'{}' must be at least 0, got {}", name, value));
+            }
+        }
+    }
+    // rounding alone leaves up to half a count behind
+    if !(args.accum_bound.is_finite() && args.accum_bound >= 0.5) {
+        return Err(format!(
+            "'--accum-bound' must be at least 0.5, got {}",
+            args.accum_bound
+        ));
+    }
+    if !(args.min_cutoff.is_finite() && args.min_cutoff > 0.0) {
+        return Err(format!(
+            "'--mincutoff' must be positive, got {}",
+            args.min_cutoff
+        ));
+    }
+    if let Some(alpha) = args.input_smooth {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return Err(format!(
+                "'--input-smooth' must be above 0 and at most 1, got {}",
+                alpha
+            ));
+        }
+    }
+    if !(args.beta.is_finite() && args.beta >= 0.0) {
+        return Err(format!("'--beta' must be at least 0, got {}", args.beta));
+    }
+    if !(args.input_scale.is_finite() && args.input_scale > 0.0) {
+        return Err(format!(
+            "'--input-scale' must be positive, got {}",
+            args.input_scale
+        ));
+    }
+    if !(args.reversal_angle > 0.0 && args.reversal_angle < 180.0) {
+        return Err(format!(
+            "'--reversal-angle' must be between 0 and 180, got {}",
+            args.reversal_angle
+        ));
+    }
+    if let Mode::SensAt(speed) = args.mode {
+        if !(speed.is_finite() && speed >= 0.0) {
+            return Err(format!("'--sens-at' must be at least 0, got {}", speed));
+        }
+    }
+    if !(args.pattern_speed.is_finite() && args.pattern_speed > 0.0) {
+        return Err(format!(
+            "'--pattern-speed' must be positive, got {}",
+            args.pattern_speed
+        ));
+    }
+    if !(args.replay_speed.is_finite() && args.replay_speed > 0.0) {
+        return Err(format!(
+            "'--replay-speed' must be positive, got {}",
+            args.replay_speed
+        ));
+    }
+    for (name, max) in [
+        ("--max-x-speed", args.max_x_speed),
+        ("--max-y-speed", args.max_y_speed),
+    ] {
+        if let Some(max) = max {
+            if !(max.is_finite() && max > 0.0) {
+                return Err(format!("'{}' must be positive, got {}", name, max));
+            }
+        }
+    }
+    if let Some(budget) = args.click_stabilize {
+        if !(budget.is_finite() && budget >= 0.0) {
+            return Err(format!(
+                "'--click-stabilize' must be at least 0, got {}",
+                budget
+            ));
+        }
+    }
+    if !(args.click_stabilize_mult.is_finite() && args.click_stabilize_mult > 0.0) {
+        return Err(format!(
+            "'--click-stabilize-mult' must be positive, got {}",
+            args.click_stabilize_mult
+        ));
+    }
+    // the multiplier has to stay positive
+    if !(args.diagonal_compensation.is_finite() && args.diagonal_compensation > -1.0) {
+        return Err(format!(
+            "'--diagonal-compensation' must be above -1, got {}",
+            args.diagonal_compensation
+        ));
+    }
+    if let Some(budget) = args.accel_budget {
+        if !(budget.is_finite() && budget > 0.0) {
+            return Err(format!("'--accel-budget' must be positive, got {}", budget));
+        }
+    }
+    if args.adaptive_offset && !matches!(args.curve, Curve::Linear { .. }) {
+        return Err("'--adaptive-offset' needs a linear curve".to_string());
+    }
+    if !(args.adaptive_offset_percentile > 0.0 && args.adaptive_offset_percentile < 100.0) {
+        return Err(format!(
+            "'--adaptive-offset-percentile' must be above 0 and below 100, got {}",
+            args.adaptive_offset_percentile
+        ));
+    }
+    if !(args.adaptive_offset_rate.is_finite() && args.adaptive_offset_rate > 0.0) {
+        return Err(format!(
+            "'--adaptive-offset-rate' must be positive, got {}",
+            args.adaptive_offset_rate
+        ));
+    }
+    if let Some(after) = args.accel_after {
+        if !(after.is_finite() && after >= 0.0) {
+            return Err(format!("'--accel-after' must be at least 0, got {}", after));
+        }
+    }
+    for (name, value) in [
+        ("--flick-threshold", args.flick_threshold),
+        ("--flick-cooldown", args.flick_cooldown),
+    ] {
+        if let Some(value) = value {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("'{}' must be positive, got {}", name, value));
+            }
+        }
+    }
+    if args.flick_cooldown.is_some() && args.flick_threshold.is_none() {
+        return Err("'--flick-cooldown' needs '--flick-threshold'".to_string());
+    }
+    if let Some(friction) = args.static_friction {
+        if !(friction.is_finite() && friction >= 0.0) {
+            return Err(format!(
+                "'--static-friction' must be at least 0, got {}",
+                friction
+            ));
+        }
+    }
+    if let Some(delta) = args.speed_hysteresis {
+        if !(delta.is_finite() && delta >= 0.0) {
+            return Err(format!(
+                "'--speed-hysteresis' must be at least 0, got {}",
+                delta
+            ));
+        }
+    }
+    if args.print_config_continue {
+        if !args.print_config {
+            return Err("'--print-config-continue' needs '--print-config'".to_string());
+        }
+        // both write to stdout
+        for (given, name) in [
+            (args.emit_raw, "--emit-raw"),
+            (args.emit_evemu, "--emit-evemu"),
+        ] {
+            if given {
+                return Err(format!(
+                    "'--print-config-continue' can't be used with '{}'",
+                    name
+                ));
+            }
+        }
+    }
+    if args.emit_raw {
+        for (given, name) in [
+            (args.emit_evemu, "--emit-evemu"),
+            (args.no_create_output.is_some(), "--no-create-output"),
+            (args.mirror_device.is_some(), "--mirror-device"),
+        ] {
+            if given {
+                return Err(format!("'--emit-raw' can't be used with '{}'", name));
+            }
+        }
+    }
+    if args.threaded && !matches!(args.backend, Backend::EvdevRs) {
+        return Err("'--threaded' only works with the evdev-rs backend".to_string());
+    }
+    if args.logind {
+        if cfg!(not(feature = "logind")) {
+            return Err("'--logind' requires building with the logind feature".to_string());
+        }
+        if !matches!(args.backend, Backend::EvdevRs) {
+            return Err("'--logind' only works with the evdev-rs backend".to_string());
+        }
+        // the reading thread has no way to take the device back
+        if args.threaded {
+            return Err("'--logind' can't be used with '--threaded'".to_string());
+        }
+    }
+    if args.no_transform && args.emit_multiplier_to.is_none() {
+        return Err("'--no-transform' needs '--emit-multiplier-to'".to_string());
+    }
+    if args.strict_flush_after.is_some() && !args.strict {
+        return Err("'--strict-flush-after' needs '--strict'".to_string());
+    }
+    if args.strict_flush_after.is_some_and(|after| after.is_zero()) {
+        return Err("'--strict-flush-after' must be at least 1".to_string());
+    }
+    if args.syn_timeout.is_some_and(|timeout| timeout.is_zero()) {
+        return Err("'--syn-timeout' must be at least 1".to_string());
+    }
+    if args.anti_drift.is_some_and(|idle| idle.is_zero()) {
+        return Err("'--anti-drift' must be at least 1".to_string());
+    }
+    if args.heartbeat_interval.is_zero() {
+        return Err("'--heartbeat-interval' must be at least 1".to_string());
+    }
+    for (name, accel) in [
+        ("--scroll-up-accel", args.scroll_up_accel),
+        ("--scroll-down-accel", args.scroll_down_accel),
+        ("--touchpad-scroll-accel", Some(args.touchpad_scroll_accel)),
+    ] {
+        if let Some(accel) = accel {
+            if !(accel.is_finite() && accel >= 0.0) {
+                return Err(format!("'{}' must be at least 0, got {}", name, accel));
+            }
+        }
+    }
+    if let Some(slew) = args.max_mult_slew {
+        if !(slew.is_finite() && slew > 0.0) {
+            return Err(format!("'--max-mult-slew' must be positive, got {}", slew));
+        }
+    }
+    if args.output_average == Some(0) {
+        return Err("'--output-average' must be at least 1".to_string());
+    }
+    if args.smooth_scroll == Some(0) {
+        return Err("'--smooth-scroll' must be at least 1".to_string());
+    }
+    if args
+        .min_report_interval
+        .is_some_and(|interval| interval.is_zero())
+    {
+        return Err("'--min-report-interval' must be at least 1".to_string());
+    }
+    if args.max_event_rate == Some(0) {
+        return Err("'--max-event-rate' must be at least 1".to_string());
+    }
+    if args.debug_speeds.is_empty() {
+        return Err(format!(
+            "'--debug-speed-min' ({}) can't be above '--debug-speed-max' ({})",
+            args.debug_speeds.start(),
+            args.debug_speeds.end()
+        ));
+    }
+    // never waiting would spin
+    if args.idle_poll_interval.is_zero() {
+        return Err("'--idle-poll-interval' must be at least 1".to_string());
+    }
+    for (name, mult) in [
+        ("--x-pos-mult", args.x_pos_mult),
+        ("--x-neg-mult", args.x_neg_mult),
+        ("--y-pos-mult", args.y_pos_mult),
+        ("--y-neg-mult", args.y_neg_mult),
+    ] {
+        if !(mult.is_finite() && mult > 0.0) {
+            return Err(format!("'{}' must be positive, got {}", name, mult));
+        }
+    }
+    Ok(())
+}
+
+// has to come after every option, the device file is a free argument
+fn parse_mode(arguments: &mut Arguments) -> Result<Mode, ArgsError> {
+    if let Some(seed) = arguments.opt_value_from_str("--fuzz")? {
+        return Ok(Mode::Fuzz(seed));
+    }
+    if let Some(pattern) = arguments.opt_value_from_fn("--test-pattern", parse_pattern)? {
+        return Ok(Mode::TestPattern(pattern));
+    }
+    if arguments.contains("--demo-curve") {
+        return Ok(Mode::DemoCurve);
+    }
+    if let Some(path) = arguments.opt_value_from_str("--replay")? {
+        return Ok(Mode::Replay(path));
+    }
+    if let Some(speed) = arguments.opt_value_from_str("--sens-at")? {
+        return Ok(Mode::SensAt(speed));
+    }
+    if arguments.contains("--benchmark-curves") {
+        return Ok(Mode::BenchmarkCurves);
+    }
+    if let Some(path) = arguments.opt_value_from_str("--export-curve")? {
+        return Ok(Mode::ExportCurve(path));
+    }
+    if let Some(path) = arguments.opt_value_from_str("--calibrate-gain")? {
+        return Ok(Mode::CalibrateGain(path));
+    }
+    let advice = arguments.contains("--print-libinput-advice");
+    let filename = arguments.free_from_str()?;
+    Ok(if advice {
+        Mode::LibinputAdvice(filename)
+    } else {
+        Mode::Device(filename)
+    })
+}
+
+// the sensitivity multiplier (before resolution scaling) and the curve it scales, from either the
+// options or an imported curve file; a registered file replaces the curve options instead of
+// conflicting with them, they're only for devices the registry doesn't have
+fn parse_curve(
+    arguments: &mut Arguments,
+    imported: Option<&CurveFile>,
+    registered: bool,
+) -> Result<(f64, Curve), ArgsError> {
+    let clamp_offset = arguments.contains("--clamp-offset-to-zero");
+    let clamp = |offset: f64| {
+        if clamp_offset {
+            f64::max(offset, 0.0)
+        } else {
+            offset
+        }
+    };
+    let sens_mult: Option<f64> = arguments.opt_value_from_str("-m")?;
+    let accel: Option<f64> = arguments.opt_value_from_str("-a")?;
+    let cap: Option<f64> = arguments.opt_value_from_str("-c")?;
+    let offset: Option<f64> = arguments.opt_value_from_str("-o")?;
+    let expr: Option<String> = arguments.opt_value_from_str("--expr")?;
+    let velocity_map: Option<PathBuf> = arguments.opt_value_from_str("--velocity-map")?;
+    let breakaway: Option<f64> = arguments.opt_value_from_str("--breakaway")?;
+    let blend: Option<f64> = arguments.opt_value_from_str("--breakaway-blend")?;
+    let hysteresis: Option<f64> = arguments.opt_value_from_str("--breakaway-hysteresis")?;
+    let named: Option<CurveType> = arguments.opt_value_from_fn("--curve", parse_curve_type)?;
+    let segments: Vec<String> = arguments.values_from_str("--segment")?;
+
+    if let Some(file) = imported {
+        let curve_options = named.is_some()
+            || !segments.is_empty()
+            || accel.is_some()
+            || cap.is_some()
+            || offset.is_some()
+            || expr.is_some()
+            || velocity_map.is_some()
+            || breakaway.is_some()
+            || blend.is_some()
+            || hysteresis.is_some();
+        if curve_options && !registered {
+            return Err(ArgsError::Invalid(
+                "'--import-curve' can't be combined with options setting the curve".to_string(),
+            ));
+        }
+        let curve = match file.curve.to_curve() {
+            Ok(Curve::Linear { accel, cap, offset }) => Curve::Linear {
+                accel,
+                cap,
+                offset: clamp(offset),
+            },
+            Ok(curve) => curve,
+            Err(err) => return Err(ArgsError::Invalid(format!("'--import-curve': {}", err))),
+        };
+        return Ok((given_or(sens_mult, file.sens_mult, registered), curve));
+    }
+    let defaults = named.map(CurveType::defaults).unwrap_or_default();
+    let segments = match segments.is_empty() {
+        true => defaults
+            .segments
+            .iter()
+            .map(|segment| segment.to_string())
+            .collect(),
+        false => segments,
+    };
+    if !segments.is_empty() {
+        let curve_options = named.is_some_and(|named| !matches!(named, CurveType::Segmented))
+            || accel.is_some()
+            || cap.is_some()
+            || offset.is_some()
+            || expr.is_some()
+            || velocity_map.is_some()
+            || breakaway.is_some()
+            || blend.is_some()
+            || hysteresis.is_some();
+        if curve_options {
+            return Err(ArgsError::Invalid(
+                "'--segment' can't be combined with other options setting the curve".to_string(),
+            ));
+        }
+        let curve = parse_segments(&segments)
+            .map_err(|err| ArgsError::Invalid(format!("'--segment': {}", err)))?;
+        return Ok((sens_mult.unwrap_or(1.0), clamp_segments(curve, clamp)));
+    }
+    // a breakaway curve named alongside another's options is caught with --breakaway below
+    let conflict = match named {
+        Some(CurveType::Linear)
+            if breakaway.is_some() || expr.is_some() || velocity_map.is_some() =>
+        {
+            Some(
+                "'--curve linear' can't be combined with '--breakaway', '--expr' or \
+                 '--velocity-map'",
+            )
+        }
+        Some(CurveType::Expr) if breakaway.is_some() || velocity_map.is_some() => {
+            Some("'--curve expr' can't be combined with '--breakaway' or '--velocity-map'")
+        }
+        Some(CurveType::VelocityMap) if breakaway.is_some() || expr.is_some() => {
+            Some("'--curve velocity-map' can't be combined with '--breakaway' or '--expr'")
+        }
+        _ => None,
+    };
+    if let Some(conflict) = conflict {
+        return Err(ArgsError::Invalid(conflict.to_string()));
+    }
+    let accel = accel.or(defaults.accel);
+    let breakaway = breakaway.or(defaults.breakaway);
+    let cap = cap.or(defaults.cap);
+    let expr = expr.or(defaults.expr.map(String::from));
+    if (blend.is_some() || hysteresis.is_some()) && breakaway.is_none() {
+        return Err(ArgsError::Invalid(
+            "'--breakaway-blend' and '--breakaway-hysteresis' require '--breakaway'".to_string(),
+        ));
+    }
+    if let Some(threshold) = breakaway {
+        if accel.is_some() || offset.is_some() || expr.is_some() || velocity_map.is_some() {
+            return Err(ArgsError::Invalid(
+                "'--breakaway' can't be combined with '-a', '-o', '--expr' or '--velocity-map'"
+                    .to_string(),
+            ));
+        }
+        return Ok((
+            sens_mult.ok_or(pico_args::Error::MissingOption("-m".into()))?,
+            Curve::Breakaway {
+                threshold,
+                cap: cap.ok_or(pico_args::Error::MissingOption("-c".into()))?,
+                blend: blend.unwrap_or(0.0),
+                hysteresis: hysteresis.unwrap_or(threshold / 10.0),
+            },
+        ));
+    }
+    // an expression or a map is the whole graph, so it doesn't need anything scaling it
+    let curve = match (expr, velocity_map) {
+        (Some(_), Some(_)) => {
+            return Err(ArgsError::Invalid(
+                "only one of '--expr' and '--velocity-map' can be set".to_string(),
+            ))
+        }
+        (Some(source), None) => Curve::Expr {
+            expr: expr::parse(&source)
+                .map_err(|err| ArgsError::Invalid(format!("'--expr': {}", err)))?,
+            source,
+        },
+        (None, Some(path)) => Curve::VelocityMap(VelocityMap::load(&path).map_err(|err| {
+            ArgsError::Invalid(format!("'--velocity-map' {}: {}", path.display(), err))
+        })?),
+        (None, None) => match defaults.velocity_map {
+            Some(points) => {
+                Curve::VelocityMap(VelocityMap::from_points(points).map_err(ArgsError::Invalid)?)
+            }
+            None => {
+                return Ok((
+                    sens_mult.ok_or(pico_args::Error::MissingOption("-m".into()))?,
+                    Curve::Linear {
+                        accel: accel.ok_or(pico_args::Error::MissingOption("-a".into()))?,
+                        cap: cap.unwrap_or(f64::INFINITY),
+                        offset: clamp(offset.unwrap_or(0.0)),
+                    },
+                ))
+            }
+        },
+    };
+    Ok((sens_mult.unwrap_or(1.0), curve))
+}
+
+// the device registry has to know the device before the curve can be worked out, and options
+// can't be taken from pico-args until every one before the device is, so the device is looked up
+// as the last argument first, after any config files' options are put in front of it
+pub fn parse_args(raw: Vec<OsString>) -> Result<Args, ArgsError> {
+    let (raw, settings) = config::with_configs(raw).map_err(ArgsError::Invalid)?;
+    // either the command line or a file can turn it on
+    if !settings.is_empty() && raw.iter().any(|arg| arg == "--debug") {
+        eprintln!("{}", config::describe(&settings));
+    }
+    let registry: Option<PathBuf> =
+        Arguments::from_vec(raw.clone()).opt_value_from_str("--device-registry")?;
+    let registered = match (registry, raw.last()) {
+        (Some(path), Some(device)) => registry::lookup(&path, device).map_err(|err| {
+            ArgsError::Invalid(format!("'--device-registry' {}: {}", path.display(), err))
+        })?,
+        _ => None,
+    };
+    let is_registered = registered.is_some();
+    let mut args = parse_args_with(&mut Arguments::from_vec(raw.clone()), registered)?;
+    args.raw = raw;
+    args.registered = is_registered;
+    Ok(args)
+}
+
+// a value from the options, unless it's the registry's curve file the default comes from, which
+// then takes the place of them
+fn given_or<T>(given: Option<T>, default: T, registered: bool) -> T {
+    match given {
+        Some(given) if !registered => given,
+        _ => default,
+    }
+}
+
+fn parse_args_with(
+    arguments: &mut Arguments,
+    registered: Option<CurveFile>,
+) -> Result<Args, ArgsError> {
+    let scale = parse_resolution_scale(arguments)?;
+    // still taken so it isn't mistaken for the device
+    let _: Option<PathBuf> = arguments.opt_value_from_str("--device-registry")?;
+    let explicit = match arguments.opt_value_from_str::<_, PathBuf>("--import-curve")? {
+        Some(path) => Some(CurveFile::load(&path).map_err(|err| {
+            ArgsError::Invalid(format!("'--import-curve' {}: {}", path.display(), err))
+        })?),
+        None => None,
+    };
+    let is_registered = registered.is_some();
+    let imported = registered.or(explicit);
+    let (sens_mult, curve) = parse_curve(arguments, imported.as_ref(), is_registered)?;
+    let curve = match arguments.opt_value_from_fn("--cap-mode", parse_cap_mode)? {
+        None | Some(CapMode::Relative) => curve,
+        // a registered curve replaces the options, this one among them
+        Some(CapMode::Absolute) if is_registered => curve,
+        Some(CapMode::Absolute) if imported.is_some() => {
+            return Err(ArgsError::Invalid(
+                "'--cap-mode absolute' is for '-c', not an imported curve".to_string(),
+            ))
+        }
+        // the cap is in the reference screen's terms like -m, so it's scaled the same
+        Some(CapMode::Absolute) => absolute_cap(curve, sens_mult),
+    };
+    // anything else in the file is a default the options can still override
+    let directions = imported
+        .as_ref()
+        .map(|file| file.directions)
+        .unwrap_or_default();
+    let args = Args {
+        sens_mult: sens_mult * scale,
+        dynamic_mult_file: arguments.opt_value_from_str("--dynamic-mult-file")?,
+        fov_file: arguments.opt_value_from_str("--fov-file")?,
+        observed_gain_file: arguments.opt_value_from_str("--observed-gain-file")?,
+        focus_file: arguments.opt_value_from_str("--focus-file")?,
+        lock_mult: arguments
+            .opt_value_from_str("--lock-mult")?
+            .unwrap_or(sens_mult * scale),
+        curve,
+        speed_metric: given_or(
+            arguments.opt_value_from_fn("--speed-metric", parse_speed_metric)?,
+            imported
+                .as_ref()
+                .map_or_else(SpeedMetric::default, |file| file.speed_metric),
+            is_registered,
+        ),
+        min_move: arguments.contains("--min-move"),
+        flush_on_click: arguments.contains("--flush-on-click"),
+        panic_keys: arguments
+            .opt_value_from_fn("--panic-keys", parse_panic_keys)?
+            .unwrap_or_default(),
+        modifier_map: arguments.values_from_fn("--modifier-map", parse_modifier)?,
+        accum_bound: arguments
+            .opt_value_from_str("--accum-bound")?
+            .unwrap_or(1.0),
+        no_accum: arguments.contains("--no-accum"),
+        anti_drift: arguments
+            .opt_value_from_str("--anti-drift")?
+            .map(Duration::from_millis),
+        max_event_rate: arguments.opt_value_from_str("--max-event-rate")?,
+        min_report_interval: arguments
+            .opt_value_from_str("--min-report-interval")?
+            .map(Duration::from_millis),
+        power_supply: arguments.opt_value_from_str("--power-supply")?,
+        strict: arguments.contains("--strict"),
+        strict_flush_after: arguments
+            .opt_value_from_str("--strict-flush-after")?
+            .map(Duration::from_millis),
+        syn_timeout: arguments
+            .opt_value_from_str("--syn-timeout")?
+            .map(Duration::from_millis),
+        scroll_up_accel: arguments.opt_value_from_str("--scroll-up-accel")?,
+        scroll_down_accel: arguments.opt_value_from_str("--scroll-down-accel")?,
+        touchpad_mode: arguments.contains("--touchpad-mode"),
+        touchpad_scroll_accel: arguments
+            .opt_value_from_str("--touchpad-scroll-accel")?
+            .unwrap_or(0.02),
+        smooth_scroll: arguments.opt_value_from_str("--smooth-scroll")?,
+        click_stabilize: arguments.opt_value_from_str("--click-stabilize")?,
+        click_stabilize_mult: arguments
+            .opt_value_from_str("--click-stabilize-mult")?
+            .unwrap_or(0.5),
+        swap_xy: arguments.contains("--swap-xy"),
+        extra_axes: arguments
+            .opt_value_from_fn("--axes", parse_axes)?
+            .unwrap_or_default(),
+        max_x_speed: arguments.opt_value_from_str("--max-x-speed")?,
+        max_y_speed: arguments.opt_value_from_str("--max-y-speed")?,
+        preserve_direction: arguments.contains("--preserve-direction"),
+        accel_after: arguments.opt_value_from_str("--accel-after")?,
+        path_length_speed: arguments.contains("--path-length-speed"),
+        flick_threshold: arguments.opt_value_from_str("--flick-threshold")?,
+        flick_cooldown: arguments.opt_value_from_str("--flick-cooldown")?,
+        accel_budget: arguments.opt_value_from_str("--accel-budget")?,
+        adaptive_offset: arguments.contains("--adaptive-offset"),
+        adaptive_offset_percentile: arguments
+            .opt_value_from_str("--adaptive-offset-percentile")?
+            .unwrap_or(50.0),
+        adaptive_offset_rate: arguments
+            .opt_value_from_str("--adaptive-offset-rate")?
+            .unwrap_or(0.5),
+        static_friction: arguments.opt_value_from_str("--static-friction")?,
+        speed_hysteresis: arguments.opt_value_from_str("--speed-hysteresis")?,
+        reset_accum_on_reload: arguments.contains("--reset-accum-on-reload"),
+        input_smooth: arguments.opt_value_from_str("--input-smooth")?,
+        output_average: arguments.opt_value_from_str("--output-average")?,
+        max_mult_slew: arguments.opt_value_from_str("--max-mult-slew")?,
+        one_euro: arguments.contains("--one-euro"),
+        min_cutoff: arguments.opt_value_from_str("--mincutoff")?.unwrap_or(1.0),
+        beta: arguments.opt_value_from_str("--beta")?.unwrap_or(0.007),
+        directional_accel: arguments.contains("--directional-accel"),
+        reversal_angle: arguments
+            .opt_value_from_str("--reversal-angle")?
+            .unwrap_or(90.0),
+        monotonic_time: arguments.contains("--monotonic-time"),
+        input_scale: arguments
+            .opt_value_from_str("--input-scale")?
+            .unwrap_or(1.0),
+        x_pos_mult: given_or(
+            arguments.opt_value_from_str("--x-pos-mult")?,
+            directions.x_pos,
+            is_registered,
+        ),
+        x_neg_mult: given_or(
+            arguments.opt_value_from_str("--x-neg-mult")?,
+            directions.x_neg,
+            is_registered,
+        ),
+        y_pos_mult: given_or(
+            arguments.opt_value_from_str("--y-pos-mult")?,
+            directions.y_pos,
+            is_registered,
+        ),
+        y_neg_mult: given_or(
+            arguments.opt_value_from_str("--y-neg-mult")?,
+            directions.y_neg,
+            is_registered,
+        ),
+        diagonal_compensation: arguments
+            .opt_value_from_str("--diagonal-compensation")?
+            .unwrap_or(0.0),
+        backend: arguments
+            .opt_value_from_fn("--backend", parse_backend)?
+            .unwrap_or(Backend::EvdevRs),
+        emit_evemu: arguments.contains("--emit-evemu"),
+        emit_raw: arguments.contains("--emit-raw"),
+        forward_all_syn: arguments.contains("--forward-all-syn"),
+        mirror_device: arguments.opt_value_from_str("--mirror-device")?,
+        heartbeat_device: arguments.opt_value_from_str("--heartbeat-device")?,
+        heartbeat_interval: Duration::from_millis(
+            arguments
+                .opt_value_from_str("--heartbeat-interval")?
+                .unwrap_or(1000),
+        ),
+        dry_run: arguments.contains("--dry-run"),
+        no_create_output: arguments.opt_value_from_str("--no-create-output")?,
+        batch_writes: arguments.contains("--batch-writes"),
+        threaded: arguments.contains("--threaded"),
+        logind: arguments.contains("--logind"),
+        debug: arguments.contains("--debug"),
+        debug_speeds: arguments
+            .opt_value_from_str("--debug-speed-min")?
+            .unwrap_or(f64::NEG_INFINITY)
+            ..=arguments
+                .opt_value_from_str("--debug-speed-max")?
+                .unwrap_or(f64::INFINITY),
+        control_fifo: arguments.opt_value_from_str("--control-fifo")?,
+        idle_poll_interval: Duration::from_millis(
+            arguments
+                .opt_value_from_str("--idle-poll-interval")?
+                .unwrap_or(100),
+        ),
+        debug_udp: arguments.opt_value_from_fn("--debug-udp", parse_socket_addr)?,
+        preview_stream: arguments.opt_value_from_str("--preview-stream")?,
+        emit_multiplier_to: arguments.opt_value_from_str("--emit-multiplier-to")?,
+        no_transform: arguments.contains("--no-transform"),
+        pattern_speed: arguments
+            .opt_value_from_str("--pattern-speed")?
+            .unwrap_or(2.0),
+        replay_speed: arguments
+            .opt_value_from_str("--replay-speed")?
+            .unwrap_or(1.0),
+        // filled in by parse_args, which has the whole command line
+        raw: Vec::new(),
+        registered: false,
+        print_config: arguments.contains("--print-config"),
+        print_config_continue: arguments.contains("--print-config-continue"),
+        mode: parse_mode(arguments)?,
+    };
+    validate(&args).map_err(ArgsError::Invalid)?;
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::parse_args;
+    use crate::accel::sensitivity;
+
+    // what the options are rejected with, if they're rejected
+    fn rejection(options: &str) -> Option<String> {
+        let options = options.split_whitespace().chain(["selftest-device"]);
+        parse_args(options.map(OsString::from).collect())
+            .err()
+            .map(|err| err.to_string())
+    }
+
+    fn rejected_for(options: &str, expected: &str) -> Result<(), String> {
+        match rejection(options) {
+            Some(err) if err == expected => Ok(()),
+            Some(err) => Err(format!("{:?} rejected with {:?}", options, err)),
+            None => Err(format!("{:?} accepted", options)),
+        }
+    }
+
+    // against a 1440p reference, a 1080p screen gets 0.75x the multiplier and a 4k one 1.5x, and
+    // so does the most it reaches, whether -c caps the curve or the sensitivity
+    #[test]
+    fn resolution_scaling() -> Result<(), String> {
+        for (screen, mult, capped) in [("1080", 1.5, 4.5), ("2160", 3.0, 9.0)] {
+            for (mode, capped) in [("relative", capped), ("absolute", capped / 2.0)] {
+                let options = format!(
+                    "-m 2 -a 1 -c 3 --cap-mode {} --screen-height {} --normalize-to 1440 \
+                     selftest-device",
+                    mode, screen
+                );
+                let args = parse_args(options.split_whitespace().map(OsString::from).collect())
+                    .map_err(|err| format!("{:?}: {}", options, err))?;
+                let slow = sensitivity(args.sens_mult, &args.curve, 0.0, &mut false);
+                let fast = sensitivity(args.sens_mult, &args.curve, 1000.0, &mut false);
+                if (slow - mult).abs() > 1e-9 || (fast - capped).abs() > 1e-9 {
+                    return Err(format!(
+                        "{:?}: expected {} to {}, got {} to {}",
+                        options, mult, capped, slow, fast
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // a named curve can't have another curve's options, but a segmented one can have its segments
+    #[test]
+    fn curve_conflicts() -> Result<(), String> {
+        rejected_for(
+            "-m 1 --curve expr --breakaway 5",
+            "'--curve expr' can't be combined with '--breakaway' or '--velocity-map'",
+        )?;
+        rejected_for(
+            "-m 1 --curve velocity-map --expr s",
+            "'--curve velocity-map' can't be combined with '--breakaway' or '--expr'",
+        )?;
+        rejected_for(
+            "-m 1 --curve linear --segment {\"from\":0,\"type\":\"linear\",\"accel\":0}",
+            "'--segment' can't be combined with other options setting the curve",
+        )?;
+        match rejection(
+            "-m 1 --curve segmented --segment {\"from\":0,\"type\":\"linear\",\"accel\":0}",
+        ) {
+            None => Ok(()),
+            Some(err) => Err(format!(
+                "segments of a named segmented curve rejected with {:?}",
+                err
+            )),
+        }
+    }
+
+    #[test]
+    fn negative_sens_mult() -> Result<(), String> {
+        rejected_for("-m -1 -a 0.1", "'-m' must be positive, got -1")?;
+        rejected_for("-m 0 -a 0.1", "'-m' must be positive, got 0")
+    }
+
+    #[test]
+    fn negative_cap() -> Result<(), String> {
+        rejected_for("-m 1 -a 0.1 -c -0.5", "'-c' must be at least 0, got -0.5")?;
+        rejected_for("-m 1 -a 0.1 -c NaN", "'-c' must be at least 0, got NaN")?;
+        // the default, no cap
+        match rejection("-m 1 -a 0.1 -c inf") {
+            None => Ok(()),
+            Some(err) => Err(format!("an infinite cap rejected with {:?}", err)),
+        }
+    }
+
+    #[test]
+    fn non_finite_accel() -> Result<(), String> {
+        rejected_for("-m 1 -a NaN", "'-a' must be finite, got NaN")?;
+        rejected_for("-m 1 -a inf", "'-a' must be finite, got inf")?;
+        rejected_for("-m 1 -a -inf", "'-a' must be finite, got -inf")
+    }
+
+    #[test]
+    fn non_finite_offset() -> Result<(), String> {
+        rejected_for("-m 1 -a 0.1 -o NaN", "'-o' must be finite, got NaN")?;
+        rejected_for("-m 1 -a 0.1 -o inf", "'-o' must be finite, got inf")
+    }
+}
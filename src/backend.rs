@@ -0,0 +1,714 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    path::{Path, PathBuf},
+    sync::mpsc::Receiver,
+    time::Duration,
+};
+
+use evdev_rs::{
+    enums::{EventCode, EventType, EV_KEY, EV_REL, EV_SYN},
+    Device, DeviceWrapper, GrabMode, InputEvent, ReadFlag, ReadStatus, UInputDevice, UninitDevice,
+};
+
+#[cfg(feature = "backend-evdev")]
+pub mod evdev;
+pub mod evemu;
+pub mod heartbeat;
+pub mod mirror;
+#[cfg(any(feature = "logind", test))]
+pub mod opener;
+pub mod pattern;
+pub mod raw;
+pub mod replay;
+pub mod threaded;
+
+// a grabbed source device paired with the virtual device its accelerated events go to
+pub trait InputBackend {
+    // blocks until the source has an event, None once the source has no more events to give
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>>;
+    // waits up to timeout for next_event to have something without blocking
+    fn wait(&mut self, _timeout: Duration) -> io::Result<bool> {
+        Ok(true)
+    }
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()>;
+    // write syscalls made so far, for seeing what batching saves
+    fn write_calls(&self) -> u64 {
+        0
+    }
+    // how long the last event read waited to be taken after coming off the device, for backends
+    // that read ahead
+    fn queued(&self) -> Option<Duration> {
+        None
+    }
+    fn grab(&mut self) -> io::Result<()>;
+    fn ungrab(&mut self) -> io::Result<()>;
+    // removes the virtual device right away instead of leaving it to the process exiting, so it's
+    // gone from /dev/input before a restart creates the next one; anything written after is
+    // dropped
+    fn destroy(&mut self) {}
+}
+
+// where accelerated events go
+pub enum Output {
+    // dropped
+    Discard,
+    // a new virtual device copying the source, with hi-res axes added for the wheels it has if
+    // hi_res_scroll
+    Create { hi_res_scroll: bool },
+    // an existing device (like an earlier stage's virtual device) written to directly, which the
+    // kernel passes on as if the device sent them
+    Existing(PathBuf),
+}
+
+// a signal getting in the way counts as a timeout
+pub fn poll_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+        -1 => match io::Error::last_os_error() {
+            err if err.kind() == io::ErrorKind::Interrupted => Ok(false),
+            err => Err(err),
+        },
+        ready => Ok(ready > 0),
+    }
+}
+
+// what to do about the virtual device not being created, for the errors where it's clear
+fn uinput_hint(err: &io::Error) -> Option<&'static str> {
+    match err.raw_os_error()? {
+        // a static node without the module behind it gives ENODEV
+        libc::ENOENT | libc::ENODEV => Some(
+            "/dev/uinput is missing, so the uinput module probably isn't loaded; load it with \
+             `modprobe uinput`, or write to an existing device with --no-create-output",
+        ),
+        libc::EACCES | libc::EPERM => Some(
+            "no permission to open /dev/uinput; run as root or give this user write access to it",
+        ),
+        _ => None,
+    }
+}
+
+// kept the same kind so it's still matched on the same way
+pub fn uinput_error(err: io::Error) -> io::Error {
+    match uinput_hint(&err) {
+        Some(hint) => io::Error::new(
+            err.kind(),
+            format!("couldn't create the virtual device: {}: {}", err, hint),
+        ),
+        None => err,
+    }
+}
+
+// each wheel and its hi-res counterpart
+const HI_RES_WHEELS: [(EV_REL, EV_REL); 2] = [
+    (EV_REL::REL_WHEEL, EV_REL::REL_WHEEL_HI_RES),
+    (EV_REL::REL_HWHEEL, EV_REL::REL_HWHEEL_HI_RES),
+];
+
+// a virtual device that can pass for a mouse, with nothing to copy it from
+pub fn create_pointer(name: &str) -> io::Result<UInputDevice> {
+    let device = UninitDevice::new().ok_or_else(|| io::Error::other("couldn't allocate device"))?;
+    device.set_name(name);
+    device.enable_event_type(&EventType::EV_REL)?;
+    for code in [
+        EV_REL::REL_X,
+        EV_REL::REL_Y,
+        EV_REL::REL_WHEEL,
+        EV_REL::REL_HWHEEL,
+    ] {
+        device.enable_event_code(&EventCode::EV_REL(code), None)?;
+    }
+    // without a button nothing takes it for a pointer
+    device.enable_event_type(&EventType::EV_KEY)?;
+    for button in [
+        EV_KEY::BTN_LEFT,
+        EV_KEY::BTN_RIGHT,
+        EV_KEY::BTN_MIDDLE,
+        EV_KEY::BTN_SIDE,
+        EV_KEY::BTN_EXTRA,
+    ] {
+        device.enable_event_code(&EventCode::EV_KEY(button), None)?;
+    }
+    UInputDevice::create_from_device(&device).map_err(uinput_error)
+}
+
+enum Sink {
+    Uinput(UInputDevice),
+    Device(File),
+}
+
+// the writing half of the evdev-rs backend, which the threaded backend splits off
+struct Writer {
+    // None drops everything written
+    out: Option<Sink>,
+    // when batching, events are held until the SYN_REPORT ending their frame and the frame is
+    // written in one go
+    batch: Option<Vec<libc::input_event>>,
+    write_calls: u64,
+}
+
+pub struct EvdevRsBackend {
+    source: Device,
+    writer: Writer,
+    // a new file for the source each time the one it has is revoked and given back, from a
+    // session manager that took it
+    resumed: Option<Receiver<File>>,
+    grabbed: bool,
+}
+
+impl EvdevRsBackend {
+    pub fn open(
+        path: impl AsRef<Path>,
+        output: &Output,
+        batch: bool,
+    ) -> io::Result<EvdevRsBackend> {
+        EvdevRsBackend::from_file(File::open(path)?, output, batch)
+    }
+
+    pub fn from_file(file: File, output: &Output, batch: bool) -> io::Result<EvdevRsBackend> {
+        let source = Device::new_from_file(file)?;
+        let out = match output {
+            Output::Discard => None,
+            Output::Create { hi_res_scroll } => {
+                if *hi_res_scroll {
+                    // only changes what libevdev has for the source, not the device itself
+                    for (wheel, hi_res) in HI_RES_WHEELS {
+                        if source.has(EventCode::EV_REL(wheel)) {
+                            source.enable_event_code(&EventCode::EV_REL(hi_res), None)?;
+                        }
+                    }
+                }
+                Some(Sink::Uinput(
+                    UInputDevice::create_from_device(&source).map_err(uinput_error)?,
+                ))
+            }
+            Output::Existing(path) => Some(Sink::open_existing(path)?),
+        };
+        Ok(EvdevRsBackend {
+            source,
+            writer: Writer {
+                out,
+                batch: batch.then(Vec::new),
+                write_calls: 0,
+            },
+            resumed: None,
+            grabbed: false,
+        })
+    }
+
+    #[cfg(feature = "logind")]
+    pub fn with_resumed(self, resumed: Receiver<File>) -> EvdevRsBackend {
+        EvdevRsBackend {
+            resumed: Some(resumed),
+            ..self
+        }
+    }
+
+    // the node of the virtual device created to write to, if one was
+    #[cfg(test)]
+    pub fn created_node(&self) -> Option<String> {
+        match self.writer.out {
+            Some(Sink::Uinput(ref device)) => device.devnode().map(String::from),
+            _ => None,
+        }
+    }
+
+    // waits out the source being revoked, until it's given back
+    fn resume(&mut self, err: io::Error) -> io::Result<()> {
+        let Some(ref resumed) = self.resumed else {
+            return Err(err);
+        };
+        let file = resumed.recv().map_err(|_| err)?;
+        self.source.change_file(file)?;
+        // a new file starts out ungrabbed
+        if self.grabbed {
+            self.source.grab(GrabMode::Grab)?;
+        }
+        Ok(())
+    }
+}
+
+// blocks for the next event
+fn read_event(source: &Device) -> io::Result<InputEvent> {
+    let (status, event) = source.next_event(ReadFlag::NORMAL | ReadFlag::BLOCKING)?;
+    if status == ReadStatus::Sync {
+        // eat syncs until done (we probably don't need whats in it)
+        // the event itself is the SYN_DROPPED, so the caller still gets to know about it
+        while source
+            .next_event(ReadFlag::SYNC | ReadFlag::BLOCKING)
+            .is_ok()
+        {}
+    }
+    Ok(event)
+}
+
+impl Writer {
+    // dropping the uinput device destroys it, an existing device is only closed
+    fn destroy(&mut self) {
+        self.out = None;
+        if let Some(ref mut frame) = self.batch {
+            frame.clear();
+        }
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        let Some(ref out) = self.out else {
+            return Ok(());
+        };
+        match self.batch {
+            Some(ref mut frame) => {
+                frame.push(event.as_raw());
+                if event.event_code != EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+                    return Ok(());
+                }
+                self.write_calls += 1;
+                let result = out.fd().and_then(|fd| write_frame(fd, frame));
+                frame.clear();
+                result
+            }
+            None => {
+                self.write_calls += 1;
+                out.write(event)
+            }
+        }
+    }
+}
+
+impl Sink {
+    fn fd(&self) -> io::Result<RawFd> {
+        match self {
+            Sink::Uinput(out) => out
+                .as_fd()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected)),
+            Sink::Device(file) => Ok(file.as_raw_fd()),
+        }
+    }
+
+    fn write(&self, event: &InputEvent) -> io::Result<()> {
+        match self {
+            Sink::Uinput(out) => out.write_event(event),
+            Sink::Device(file) => write_frame(file.as_raw_fd(), &[event.as_raw()]),
+        }
+    }
+
+    fn open_existing(path: &Path) -> io::Result<Sink> {
+        Ok(Sink::Device(File::options().write(true).open(path)?))
+    }
+}
+
+// uinput and event devices take any number of whole events per write, libevdev just never gives
+// uinput more than one
+pub fn write_frame(fd: RawFd, frame: &[libc::input_event]) -> io::Result<()> {
+    let len = std::mem::size_of_val(frame);
+    let written = unsafe { libc::write(fd, frame.as_ptr().cast(), len) };
+    if written < 0 {
+        Err(io::Error::last_os_error())
+    } else if written as usize != len {
+        Err(io::Error::from(io::ErrorKind::WriteZero))
+    } else {
+        Ok(())
+    }
+}
+
+impl InputBackend for EvdevRsBackend {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        loop {
+            match read_event(&self.source) {
+                Err(err) if err.raw_os_error() == Some(libc::ENODEV) => self.resume(err)?,
+                read => return read.map(Some),
+            }
+        }
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        // libevdev may already have events read off the fd
+        if self.source.has_event_pending() {
+            return Ok(true);
+        }
+        poll_readable(self.source.file().as_raw_fd(), timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        self.writer.write_event(event)
+    }
+
+    fn write_calls(&self) -> u64 {
+        self.writer.write_calls
+    }
+
+    fn grab(&mut self) -> io::Result<()> {
+        self.source.grab(GrabMode::Grab)?;
+        self.grabbed = true;
+        Ok(())
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.source.grab(GrabMode::Ungrab)?;
+        self.grabbed = false;
+        Ok(())
+    }
+
+    fn destroy(&mut self) {
+        self.writer.destroy();
+    }
+}
+
+// replays a scripted list of events and records everything written, for running the real
+// processing without a device
+#[derive(Default)]
+pub struct MockBackend {
+    events: VecDeque<InputEvent>,
+    pub written: Vec<InputEvent>,
+    pub grabbed: bool,
+    // writes fail afterwards the way they do once a device is removed
+    pub destroyed: bool,
+}
+
+impl MockBackend {
+    pub fn new(events: impl IntoIterator<Item = InputEvent>) -> MockBackend {
+        MockBackend {
+            events: events.into_iter().collect(),
+            ..MockBackend::default()
+        }
+    }
+}
+
+impl InputBackend for MockBackend {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        Ok(self.events.pop_front())
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        if self.destroyed {
+            return Err(io::Error::from_raw_os_error(libc::ENODEV));
+        }
+        self.written.push(event.clone());
+        Ok(())
+    }
+
+    fn grab(&mut self) -> io::Result<()> {
+        self.grabbed = true;
+        Ok(())
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.grabbed = false;
+        Ok(())
+    }
+
+    fn destroy(&mut self) {
+        self.destroyed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs::File, io, path::Path, sync::mpsc, thread, time::Duration};
+
+    use evdev_rs::{
+        enums::{EventCode, EV_REL, EV_SYN},
+        Device, InputEvent, ReadFlag, TimeVal, UInputDevice,
+    };
+
+    use super::{
+        create_pointer, uinput_error, uinput_hint, EvdevRsBackend, InputBackend, MockBackend,
+        Output,
+    };
+    use crate::{
+        accel::Accelerator, args::parse_args, control::Control, taps::Taps, testing::frames,
+    };
+
+    // a missing module and missing permissions each get their hint, kept in the error, and other
+    // errors are left as they are
+    #[test]
+    fn uinput_hints() -> Result<(), String> {
+        let cases = [
+            (libc::ENOENT, Some("modprobe uinput")),
+            (libc::ENODEV, Some("modprobe uinput")),
+            (libc::EACCES, Some("no permission")),
+            (libc::EPERM, Some("no permission")),
+            (libc::EINVAL, None),
+        ];
+        for (errno, expected) in cases {
+            let err = io::Error::from_raw_os_error(errno);
+            let hint = uinput_hint(&err);
+            let matched = match (hint, expected) {
+                (Some(hint), Some(expected)) => hint.contains(expected),
+                (hint, expected) => hint.is_none() && expected.is_none(),
+            };
+            if !matched {
+                return Err(format!("{}: expected {:?}, got {:?}", err, expected, hint));
+            }
+            let kind = err.kind();
+            let wrapped = uinput_error(err);
+            let message = wrapped.to_string();
+            if expected.is_some_and(|expected| !message.contains(expected))
+                || wrapped.kind() != kind
+            {
+                return Err(format!("{}: no hint in {:?}", errno, message));
+            }
+        }
+        Ok(())
+    }
+
+    // a new virtual pointer and its node, once the node's there to open
+    fn pointer(name: &str) -> Result<(UInputDevice, String), String> {
+        let device = create_pointer(name).map_err(|err| err.to_string())?;
+        let node = device.devnode().ok_or("no device node")?.to_string();
+        for _ in 0..100 {
+            if Path::new(&node).exists() {
+                return Ok((device, node));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Err(format!("{} never showed up", node))
+    }
+
+    fn frame(moved: &[(EV_REL, i32)]) -> Vec<InputEvent> {
+        let time = TimeVal::new(0, 0);
+        let moved = moved
+            .iter()
+            .map(|&(axis, value)| InputEvent::new(&time, &EventCode::EV_REL(axis), value));
+        let syn = InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0);
+        moved.chain([syn]).collect()
+    }
+
+    fn codes(events: &[InputEvent]) -> Vec<(EventCode, i32)> {
+        events
+            .iter()
+            .map(|event| (event.event_code, event.value))
+            .collect()
+    }
+
+    // what each backend has to do the same: a frame moved on the source is read back whole and in
+    // order once grabbed, and a frame written reaches the device written to as it was written;
+    // feed moves the source, and arrived takes that many events off wherever output goes
+    fn behaves<B: InputBackend>(
+        backend: &mut B,
+        mut feed: impl FnMut(&mut B, &InputEvent) -> Result<(), String>,
+        mut arrived: impl FnMut(&mut B, usize) -> Result<Vec<InputEvent>, String>,
+    ) -> Result<(), String> {
+        backend.grab().map_err(|err| err.to_string())?;
+
+        let moved = frame(&[(EV_REL::REL_X, 3), (EV_REL::REL_Y, 4)]);
+        for event in &moved {
+            feed(backend, event)?;
+        }
+        let mut read = Vec::new();
+        while read.len() < moved.len() {
+            let event = backend.next_event().map_err(|err| err.to_string())?;
+            read.push(event.ok_or("the source ended")?);
+        }
+        if codes(&read) != codes(&moved) {
+            return Err(format!("read {:?} for {:?}", codes(&read), codes(&moved)));
+        }
+
+        let written = frame(&[(EV_REL::REL_X, 12), (EV_REL::REL_Y, 16)]);
+        for event in &written {
+            backend.write_event(event).map_err(|err| err.to_string())?;
+        }
+        let arrived = arrived(backend, written.len())?;
+        if codes(&arrived) != codes(&written) {
+            return Err(format!(
+                "{:?} arrived for {:?}",
+                codes(&arrived),
+                codes(&written)
+            ));
+        }
+        backend.ungrab().map_err(|err| err.to_string())?;
+        Ok(())
+    }
+
+    // the same behavior on a real backend, moving a virtual source and watching a virtual sink
+    fn same_behavior<B: InputBackend>(
+        open: impl Fn(&str, &Output) -> io::Result<B>,
+    ) -> Result<(), String> {
+        let (source, source_node) = pointer("accelerator test source")?;
+        let (_sink, sink_node) = pointer("accelerator test sink")?;
+        let watcher = File::open(&sink_node).map_err(|err| err.to_string())?;
+        let watcher = Device::new_from_file(watcher).map_err(|err| err.to_string())?;
+        let output = Output::Existing(sink_node.into());
+        let mut backend = open(&source_node, &output).map_err(|err| err.to_string())?;
+        behaves(
+            &mut backend,
+            |_, event| source.write_event(event).map_err(|err| err.to_string()),
+            |_, count| {
+                let mut arrived = Vec::new();
+                while arrived.len() < count {
+                    let (_, event) = watcher
+                        .next_event(ReadFlag::NORMAL | ReadFlag::BLOCKING)
+                        .map_err(|err| err.to_string())?;
+                    arrived.push(event);
+                }
+                Ok(arrived)
+            },
+        )
+    }
+
+    // what the real backends are held to, on the mock every other test stands in for them with
+    #[test]
+    fn mock_behavior() -> Result<(), String> {
+        let mut backend = MockBackend::default();
+        behaves(
+            &mut backend,
+            |backend, event| {
+                backend.events.push_back(event.clone());
+                Ok(())
+            },
+            |backend, count| {
+                Ok(backend
+                    .written
+                    .drain(..count.min(backend.written.len()))
+                    .collect())
+            },
+        )?;
+        if backend.grabbed {
+            return Err("still grabbed".to_string());
+        }
+        Ok(())
+    }
+
+    // a stage reading one device, run the way the main loop runs it once it's grabbed its input,
+    // and the node of the virtual device it created if it did
+    fn stage(
+        input: String,
+        output: Output,
+        options: &'static str,
+    ) -> Result<Option<String>, String> {
+        let (grabbed, ready) = mpsc::channel();
+        thread::spawn(move || {
+            let given = options.split_whitespace().chain([input.as_str()]);
+            let args =
+                parse_args(given.map(OsString::from).collect()).map_err(|err| err.to_string());
+            let opened = args.and_then(|args| {
+                let mut backend = EvdevRsBackend::open(&input, &output, false)
+                    .and_then(|mut backend| backend.grab().map(|()| backend))
+                    .map_err(|err| err.to_string())?;
+                let _ = grabbed.send(Ok(backend.created_node()));
+                let (mut accel, mut taps) = (Accelerator::new(), Taps::none());
+                let _ = crate::run(
+                    &mut backend,
+                    &args,
+                    &mut accel,
+                    &mut taps,
+                    &Control::default(),
+                );
+                Ok(())
+            });
+            if let Err(err) = opened {
+                let _ = grabbed.send(Err(err));
+            }
+        });
+        ready
+            .recv_timeout(Duration::from_secs(5))
+            .map_err(|err| err.to_string())?
+    }
+
+    // two stages back to back, the second reading what the first wrote and writing to an existing
+    // device: the gains multiply, and the second stage accelerates by the speed the first wrote at
+    #[test]
+    fn chained_mock() -> Result<(), String> {
+        let cases = [
+            ("-m 2 -a 0", "-m 3 -a 0", [30, 42]),
+            // 10 counts a ms get 1 + 0.1 * 10, 14 get 1 + 0.1 * 14
+            ("-m 2 -a 0", "-m 1 -a 0.1", [20, 34]),
+        ];
+        for (first, second, expected) in cases {
+            let events = frames(&[
+                (0, &[]),
+                (1, &[(EV_REL::REL_X, 5)]),
+                (2, &[(EV_REL::REL_X, 7)]),
+            ]);
+            let run_stage = |options: &str, events: Vec<InputEvent>| {
+                let options = format!("{} --no-create-output /dev/null selftest-device", options);
+                let args = parse_args(options.split_whitespace().map(OsString::from).collect())
+                    .map_err(|err| format!("bad options: {}", err))?;
+                let mut backend = MockBackend::new(events);
+                let (mut accel, mut taps) = (Accelerator::new(), Taps::none());
+                crate::run(
+                    &mut backend,
+                    &args,
+                    &mut accel,
+                    &mut taps,
+                    &Control::default(),
+                )
+                .map_err(|err| err.to_string())?;
+                Ok::<_, String>(backend.written)
+            };
+            let chained = run_stage(second, run_stage(first, events)?)?;
+            let moved: Vec<_> = chained
+                .iter()
+                .filter(|event| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+                .map(|event| event.value)
+                .collect();
+            if moved != expected {
+                return Err(format!(
+                    "{} then {}: expected {:?}, got {:?}",
+                    first, second, expected, moved
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // two stages chained through the virtual device the first creates and the second reads, each
+    // scaling by its own -m, and only the second's output reaches the device at the end
+    #[test]
+    #[ignore = "needs /dev/uinput"]
+    fn chained() -> Result<(), String> {
+        let (source, source_node) = pointer("accelerator test source")?;
+        let (_sink, sink_node) = pointer("accelerator test sink")?;
+        let watcher = File::open(&sink_node).map_err(|err| err.to_string())?;
+        let watcher = Device::new_from_file(watcher).map_err(|err| err.to_string())?;
+        let create = Output::Create {
+            hi_res_scroll: false,
+        };
+        let chain_node = stage(source_node, create, "-m 2 -a 0")?.ok_or("no virtual device")?;
+        for _ in 0..100 {
+            if Path::new(&chain_node).exists() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        stage(chain_node, Output::Existing(sink_node.into()), "-m 3 -a 0")?;
+
+        for event in frame(&[(EV_REL::REL_X, 5)]) {
+            source.write_event(&event).map_err(|err| err.to_string())?;
+        }
+        // the y of 0 written with it is dropped by the kernel, which passes on no movement
+        let expected = frame(&[(EV_REL::REL_X, 30)]);
+        let mut arrived = Vec::new();
+        while arrived.len() < expected.len() {
+            let (_, event) = watcher
+                .next_event(ReadFlag::NORMAL | ReadFlag::BLOCKING)
+                .map_err(|err| err.to_string())?;
+            arrived.push(event);
+        }
+        if codes(&arrived) != codes(&expected) {
+            return Err(format!("{:?} arrived through both stages", codes(&arrived)));
+        }
+        Ok(())
+    }
+
+    // creating the source and sink takes /dev/uinput, which CI doesn't have
+    #[test]
+    #[ignore = "needs /dev/uinput"]
+    fn evdev_rs_behavior() -> Result<(), String> {
+        same_behavior(|path, output| EvdevRsBackend::open(path, output, false))?;
+        same_behavior(|path, output| EvdevRsBackend::open(path, output, true))
+    }
+
+    #[cfg(feature = "backend-evdev")]
+    #[test]
+    #[ignore = "needs /dev/uinput"]
+    fn evdev_behavior() -> Result<(), String> {
+        same_behavior(|path, output| super::evdev::EvdevBackend::open(path, output))
+    }
+}
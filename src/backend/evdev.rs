@@ -0,0 +1,153 @@
+use std::{collections::VecDeque, io, os::unix::io::AsRawFd, path::Path, time::Duration};
+
+use evdev::{
+    uinput::{VirtualDevice, VirtualDeviceBuilder},
+    AttributeSet, Device, RelativeAxisType,
+};
+use evdev_rs::{
+    enums::{EventCode, EV_SYN},
+    InputEvent,
+};
+
+use super::{poll_readable, uinput_error, InputBackend, Output};
+
+enum Sink {
+    Virtual(VirtualDevice),
+    // boxed since it's much bigger, carrying all of the device's state
+    Device(Box<Device>),
+}
+
+// the rest of the program still speaks evdev-rs events, so they're converted at the edges through
+// the shared kernel struct
+
+pub struct EvdevBackend {
+    source: Device,
+    // None drops everything written
+    out: Option<Sink>,
+    pending: VecDeque<InputEvent>,
+    // VirtualDevice::emit always terminates a batch with its own SYN_REPORT, so a frame is held
+    // until the SYN_REPORT is written
+    frame: Vec<evdev::InputEvent>,
+    write_calls: u64,
+}
+
+impl EvdevBackend {
+    pub fn open(path: impl AsRef<Path>, output: &Output) -> io::Result<EvdevBackend> {
+        let source = Device::open(path)?;
+        let out = match output {
+            Output::Discard => None,
+            Output::Create { hi_res_scroll } => {
+                Some(Sink::Virtual(create_output_for(&source, *hi_res_scroll)?))
+            }
+            Output::Existing(path) => Some(Sink::Device(Box::new(Device::open(path)?))),
+        };
+        Ok(EvdevBackend {
+            source,
+            out,
+            pending: VecDeque::new(),
+            frame: Vec::new(),
+            write_calls: 0,
+        })
+    }
+}
+
+fn create_output_for(source: &Device, hi_res_scroll: bool) -> io::Result<VirtualDevice> {
+    let name = source.name().unwrap_or("accelerator").to_string();
+    let mut builder = VirtualDeviceBuilder::new()
+        .map_err(uinput_error)?
+        .name(&name)
+        .input_id(source.input_id())
+        .with_properties(source.properties())?;
+    if let Some(keys) = source.supported_keys() {
+        builder = builder.with_keys(keys)?;
+    }
+    if let Some(axes) = source.supported_relative_axes() {
+        let mut axes: AttributeSet<RelativeAxisType> = axes.iter().collect();
+        if hi_res_scroll {
+            for (wheel, hi_res) in [
+                (
+                    RelativeAxisType::REL_WHEEL,
+                    RelativeAxisType::REL_WHEEL_HI_RES,
+                ),
+                (
+                    RelativeAxisType::REL_HWHEEL,
+                    RelativeAxisType::REL_HWHEEL_HI_RES,
+                ),
+            ] {
+                if axes.contains(wheel) {
+                    axes.insert(hi_res);
+                }
+            }
+        }
+        builder = builder.with_relative_axes(&axes)?;
+    }
+    if let Some(misc) = source.misc_properties() {
+        builder = builder.with_msc(misc)?;
+    }
+    builder.build()
+}
+
+impl InputBackend for EvdevBackend {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        // fetch_events handles SYN_DROPPED itself by synthesizing the missed state changes
+        while self.pending.is_empty() {
+            self.pending.extend(
+                self.source
+                    .fetch_events()?
+                    .map(|event| InputEvent::from_raw(event.as_ref())),
+            );
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        if !self.pending.is_empty() {
+            return Ok(true);
+        }
+        poll_readable(self.source.as_raw_fd(), timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        match event.event_code {
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                let result = match self.out {
+                    Some(Sink::Virtual(ref mut out)) => {
+                        // emit is a write_all of the frame then another of the SYN_REPORT it adds
+                        // (as of evdev 0.12), and uinput takes a whole frame in one write
+                        self.write_calls += 2;
+                        out.emit(&self.frame)
+                    }
+                    Some(Sink::Device(ref mut out)) => {
+                        self.write_calls += 1;
+                        self.frame.push(evdev::InputEvent::from(event.as_raw()));
+                        out.send_events(&self.frame)
+                    }
+                    None => Ok(()),
+                };
+                self.frame.clear();
+                result
+            }
+            _ => {
+                self.frame.push(evdev::InputEvent::from(event.as_raw()));
+                Ok(())
+            }
+        }
+    }
+
+    fn write_calls(&self) -> u64 {
+        self.write_calls
+    }
+
+    fn grab(&mut self) -> io::Result<()> {
+        self.source.grab()
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.source.ungrab()
+    }
+
+    fn destroy(&mut self) {
+        self.out = None;
+        self.frame.clear();
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    io::{self, BufWriter, Stdout, Write},
+    time::Duration,
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_SYN},
+    util::event_code_to_int,
+    InputEvent,
+};
+
+use super::InputBackend;
+
+// prints everything written to stdout in evemu's event format before passing it on, so the output
+// can be piped into evemu-play and friends
+pub struct EvemuTee<B, W = BufWriter<Stdout>> {
+    inner: B,
+    out: W,
+}
+
+impl<B: InputBackend> EvemuTee<B> {
+    pub fn new(inner: B) -> EvemuTee<B> {
+        EvemuTee::with_writer(inner, BufWriter::new(io::stdout()))
+    }
+}
+
+impl<B: InputBackend, W: Write> EvemuTee<B, W> {
+    pub fn with_writer(inner: B, out: W) -> EvemuTee<B, W> {
+        EvemuTee { inner, out }
+    }
+
+    #[cfg(test)]
+    pub fn writer(&self) -> &W {
+        &self.out
+    }
+}
+
+impl<B: InputBackend, W: Write> InputBackend for EvemuTee<B, W> {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        self.inner.next_event()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait(timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        let (event_type, code) = event_code_to_int(&event.event_code);
+        writeln!(
+            self.out,
+            "E: {}.{:06} {:04x} {:04x} {:04}",
+            event.time.tv_sec, event.time.tv_usec, event_type, code, event.value
+        )?;
+        // whole frames at a time, so readers never see half of one
+        if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+            self.out.flush()?;
+        }
+        self.inner.write_event(event)
+    }
+
+    fn write_calls(&self) -> u64 {
+        self.inner.write_calls()
+    }
+
+    fn queued(&self) -> Option<Duration> {
+        self.inner.queued()
+    }
+
+    fn grab(&mut self) -> io::Result<()> {
+        self.inner.grab()
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.inner.ungrab()
+    }
+
+    fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::{
+        enums::{EventCode, EV_REL, EV_SYN},
+        InputEvent, TimeVal,
+    };
+
+    use super::EvemuTee;
+    use crate::backend::{InputBackend, MockBackend};
+
+    // a frame comes out as evemu-record writes it, microseconds padded to six digits, and is
+    // still passed on
+    #[test]
+    fn evemu_lines() -> Result<(), String> {
+        let time = TimeVal::new(1_700_000_000, 4_200);
+        let events = [
+            (EventCode::EV_REL(EV_REL::REL_X), 5),
+            (EventCode::EV_REL(EV_REL::REL_Y), -3),
+            (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+        ]
+        .map(|(code, value)| InputEvent::new(&time, &code, value));
+        let mut tee = EvemuTee::with_writer(MockBackend::default(), Vec::new());
+        for event in &events {
+            tee.write_event(event).map_err(|err| err.to_string())?;
+        }
+        let printed = String::from_utf8_lossy(tee.writer());
+        let expected = "E: 1700000000.004200 0002 0000 0005\n\
+                        E: 1700000000.004200 0002 0001 -003\n\
+                        E: 1700000000.004200 0000 0000 0000\n";
+        if printed != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, printed));
+        }
+        if tee.inner.written.len() != events.len() {
+            return Err(format!("passed on {} events", tee.inner.written.len()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use evdev_rs::{
+    enums::{EventCode, EventType, EV_MSC, EV_SYN},
+    DeviceWrapper, InputEvent, TimeVal, UInputDevice, UninitDevice,
+};
+
+use super::uinput_error;
+
+// the beats are MSC_SERIAL events numbered from 0 at startup, with this one at shutdown
+pub const HEARTBEAT_CODE: EV_MSC = EV_MSC::MSC_SERIAL;
+pub const SHUTDOWN_BEAT: i32 = -1;
+
+// when beats are due, the first right away
+pub struct Heartbeat {
+    interval: Duration,
+    next_due: Instant,
+    beats: i32,
+}
+
+impl Heartbeat {
+    pub fn new(interval: Duration, now: Instant) -> Heartbeat {
+        Heartbeat {
+            interval,
+            next_due: now,
+            beats: 0,
+        }
+    }
+
+    pub fn due_in(&self, now: Instant) -> Duration {
+        self.next_due.saturating_duration_since(now)
+    }
+
+    // the number of the beat due by now, if one is; the beats keep to the interval however late
+    // they're checked for, but any missed entirely aren't made up for
+    pub fn poll(&mut self, now: Instant) -> Option<i32> {
+        if now < self.next_due {
+            return None;
+        }
+        let beat = self.beats;
+        self.beats = self.beats.wrapping_add(1).max(0);
+        self.next_due += self.interval;
+        if self.next_due <= now {
+            self.next_due = now + self.interval;
+        }
+        Some(beat)
+    }
+}
+
+// a virtual device of its own the beats are written to, so a supervisor can tell accelerator is
+// alive without anything being moved, and nothing reading the real output sees them
+pub struct HeartbeatDevice {
+    heartbeat: Heartbeat,
+    device: UInputDevice,
+    // a broken device is only mentioned once
+    failed: bool,
+}
+
+impl HeartbeatDevice {
+    pub fn create(name: &str, interval: Duration) -> io::Result<HeartbeatDevice> {
+        let device =
+            UninitDevice::new().ok_or_else(|| io::Error::other("couldn't allocate device"))?;
+        device.set_name(name);
+        device.enable_event_type(&EventType::EV_MSC)?;
+        device.enable_event_code(&EventCode::EV_MSC(HEARTBEAT_CODE), None)?;
+        Ok(HeartbeatDevice {
+            heartbeat: Heartbeat::new(interval, Instant::now()),
+            device: UInputDevice::create_from_device(&device).map_err(uinput_error)?,
+            failed: false,
+        })
+    }
+
+    // writes the beat due by now, if there is one, and says how long until the next
+    pub fn poll(&mut self, now: Instant) -> Duration {
+        if let Some(beat) = self.heartbeat.poll(now) {
+            self.write(beat);
+        }
+        self.heartbeat.due_in(now)
+    }
+
+    pub fn shutdown(&mut self) {
+        self.write(SHUTDOWN_BEAT);
+    }
+
+    fn write(&mut self, beat: i32) {
+        let time = TimeVal::new(0, 0);
+        let result = [
+            (EventCode::EV_MSC(HEARTBEAT_CODE), beat),
+            (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+        ]
+        .iter()
+        .try_for_each(|(code, value)| {
+            self.device
+                .write_event(&InputEvent::new(&time, code, *value))
+        });
+        if let Err(err) = result {
+            if !self.failed {
+                eprintln!("Warning: writing to the heartbeat device failed: {}", err);
+                self.failed = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Heartbeat;
+
+    // a beat right away at startup, then every 100ms; a late check still gets its beat, and the
+    // next stays on time rather than a burst catching up
+    #[test]
+    fn heartbeat_interval() -> Result<(), String> {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut heartbeat = Heartbeat::new(Duration::from_millis(100), start);
+        let mut beats = Vec::new();
+        for ms in [0, 50, 99, 100, 150, 250, 260, 349, 350] {
+            if let Some(beat) = heartbeat.poll(at(ms)) {
+                beats.push((ms, beat));
+            }
+        }
+        if beats != [(0, 0), (100, 1), (250, 2), (349, 3)] {
+            return Err(format!(
+                "expected beats 0 to 3 at 0, 100, 250 and 349ms, got {:?}",
+                beats
+            ));
+        }
+        let due = heartbeat.due_in(at(360));
+        if due != Duration::from_millis(40) {
+            return Err(format!("expected the next beat in 40ms, got {:?}", due));
+        }
+        Ok(())
+    }
+}
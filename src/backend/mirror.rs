@@ -0,0 +1,168 @@
+use std::{io, time::Duration};
+
+use evdev_rs::{InputEvent, UInputDevice};
+
+use super::{create_pointer, InputBackend};
+
+// where the mirrored events go
+pub trait MirrorSink {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()>;
+}
+
+impl MirrorSink for UInputDevice {
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        UInputDevice::write_event(self, event)
+    }
+}
+
+// writes everything to a second virtual device too, for watching the output without getting in
+// its way
+pub struct Mirror<B, M = UInputDevice> {
+    inner: B,
+    // None once destroyed
+    mirror: Option<M>,
+    // a broken mirror is only mentioned once
+    failed: bool,
+}
+
+impl<B: InputBackend> Mirror<B> {
+    pub fn new(inner: B, name: &str) -> io::Result<Mirror<B>> {
+        Ok(Mirror::with_mirror(inner, create_pointer(name)?))
+    }
+}
+
+impl<B: InputBackend, M: MirrorSink> Mirror<B, M> {
+    pub fn with_mirror(inner: B, mirror: M) -> Mirror<B, M> {
+        Mirror {
+            inner,
+            mirror: Some(mirror),
+            failed: false,
+        }
+    }
+}
+
+impl<B: InputBackend, M: MirrorSink> InputBackend for Mirror<B, M> {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        self.inner.next_event()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait(timeout)
+    }
+
+    // the mirror failing never stops the real output
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        self.inner.write_event(event)?;
+        let Some(ref mut mirror) = self.mirror else {
+            return Ok(());
+        };
+        if let Err(err) = mirror.write_event(event) {
+            if !self.failed {
+                eprintln!("Warning: writing to the mirror device failed: {}", err);
+                self.failed = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_calls(&self) -> u64 {
+        self.inner.write_calls()
+    }
+
+    fn queued(&self) -> Option<Duration> {
+        self.inner.queued()
+    }
+
+    fn grab(&mut self) -> io::Result<()> {
+        self.inner.grab()
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.inner.ungrab()
+    }
+
+    fn destroy(&mut self) {
+        self.inner.destroy();
+        self.mirror = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use evdev_rs::{
+        enums::{EventCode, EV_KEY, EV_REL},
+        InputEvent,
+    };
+
+    use super::{Mirror, MirrorSink};
+    use crate::{
+        accel::Accelerator,
+        args::parse_args,
+        backend::{InputBackend, MockBackend},
+        control::Control,
+        taps::Taps,
+        testing::{event, frames},
+    };
+
+    impl MirrorSink for MockBackend {
+        fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+            InputBackend::write_event(self, event)
+        }
+    }
+
+    // the mirror gets exactly what the output does, and a mirror that can't be written to leaves
+    // the output as it would be without one
+    #[test]
+    fn mirrored() -> Result<(), String> {
+        let events = || {
+            let mut events = frames(&[
+                (1, &[(EV_REL::REL_X, 3), (EV_REL::REL_Y, -2)]),
+                (2, &[(EV_REL::REL_X, 10)]),
+                (3, &[(EV_REL::REL_WHEEL, 1)]),
+            ]);
+            events.push(event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1));
+            events
+        };
+        let args = parse_args(
+            ["-m", "2", "-a", "0.1", "selftest-device"]
+                .map(Into::into)
+                .to_vec(),
+        )
+        .map_err(|err| format!("bad options: {}", err))?;
+        let run = |mirror: MockBackend| {
+            let mut backend = Mirror::with_mirror(MockBackend::new(events()), mirror);
+            crate::run(
+                &mut backend,
+                &args,
+                &mut Accelerator::new(),
+                &mut Taps::none(),
+                &Control::default(),
+            )
+            .map_err(|err| err.to_string())?;
+            Ok::<_, String>(backend)
+        };
+        let written = |backend: &MockBackend| {
+            let written = backend.written.iter();
+            written
+                .map(|event| (event.event_code, event.value))
+                .collect::<Vec<_>>()
+        };
+        let mirrored = run(MockBackend::default())?;
+        let output = written(&mirrored.inner);
+        let mirror = written(mirrored.mirror.as_ref().ok_or("mirror gone")?);
+        if output.len() < 8 || mirror != output {
+            return Err(format!("wrote {:?}, mirrored {:?}", output, mirror));
+        }
+        let broken = MockBackend {
+            destroyed: true,
+            ..MockBackend::default()
+        };
+        let unmirrored = written(&run(broken)?.inner);
+        if unmirrored != output {
+            return Err(format!("expected {:?}, got {:?}", output, unmirrored));
+        }
+        Ok(())
+    }
+}
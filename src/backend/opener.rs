@@ -0,0 +1,89 @@
+use std::{fs::File, io, path::Path};
+
+// where the file of the device read comes from
+pub trait DeviceOpener {
+    fn open(&mut self, path: &Path) -> io::Result<File>;
+}
+
+pub struct DirectOpener;
+
+impl DeviceOpener for DirectOpener {
+    fn open(&mut self, path: &Path) -> io::Result<File> {
+        File::open(path)
+    }
+}
+
+// the file opener (named name) gives, or the device opened directly if it can't give one
+pub fn open_or_direct(opener: &mut dyn DeviceOpener, name: &str, path: &Path) -> io::Result<File> {
+    match opener.open(path) {
+        Ok(file) => Ok(file),
+        Err(err) => {
+            eprintln!(
+                "Warning: couldn't get {} from {}, opening it directly: {}",
+                path.display(),
+                name,
+                err
+            );
+            DirectOpener.open(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        io::{self, Read},
+        path::Path,
+    };
+
+    use super::{open_or_direct, DeviceOpener};
+
+    // stands in for a session manager, giving the device it's been handed or refusing to
+    struct MockOpener(Option<File>);
+
+    impl DeviceOpener for MockOpener {
+        fn open(&mut self, _path: &Path) -> io::Result<File> {
+            self.0
+                .take()
+                .ok_or_else(|| io::Error::other("no session to take it from"))
+        }
+    }
+
+    fn opened_contents(opener: &mut MockOpener, path: &Path) -> Result<String, String> {
+        let mut contents = String::new();
+        open_or_direct(opener, "the mock", path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|err| err.to_string())?;
+        Ok(contents)
+    }
+
+    // the device the session manager gives is used over opening the path, which is only opened when
+    // it can't give one
+    #[test]
+    fn device_opener() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-opener-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let device = dir.join("device");
+        let taken = dir.join("taken");
+        let result = (|| {
+            fs::write(&device, "direct").map_err(|err| err.to_string())?;
+            fs::write(&taken, "taken").map_err(|err| err.to_string())?;
+            let file = File::open(&taken).map_err(|err| err.to_string())?;
+            let contents = opened_contents(&mut MockOpener(Some(file)), &device)?;
+            if contents != "taken" {
+                return Err(format!("expected the mock's device, read {:?}", contents));
+            }
+            let contents = opened_contents(&mut MockOpener(None), &device)?;
+            if contents != "direct" {
+                return Err(format!(
+                    "expected to fall back to the path, read {:?}",
+                    contents
+                ));
+            }
+            Ok(())
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+}
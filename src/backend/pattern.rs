@@ -0,0 +1,343 @@
+use std::{
+    collections::VecDeque,
+    f64::consts::TAU,
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL, EV_SYN},
+    InputEvent, TimeVal,
+};
+
+use super::{create_pointer, InputBackend, Output, Sink};
+use crate::args::Curve;
+
+// known movement for checking the acceleration by eye, generated in place of a device
+#[derive(Clone, Copy)]
+pub enum Pattern {
+    // out to the right and back
+    Line,
+    // one loop, counterclockwise on screen
+    Circle,
+    // right while speeding up from standing still, then the same back
+    Ramp,
+    // strokes right and back, each faster than the last, from one speed to the other
+    Sweep { from: f64, to: f64 },
+}
+
+pub fn parse_pattern(name: &str) -> Result<Pattern, &'static str> {
+    match name {
+        "line" => Ok(Pattern::Line),
+        "circle" => Ok(Pattern::Circle),
+        "ramp" => Ok(Pattern::Ramp),
+        _ => Err("unknown test pattern"),
+    }
+}
+
+// like a 1000hz mouse
+const FRAME: Duration = Duration::from_millis(1);
+// a sweep's strokes, each moving for STROKE ms then resting until the next STROKE_PERIOD
+const STROKES: u32 = 8;
+const STROKE: f64 = 500.0;
+const STROKE_PERIOD: f64 = 750.0;
+// how far a sweep goes past where it starts for curves without a cap to go up to
+const UNCAPPED_SPAN: f64 = 10.0;
+
+// the range of speeds in counts per ms where the curve does something, from the offset (or
+// threshold) to where it reaches its cap
+pub fn demo_range(curve: &Curve) -> (f64, f64) {
+    let (from, span) = match *curve {
+        Curve::Linear { accel, cap, offset } => (offset.max(0.0), (cap - 1.0) / accel),
+        Curve::Breakaway {
+            threshold, blend, ..
+        } => (0.0, threshold + blend),
+        Curve::VelocityMap(ref map) => {
+            let points = map.points();
+            (points[0].0, points[points.len() - 1].0 - points[0].0)
+        }
+        Curve::Expr { .. } => (0.0, UNCAPPED_SPAN),
+        // past where the last segment takes over
+        Curve::Segmented(ref segments) => (0.0, segments[segments.len() - 1].0 + UNCAPPED_SPAN),
+    };
+    // an uncapped or decelerating curve never gets there
+    let span = if span.is_finite() && span > 0.0 {
+        span
+    } else {
+        UNCAPPED_SPAN
+    };
+    (from, from + span)
+}
+
+impl Pattern {
+    // the speed of each of a sweep's strokes, evenly spread out over the range, the first a step
+    // past where it starts since standing still isn't much of a stroke
+    pub fn stroke_speeds(self) -> Vec<f64> {
+        match self {
+            Pattern::Sweep { from, to } => (1..=STROKES)
+                .map(|i| from + (to - from) * i as f64 / STROKES as f64)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // which of a sweep's strokes is playing t ms in
+    fn stroke(self, t: f64) -> Option<usize> {
+        match self {
+            Pattern::Sweep { .. } => Some((t / STROKE_PERIOD) as usize),
+            _ => None,
+        }
+    }
+
+    // where the pointer would be t ms in moving at speed counts per ms (the top speed for ramp),
+    // None once the pattern is over
+    pub fn position(self, speed: f64, t: f64) -> Option<(f64, f64)> {
+        match self {
+            Pattern::Line => {
+                const LENGTH: f64 = 1000.0;
+                (t <= LENGTH).then(|| (speed * f64::min(t, LENGTH - t), 0.0))
+            }
+            Pattern::Circle => {
+                const LENGTH: f64 = 2000.0;
+                let radius = speed * LENGTH / TAU;
+                let angle = TAU * t / LENGTH;
+                (t <= LENGTH).then(|| (radius * (angle.cos() - 1.0), -radius * angle.sin()))
+            }
+            Pattern::Ramp => {
+                // each way
+                const LENGTH: f64 = 2000.0;
+                // distance covered speeding up from 0 for t ms
+                let ramp = |t: f64| speed * t * t / (2.0 * LENGTH);
+                if t <= LENGTH {
+                    Some((ramp(t), 0.0))
+                } else {
+                    (t <= 2.0 * LENGTH).then(|| (ramp(LENGTH) - ramp(t - LENGTH), 0.0))
+                }
+            }
+            Pattern::Sweep { .. } => {
+                let speed = *self.stroke_speeds().get(self.stroke(t)?)?;
+                let t = t % STROKE_PERIOD;
+                // back where it started before resting
+                Some((speed * f64::min(t, STROKE - t).max(0.0), 0.0))
+            }
+        }
+    }
+}
+
+// plays a pattern in real time, one frame per FRAME
+pub struct PatternBackend {
+    pattern: Pattern,
+    speed: f64,
+    out: Option<Sink>,
+    start: Instant,
+    // the monotonic clock at start, frames are stamped with when they were due so a late wakeup
+    // doesn't show up as a burst of speed
+    clock_start: TimeVal,
+    frame: u32,
+    // rounded position of the last frame, so rounding never adds up to drift
+    position: (i64, i64),
+    pending: VecDeque<InputEvent>,
+    // printed to stderr as each of a sweep's strokes starts
+    narration: Vec<String>,
+    stroke: Option<usize>,
+}
+
+impl PatternBackend {
+    pub fn open(pattern: Pattern, speed: f64, output: &Output) -> io::Result<PatternBackend> {
+        let out = match output {
+            Output::Discard => None,
+            // it never scrolls
+            Output::Create { .. } => {
+                Some(Sink::Uinput(create_pointer("accelerator test pattern")?))
+            }
+            Output::Existing(path) => Some(Sink::open_existing(path)?),
+        };
+        Ok(PatternBackend {
+            pattern,
+            speed,
+            out,
+            start: Instant::now(),
+            clock_start: crate::monotonic_now(),
+            frame: 0,
+            position: (0, 0),
+            pending: VecDeque::new(),
+            narration: Vec::new(),
+            stroke: None,
+        })
+    }
+
+    // a line for each stroke of a sweep
+    pub fn with_narration(self, narration: Vec<String>) -> PatternBackend {
+        PatternBackend { narration, ..self }
+    }
+}
+
+impl InputBackend for PatternBackend {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        if self.pending.is_empty() {
+            self.frame += 1;
+            let t = (FRAME * self.frame).as_secs_f64() * 1000.0;
+            let Some((x, y)) = self.pattern.position(self.speed, t) else {
+                return Ok(None);
+            };
+            let stroke = self.pattern.stroke(t);
+            if stroke != self.stroke {
+                self.stroke = stroke;
+                if let Some(line) = stroke.and_then(|stroke| self.narration.get(stroke)) {
+                    eprintln!("{}", line);
+                }
+            }
+            let due = self.start + FRAME * self.frame;
+            thread::sleep(due.saturating_duration_since(Instant::now()));
+
+            let usec = self.clock_start.tv_usec + (FRAME * self.frame).as_micros() as i64;
+            let time = TimeVal::new(self.clock_start.tv_sec + usec / 1_000_000, usec % 1_000_000);
+            let position = (x.round() as i64, y.round() as i64);
+            let (dx, dy) = (position.0 - self.position.0, position.1 - self.position.1);
+            self.position = position;
+            // like a real mouse, axes that didn't move are left out
+            for (code, delta) in [(EV_REL::REL_X, dx), (EV_REL::REL_Y, dy)] {
+                if delta != 0 {
+                    self.pending.push_back(InputEvent::new(
+                        &time,
+                        &EventCode::EV_REL(code),
+                        delta as i32,
+                    ));
+                }
+            }
+            self.pending.push_back(InputEvent::new(
+                &time,
+                &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+                0,
+            ));
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        match self.out {
+            Some(ref out) => out.write(event),
+            None => Ok(()),
+        }
+    }
+
+    // there's nothing to take from anyone
+    fn grab(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use evdev_rs::enums::{EventCode, EV_REL};
+
+    use super::{demo_range, Pattern, PatternBackend};
+    use crate::{
+        accel::Accelerator, args::parse_args, backend::Output, control::Control, taps::Taps,
+        testing::Recorder,
+    };
+
+    // the line played through the acceleration (in real time, a second of it) goes out and comes
+    // back along the direction it started in, every point written on the way staying on it
+    #[test]
+    fn line_collinear() -> Result<(), String> {
+        let options = ["-m", "1", "-a", "0.1", "-c", "3", "selftest-device"];
+        let args = parse_args(options.map(OsString::from).to_vec())
+            .map_err(|err| format!("bad options: {}", err))?;
+        let pattern = PatternBackend::open(Pattern::Line, 4.0, &Output::Discard)
+            .map_err(|err| err.to_string())?;
+        let mut backend = Recorder {
+            inner: pattern,
+            written: Vec::new(),
+        };
+        crate::run(
+            &mut backend,
+            &args,
+            &mut Accelerator::new(),
+            &mut Taps::none(),
+            &Control::default(),
+        )
+        .map_err(|err| err.to_string())?;
+        let mut point = (0i64, 0i64);
+        let mut direction = None;
+        let mut farthest = 0.0f64;
+        for (_, event) in &backend.written {
+            match event.event_code {
+                EventCode::EV_REL(EV_REL::REL_X) => point.0 += event.value as i64,
+                EventCode::EV_REL(EV_REL::REL_Y) => point.1 += event.value as i64,
+                _ => continue,
+            }
+            let (dx, dy) = *direction.get_or_insert(point);
+            let cross = (dx * point.1 - dy * point.0) as f64;
+            // a count off the line at most, as far along it as it's got
+            let length = (dx as f64).hypot(dy as f64);
+            if cross.abs() > length {
+                return Err(format!("{:?} is off the line along {:?}", point, (dx, dy)));
+            }
+            farthest = farthest.max((point.0 as f64).hypot(point.1 as f64));
+        }
+        // 2000 counts out at 1 + 0.1 * 4 and back, give or take the frames starting and turning
+        if !(2500.0..3000.0).contains(&farthest) || point.0.abs() > 5 || point.1 != 0 {
+            return Err(format!("went out {} and ended at {:?}", farthest, point));
+        }
+        Ok(())
+    }
+
+    // a linear curve starts at its offset of 2 and reaches its cap of 3 at 22 counts per ms, and
+    // the strokes' speeds as played should cover that
+    #[test]
+    fn demo_curve() -> Result<(), String> {
+        let options = [
+            "-m",
+            "1",
+            "-a",
+            "0.1",
+            "-c",
+            "3",
+            "-o",
+            "2",
+            "selftest-device",
+        ];
+        let args = parse_args(options.map(OsString::from).to_vec())
+            .map_err(|err| format!("bad options: {}", err))?;
+        let (from, to) = demo_range(&args.curve);
+        if (from, to) != (2.0, 22.0) {
+            return Err(format!(
+                "expected a range of 2 to 22, got {} to {}",
+                from, to
+            ));
+        }
+        let pattern = Pattern::Sweep { from, to };
+        let speeds = pattern.stroke_speeds();
+        // each stroke's speed as the pointer moves 100ms into it
+        let mut played = Vec::new();
+        for i in 0.. {
+            let t = i as f64 * 750.0 + 100.0;
+            let (Some(before), Some(after)) =
+                (pattern.position(0.0, t), pattern.position(0.0, t + 1.0))
+            else {
+                break;
+            };
+            played.push(after.0 - before.0);
+        }
+        if played != speeds {
+            return Err(format!("strokes played at {:?}, not {:?}", played, speeds));
+        }
+        if !played.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(format!("strokes don't get faster: {:?}", played));
+        }
+        // within a stroke of the offset, and all the way to the cap
+        match (played.first(), played.last()) {
+            (Some(&first), Some(&last)) if first > from && first <= from + 2.5 && last >= to => {
+                Ok(())
+            }
+            _ => Err(format!("strokes at {:?} don't cover 2 to 22", played)),
+        }
+    }
+}
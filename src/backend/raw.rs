@@ -0,0 +1,136 @@
+use std::{
+    io::{self, BufWriter, Stdout, Write},
+    time::Duration,
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_SYN},
+    InputEvent,
+};
+
+use super::InputBackend;
+
+pub const RAW_EVENT_LEN: usize = std::mem::size_of::<libc::input_event>();
+
+// the bytes of the kernel's struct input_event, laid out as this architecture lays it out (with
+// no padding on any of them)
+pub fn raw_bytes(event: &InputEvent) -> [u8; RAW_EVENT_LEN] {
+    unsafe { std::mem::transmute::<libc::input_event, [u8; RAW_EVENT_LEN]>(event.as_raw()) }
+}
+
+// any bytes make some event, if not a meaningful one
+#[cfg(test)]
+pub fn from_raw_bytes(bytes: [u8; RAW_EVENT_LEN]) -> InputEvent {
+    let raw = unsafe { std::mem::transmute::<[u8; RAW_EVENT_LEN], libc::input_event>(bytes) };
+    InputEvent::from_raw(&raw)
+}
+
+// writes everything written to stdout as raw struct input_events, the same bytes a write to
+// uinput takes, for another process to pass on to a device it made
+pub struct RawTee<B, W = BufWriter<Stdout>> {
+    inner: B,
+    out: W,
+}
+
+impl<B: InputBackend> RawTee<B> {
+    pub fn new(inner: B) -> RawTee<B> {
+        RawTee::with_writer(inner, BufWriter::new(io::stdout()))
+    }
+}
+
+impl<B: InputBackend, W: Write> RawTee<B, W> {
+    pub fn with_writer(inner: B, out: W) -> RawTee<B, W> {
+        RawTee { inner, out }
+    }
+
+    #[cfg(test)]
+    pub fn writer(&self) -> &W {
+        &self.out
+    }
+}
+
+impl<B: InputBackend, W: Write> InputBackend for RawTee<B, W> {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        self.inner.next_event()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait(timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        self.out.write_all(&raw_bytes(event))?;
+        // whole frames at a time, so readers never see half of one
+        if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) {
+            self.out.flush()?;
+        }
+        self.inner.write_event(event)
+    }
+
+    fn write_calls(&self) -> u64 {
+        self.inner.write_calls()
+    }
+
+    fn queued(&self) -> Option<Duration> {
+        self.inner.queued()
+    }
+
+    fn grab(&mut self) -> io::Result<()> {
+        self.inner.grab()
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.inner.ungrab()
+    }
+
+    fn destroy(&mut self) {
+        self.inner.destroy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::{
+        enums::{EventCode, EV_REL, EV_SYN},
+        InputEvent, TimeVal,
+    };
+
+    use super::{from_raw_bytes, RawTee, RAW_EVENT_LEN};
+    use crate::backend::{InputBackend, MockBackend};
+
+    // what --emit-raw writes reads back as the events written
+    #[test]
+    fn raw_events() -> Result<(), String> {
+        let time = TimeVal::new(1_700_000_000, 123_456);
+        let events = [
+            (EventCode::EV_REL(EV_REL::REL_X), 5),
+            (EventCode::EV_REL(EV_REL::REL_Y), -3),
+            (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+        ]
+        .map(|(code, value)| InputEvent::new(&time, &code, value));
+        let mut tee = RawTee::with_writer(MockBackend::default(), Vec::new());
+        for event in &events {
+            tee.write_event(event).map_err(|err| err.to_string())?;
+        }
+        let bytes = tee.writer();
+        if bytes.len() != events.len() * RAW_EVENT_LEN {
+            return Err(format!(
+                "expected {} events, got {} bytes",
+                events.len(),
+                bytes.len()
+            ));
+        }
+        for (chunk, event) in bytes.chunks_exact(RAW_EVENT_LEN).zip(&events) {
+            let read = from_raw_bytes(chunk.try_into().unwrap());
+            if (read.event_code, read.value, read.time)
+                != (event.event_code, event.value, event.time)
+            {
+                return Err(format!(
+                    "wrote {:?} {}, read back {:?} {}",
+                    event.event_code, event.value, read.event_code, read.value
+                ));
+            }
+        }
+        Ok(())
+    }
+}
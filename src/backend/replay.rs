@@ -0,0 +1,226 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+use evdev_rs::{enums::int_to_event_type, util::int_to_event_code, InputEvent, TimeVal};
+
+use super::{create_pointer, InputBackend, Output, Sink};
+use crate::accel::elapsed_ms;
+
+// the events of an evemu recording (as made by evemu-record), everything else in it is ignored
+pub fn parse_evemu(contents: &str) -> Result<Vec<InputEvent>, String> {
+    let mut events = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let Some(fields) = line.strip_prefix("E:") else {
+            continue;
+        };
+        let event = parse_event(fields).ok_or_else(|| format!("line {}: bad event", i + 1))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+// "1700000000.123456 0002 0000 5", the type and code in hex
+fn parse_event(fields: &str) -> Option<InputEvent> {
+    let mut fields = fields.split_whitespace();
+    let (sec, usec) = fields.next()?.split_once('.')?;
+    let time = TimeVal::new(sec.parse().ok()?, usec.parse().ok()?);
+    let event_type = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let code = u32::from_str_radix(fields.next()?, 16).ok()?;
+    let value = fields.next()?.parse().ok()?;
+    // int_to_event_code panics on a type it doesn't know
+    int_to_event_type(event_type)?;
+    Some(InputEvent::new(
+        &time,
+        &int_to_event_code(event_type, code),
+        value,
+    ))
+}
+
+// where the time comes from, so the pacing can be checked without waiting on it
+pub trait Clock {
+    fn now(&self) -> Instant;
+    fn sleep(&mut self, duration: Duration);
+}
+
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+// plays a recording back in place of a device, with the gaps between events divided by speed
+pub struct ReplayBackend<C = RealClock> {
+    events: VecDeque<InputEvent>,
+    speed: f64,
+    out: Option<Sink>,
+    clock: C,
+    // when the first event was played, and when it was recorded
+    start: Option<(Instant, TimeVal)>,
+}
+
+impl ReplayBackend {
+    pub fn open(path: &Path, speed: f64, output: &Output) -> io::Result<ReplayBackend> {
+        let contents = fs::read_to_string(path)?;
+        let events = parse_evemu(&contents).map_err(io::Error::other)?;
+        let out = match output {
+            Output::Discard => None,
+            // the recording doesn't say what device it's from, so it's played as a plain mouse
+            Output::Create { .. } => Some(Sink::Uinput(create_pointer("accelerator replay")?)),
+            Output::Existing(path) => Some(Sink::open_existing(path)?),
+        };
+        let mut backend = ReplayBackend::with_clock(events, speed, RealClock);
+        backend.out = out;
+        Ok(backend)
+    }
+}
+
+impl<C: Clock> ReplayBackend<C> {
+    // written events are dropped
+    pub fn with_clock(events: Vec<InputEvent>, speed: f64, clock: C) -> ReplayBackend<C> {
+        ReplayBackend {
+            events: events.into(),
+            speed,
+            out: None,
+            clock,
+            start: None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    // when the event recorded at time is to be played
+    fn due(&mut self, time: TimeVal) -> Instant {
+        let now = self.clock.now();
+        let &mut (started, recorded) = self.start.get_or_insert((now, time));
+        // a recording going back in time plays the event right away
+        let since_recorded = elapsed_ms(recorded, time).max(0.0) * 1000.0 / self.speed;
+        started + Duration::from_micros(since_recorded.round() as u64)
+    }
+}
+
+impl<C: Clock> InputBackend for ReplayBackend<C> {
+    // events keep their recorded timestamps, so they're accelerated as they were recorded however
+    // fast they're played
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        let Some(event) = self.events.pop_front() else {
+            return Ok(None);
+        };
+        let due = self.due(event.time);
+        let wait = due.saturating_duration_since(self.clock.now());
+        self.clock.sleep(wait);
+        Ok(Some(event))
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        // the end of the recording is there right away
+        let Some(time) = self.events.front().map(|event| event.time) else {
+            return Ok(true);
+        };
+        let wait = self.due(time).saturating_duration_since(self.clock.now());
+        self.clock.sleep(wait.min(timeout));
+        Ok(wait <= timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        match self.out {
+            Some(ref out) => out.write(event),
+            None => Ok(()),
+        }
+    }
+
+    // there's nothing to take from anyone
+    fn grab(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use evdev_rs::enums::{EventCode, EV_REL, EV_SYN};
+
+    use super::{parse_evemu, Clock, ReplayBackend};
+    use crate::backend::InputBackend;
+
+    // time that only passes when it's slept through
+    struct FakeClock {
+        now: Instant,
+        slept: Vec<Duration>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            self.now
+        }
+
+        fn sleep(&mut self, duration: Duration) {
+            self.now += duration;
+            self.slept.push(duration);
+        }
+    }
+
+    // the gaps in a recording are played back divided by the replay speed
+    #[test]
+    fn replay_timing() -> Result<(), String> {
+        let recording = "\
+# EVEMU 1.3
+N: Some Mouse
+E: 100.000000 0002 0000 3
+E: 100.000000 0000 0000 0
+E: 100.010000 0002 0001 -2
+E: 100.010000 0000 0000 0
+E: 100.030000 0002 0000 1
+";
+        let events = parse_evemu(recording)?;
+        let clock = FakeClock {
+            now: Instant::now(),
+            slept: Vec::new(),
+        };
+        let mut replay = ReplayBackend::with_clock(events, 2.0, clock);
+        let mut played = Vec::new();
+        while let Some(event) = replay.next_event().map_err(|err| err.to_string())? {
+            played.push((event.event_code, event.value));
+        }
+        let rel = |code, value| (EventCode::EV_REL(code), value);
+        let syn = (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0);
+        let expected_events = [
+            rel(EV_REL::REL_X, 3),
+            syn,
+            rel(EV_REL::REL_Y, -2),
+            syn,
+            rel(EV_REL::REL_X, 1),
+        ];
+        if played != expected_events {
+            return Err(format!("played {:?}", played));
+        }
+        let ms = Duration::from_millis;
+        let expected = [ms(0), ms(0), ms(5), ms(0), ms(10)];
+        if replay.clock().slept != expected {
+            return Err(format!(
+                "expected sleeping {:?}, slept {:?}",
+                expected,
+                replay.clock().slept
+            ));
+        }
+        Ok(())
+    }
+}
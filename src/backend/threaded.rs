@@ -0,0 +1,210 @@
+use std::{
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use evdev_rs::InputEvent;
+
+use super::{read_event, EvdevRsBackend, InputBackend, Writer};
+use crate::control::spawn_unsignaled;
+
+// _IOW('E', 0x90, int)
+const EVIOCGRAB: libc::c_ulong = 0x40044590;
+// signals can't interrupt waiting on the queue, so it gives up this often to let the caller check
+// for them
+const SIGNAL_CHECK: Duration = Duration::from_millis(100);
+
+type Read = io::Result<(InputEvent, Instant)>;
+
+// calls read on a thread of its own until it runs out or fails, handing everything over in order
+// through the queue
+pub struct ThreadedReader {
+    events: Receiver<Read>,
+    // taken off the queue by wait, for next_event to return
+    pending: Option<Read>,
+    queued: Option<Duration>,
+}
+
+impl ThreadedReader {
+    pub fn spawn<F>(mut read: F) -> ThreadedReader
+    where
+        F: FnMut() -> io::Result<Option<InputEvent>> + Send + 'static,
+    {
+        let (send, events) = mpsc::channel();
+        spawn_unsignaled(move || loop {
+            let read = match read() {
+                Ok(Some(event)) => Ok((event, Instant::now())),
+                // dropping the sender tells next_event the source ended
+                Ok(None) => return,
+                Err(err) => Err(err),
+            };
+            let failed = read.is_err();
+            // the receiver is only gone once everything is shutting down
+            if send.send(read).is_err() || failed {
+                return;
+            }
+        });
+        ThreadedReader {
+            events,
+            pending: None,
+            queued: None,
+        }
+    }
+
+    pub fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        let read = match self.pending.take() {
+            Some(read) => read,
+            None => match self.events.recv_timeout(SIGNAL_CHECK) {
+                Ok(read) => read,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::from(io::ErrorKind::Interrupted))
+                }
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            },
+        };
+        let (event, read_at) = read?;
+        self.queued = Some(read_at.elapsed());
+        Ok(Some(event))
+    }
+
+    pub fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        if self.pending.is_none() {
+            self.pending = match self.events.recv_timeout(timeout) {
+                Ok(read) => Some(read),
+                Err(RecvTimeoutError::Timeout) => return Ok(false),
+                // next_event reports it
+                Err(RecvTimeoutError::Disconnected) => return Ok(true),
+            };
+        }
+        Ok(true)
+    }
+}
+
+// reads the device on a thread of its own, so a slow frame never keeps events waiting in the
+// kernel
+pub struct ThreadedBackend {
+    reader: ThreadedReader,
+    writer: Writer,
+    // the source's, kept open by the reading thread owning it
+    fd: RawFd,
+}
+
+impl EvdevRsBackend {
+    pub fn threaded(self) -> ThreadedBackend {
+        let EvdevRsBackend { source, writer, .. } = self;
+        let fd = source.file().as_raw_fd();
+        ThreadedBackend {
+            reader: ThreadedReader::spawn(move || read_event(&source).map(Some)),
+            writer,
+            fd,
+        }
+    }
+}
+
+impl ThreadedBackend {
+    fn set_grab(&self, grab: bool) -> io::Result<()> {
+        match unsafe { libc::ioctl(self.fd, EVIOCGRAB, grab as libc::c_int) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl InputBackend for ThreadedBackend {
+    fn next_event(&mut self) -> io::Result<Option<InputEvent>> {
+        self.reader.next_event()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.reader.wait(timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> io::Result<()> {
+        self.writer.write_event(event)
+    }
+
+    fn write_calls(&self) -> u64 {
+        self.writer.write_calls
+    }
+
+    fn queued(&self) -> Option<Duration> {
+        self.reader.queued
+    }
+
+    // straight on the fd, the device itself belongs to the reading thread
+    fn grab(&mut self) -> io::Result<()> {
+        self.set_grab(true)
+    }
+
+    fn ungrab(&mut self) -> io::Result<()> {
+        self.set_grab(false)
+    }
+
+    fn destroy(&mut self) {
+        self.writer.destroy();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, thread, time::Duration};
+
+    use evdev_rs::enums::EV_REL;
+
+    use super::ThreadedReader;
+    use crate::{
+        backend::{InputBackend, MockBackend},
+        testing::frames,
+    };
+
+    // thousands of frames read as fast as they come are all handed over in order, with nothing
+    // lost while the reader's held up, and then the end of the source
+    #[test]
+    fn flooded() -> Result<(), String> {
+        let moves: Vec<(i64, Vec<(EV_REL, i32)>)> = (0..3000)
+            .map(|i| {
+                (
+                    i,
+                    vec![(EV_REL::REL_X, i as i32), (EV_REL::REL_Y, -(i as i32))],
+                )
+            })
+            .collect();
+        let moves: Vec<_> = moves.iter().map(|(ms, moved)| (*ms, &moved[..])).collect();
+        let events = frames(&moves);
+        let expected: Vec<_> = events
+            .iter()
+            .map(|event| (event.event_code, event.value, event.time))
+            .collect();
+        let mut source = MockBackend::new(events);
+        let mut reader = ThreadedReader::spawn(move || source.next_event());
+        let mut read = Vec::new();
+        loop {
+            match reader.next_event() {
+                Ok(Some(event)) => {
+                    read.push((event.event_code, event.value, event.time));
+                    // a slow frame now and then, for the queue to back up behind
+                    if read.len() % 1000 == 0 {
+                        thread::sleep(Duration::from_millis(5));
+                    }
+                }
+                Ok(None) => break,
+                // only the wait for signals giving up
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+        if read.len() != 9000 {
+            return Err(format!("expected 9000 events, got {}", read.len()));
+        }
+        if read != expected {
+            let at = read
+                .iter()
+                .zip(&expected)
+                .position(|(read, expected)| read != expected);
+            return Err(format!("out of order from event {:?}", at));
+        }
+        Ok(())
+    }
+}
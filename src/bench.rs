@@ -0,0 +1,142 @@
+use std::{
+    fs::File,
+    hint::black_box,
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    time::Instant,
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL, EV_SYN},
+    InputEvent, TimeVal,
+};
+
+use crate::{
+    accel::sensitivity,
+    args::{Args, Curve},
+    backend::write_frame,
+    expr,
+    velocity::VelocityMap,
+};
+
+const CALLS: u32 = 5_000_000;
+// speeds cycled through, in counts per ms, covering everything from standing still to flicks
+const SPEEDS: u32 = 1000;
+const SPEED_STEP: f64 = 0.05;
+
+// about the same shape from every kind of curve, so they're compared doing the same work
+fn sample_curves() -> Vec<(&'static str, Curve)> {
+    let source = "max(1, min(1 + 0.1*(s - 2), 3))";
+    let points: Vec<(f64, f64)> = (0..64)
+        .map(|i| {
+            let speed = i as f64;
+            (
+                speed,
+                speed * sensitivity(1.0, &linear(), speed, &mut false),
+            )
+        })
+        .collect();
+    vec![
+        ("linear", linear()),
+        (
+            "expr",
+            Curve::Expr {
+                expr: expr::parse(source).unwrap(),
+                source: source.to_string(),
+            },
+        ),
+        (
+            "velocity-map",
+            Curve::VelocityMap(VelocityMap::from_points(&points).unwrap()),
+        ),
+        (
+            "breakaway",
+            Curve::Breakaway {
+                threshold: 20.0,
+                cap: 3.0,
+                blend: 2.0,
+                hysteresis: 2.0,
+            },
+        ),
+        (
+            "segmented",
+            Curve::Segmented(vec![
+                (0.0, linear()),
+                (
+                    20.0,
+                    Curve::Expr {
+                        expr: expr::parse(source).unwrap(),
+                        source: source.to_string(),
+                    },
+                ),
+            ]),
+        ),
+    ]
+}
+
+fn linear() -> Curve {
+    Curve::Linear {
+        accel: 0.1,
+        cap: 3.0,
+        offset: 2.0,
+    }
+}
+
+fn time_per_call(sens_mult: f64, curve: &Curve) -> f64 {
+    let start = Instant::now();
+    let mut total = 0.0;
+    let mut broken_away = false;
+    for i in 0..CALLS {
+        let speed = black_box((i % SPEEDS) as f64 * SPEED_STEP);
+        total += sensitivity(sens_mult, black_box(curve), speed, &mut broken_away);
+    }
+    black_box(total);
+    start.elapsed().as_nanos() as f64 / CALLS as f64
+}
+
+// evaluates every kind of curve, and the one the options set up, without a device
+pub fn benchmark_curves(args: &Args) {
+    for (name, curve) in sample_curves() {
+        println!("{:<14} {:>8.2} ns/call", name, time_per_call(1.0, &curve));
+    }
+    println!(
+        "{:<14} {:>8.2} ns/call",
+        "configured",
+        time_per_call(args.sens_mult, &args.curve)
+    );
+}
+
+const FRAMES: u32 = 1_000_000;
+
+fn time_per_frame(fd: RawFd, batched: bool) -> io::Result<f64> {
+    let time = TimeVal::new(0, 0);
+    let frame = [
+        (EventCode::EV_REL(EV_REL::REL_X), 3),
+        (EventCode::EV_REL(EV_REL::REL_Y), -2),
+        (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+    ]
+    .map(|(code, value)| InputEvent::new(&time, &code, value).as_raw());
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        if batched {
+            write_frame(fd, &frame)?;
+        } else {
+            for event in &frame {
+                write_frame(fd, std::slice::from_ref(event))?;
+            }
+        }
+    }
+    Ok(start.elapsed().as_nanos() as f64 / FRAMES as f64)
+}
+
+// writes frames of REL_X, REL_Y and SYN_REPORT to /dev/null one event per write, as libevdev
+// writes them to uinput, and each in one write, as --batch-writes does; /dev/null does nothing with
+// them, so the difference is what the syscalls cost
+pub fn benchmark_writes() -> io::Result<()> {
+    let null = File::options().write(true).open("/dev/null")?;
+    let per_event = time_per_frame(null.as_raw_fd(), false)?;
+    let batched = time_per_frame(null.as_raw_fd(), true)?;
+    println!("{:<14} {:>8.2} ns/frame", "per-event", per_event);
+    println!("{:<14} {:>8.2} ns/frame", "batched", batched);
+    Ok(())
+}
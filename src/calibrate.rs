@@ -0,0 +1,69 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL, EV_SYN},
+    InputEvent, TimeVal,
+};
+
+use crate::{accel::parse_gain, backend::create_pointer};
+
+// how far the calibration moves the pointer, and how long it waits first for the desktop to pick
+// up the new device
+pub const CALIBRATION_COUNTS: i32 = 1000;
+const SETTLE: Duration = Duration::from_secs(1);
+const FRAME: Duration = Duration::from_millis(1);
+
+// moves a new virtual pointer CALIBRATION_COUNTS counts right at speed counts per ms, unaccelerated,
+// then asks on stdin how many pixels the pointer went and writes both to path for
+// --observed-gain-file
+pub fn calibrate_gain(path: &Path, speed: f64) -> io::Result<()> {
+    let device = create_pointer("accelerator gain calibration")?;
+    thread::sleep(SETTLE);
+    eprintln!(
+        "Moving the pointer {} counts right at {} counts/ms",
+        CALIBRATION_COUNTS, speed
+    );
+    let mut moved = 0;
+    let mut ms = 0.0;
+    while moved < CALIBRATION_COUNTS {
+        ms += 1.0;
+        let to = ((speed * ms).round() as i32).min(CALIBRATION_COUNTS);
+        let time = TimeVal::new(0, 0);
+        for (code, value) in [
+            (EventCode::EV_REL(EV_REL::REL_X), to - moved),
+            (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+        ] {
+            device.write_event(&InputEvent::new(&time, &code, value))?;
+        }
+        moved = to;
+        thread::sleep(FRAME);
+    }
+    eprint!("How many pixels did the pointer move? ");
+    io::stderr().flush()?;
+    let mut observed = String::new();
+    io::stdin().read_line(&mut observed)?;
+    let contents = format!("{} {}", CALIBRATION_COUNTS, observed.trim());
+    let Some(gain) = parse_gain(&contents) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "expected a positive number of pixels, got '{}'",
+                observed.trim()
+            ),
+        ));
+    };
+    fs::write(path, contents + "\n")?;
+    eprintln!(
+        "Wrote {}: the desktop multiplies movement by {:.3}, which --observed-gain-file divides \
+         back out",
+        path.display(),
+        gain
+    );
+    Ok(())
+}
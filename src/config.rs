@@ -0,0 +1,538 @@
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use evdev_rs::enums::EventCode;
+use pico_args::Arguments;
+use serde_json::{Map, Value};
+
+use crate::{
+    args::{Args, Backend, Curve},
+    curve_file::{CurveDef, SegmentDef},
+};
+
+// options that pick or shape the curve, which a layer setting any of them replaces all of, so a
+// base file's linear curve doesn't conflict with a host's expression
+const CURVE_OPTIONS: [&str; 11] = [
+    "--curve",
+    "--segment",
+    "-a",
+    "-c",
+    "-o",
+    "--expr",
+    "--velocity-map",
+    "--breakaway",
+    "--breakaway-blend",
+    "--breakaway-hysteresis",
+    "--import-curve",
+];
+
+// an option set by a config file
+pub struct Setting {
+    pub option: String,
+    value: Value,
+    pub file: PathBuf,
+    // the command line sets it too, which wins
+    pub overridden: bool,
+}
+
+// "m" is -m, "accel-budget" is --accel-budget
+fn option_for(key: &str) -> String {
+    if key.chars().count() == 1 {
+        format!("-{}", key)
+    } else {
+        format!("--{}", key)
+    }
+}
+
+fn is_curve_option(option: &str) -> bool {
+    CURVE_OPTIONS.contains(&option)
+}
+
+// whether the command line has option, either on its own or as option=value
+fn given(raw: &[OsString], option: &str) -> bool {
+    raw.iter().any(|arg| {
+        let arg = arg.as_encoded_bytes();
+        arg.strip_prefix(option.as_bytes())
+            .is_some_and(|rest| rest.is_empty() || rest[0] == b'=')
+    })
+}
+
+// takes every --config PATH out of the command line
+fn take_configs(raw: Vec<OsString>) -> Result<(Vec<PathBuf>, Vec<OsString>), String> {
+    let mut configs = Vec::new();
+    let mut rest = Vec::new();
+    let mut args = raw.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--config" {
+            let path = args.next().ok_or("'--config' needs a value")?;
+            configs.push(PathBuf::from(path));
+        } else if let Some(path) = arg.as_encoded_bytes().strip_prefix(b"--config=") {
+            configs.push(PathBuf::from(String::from_utf8_lossy(path).into_owned()));
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok((configs, rest))
+}
+
+// a TOML table of options, as the same values --segment and the rest of the options take as JSON
+fn load(path: &Path) -> Result<Vec<(String, Value)>, String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let options: toml::Table = toml::from_str(&contents).map_err(|err| err.to_string())?;
+    options
+        .into_iter()
+        .map(|(key, value)| {
+            Ok((
+                key,
+                serde_json::to_value(value).map_err(|err| err.to_string())?,
+            ))
+        })
+        .collect()
+}
+
+// the files' settings merged in order, with later files replacing what earlier ones set
+fn merge(configs: &[PathBuf]) -> Result<Vec<Setting>, String> {
+    let mut settings: Vec<Setting> = Vec::new();
+    for path in configs {
+        let options =
+            load(path).map_err(|err| format!("'--config' {}: {}", path.display(), err))?;
+        if options
+            .iter()
+            .any(|(key, _)| is_curve_option(&option_for(key)))
+        {
+            settings.retain(|setting| !is_curve_option(&setting.option));
+        }
+        for (key, value) in options {
+            if key == "config" {
+                return Err(format!(
+                    "'--config' {}: can't include other config files",
+                    path.display()
+                ));
+            }
+            let option = option_for(&key);
+            settings.retain(|setting| setting.option != option);
+            settings.push(Setting {
+                option,
+                value,
+                file: path.clone(),
+                overridden: false,
+            });
+        }
+    }
+    Ok(settings)
+}
+
+// what a setting adds to the command line: a flag for true, nothing for false, a table as its
+// JSON, and an array repeats the option for each of its values
+fn to_args(setting: &Setting) -> Result<Vec<OsString>, String> {
+    let values = match setting.value {
+        Value::Array(ref values) => values.iter().collect(),
+        ref value => vec![value],
+    };
+    let mut args = Vec::new();
+    for value in values {
+        match value {
+            Value::Bool(true) => args.push(OsString::from(&setting.option)),
+            Value::Bool(false) => {}
+            Value::Number(number) => {
+                args.extend([OsString::from(&setting.option), number.to_string().into()])
+            }
+            Value::String(string) => args.extend([OsString::from(&setting.option), string.into()]),
+            Value::Object(_) => {
+                args.extend([OsString::from(&setting.option), value.to_string().into()])
+            }
+            _ => {
+                return Err(format!(
+                    "'{}' from {} must be a number, string, boolean or table",
+                    setting.option,
+                    setting.file.display()
+                ))
+            }
+        }
+    }
+    Ok(args)
+}
+
+// the command line with the options of its --config files put in front, for anything it doesn't
+// set itself (a curve set on the command line replaces any from the files), along with where each
+// came from
+pub fn with_configs(raw: Vec<OsString>) -> Result<(Vec<OsString>, Vec<Setting>), String> {
+    let (configs, raw) = take_configs(raw)?;
+    let mut settings = merge(&configs)?;
+    let curve_given = CURVE_OPTIONS.iter().any(|option| given(&raw, option));
+    let mut args = Vec::new();
+    for setting in &mut settings {
+        setting.overridden =
+            given(&raw, &setting.option) || (curve_given && is_curve_option(&setting.option));
+        if !setting.overridden {
+            args.extend(to_args(setting)?);
+        }
+    }
+    args.extend(raw);
+    Ok((args, settings))
+}
+
+// for --debug
+pub fn describe(settings: &[Setting]) -> String {
+    settings
+        .iter()
+        .map(|setting| {
+            let overridden = if setting.overridden {
+                ", overridden on the command line"
+            } else {
+                ""
+            };
+            format!(
+                "config: {} from {}{}",
+                setting.option,
+                setting.file.display(),
+                overridden
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// options only ever given as files, which Args keeps just what was loaded from
+const FILE_OPTIONS: [&str; 4] = [
+    "--device-registry",
+    "--import-curve",
+    "--velocity-map",
+    "--modifier-map",
+];
+
+// the settings being built up for --print-config
+struct Resolved(Map<String, Value>);
+
+impl Resolved {
+    fn set(&mut self, key: &str, value: impl Into<Value>) {
+        self.0.insert(key.to_string(), value.into());
+    }
+
+    // an unbounded setting is the same as leaving it out
+    fn number(&mut self, key: &str, value: f64) {
+        if value.is_finite() {
+            self.set(key, value);
+        }
+    }
+
+    fn opt_number(&mut self, key: &str, value: Option<f64>) {
+        if let Some(value) = value {
+            self.number(key, value);
+        }
+    }
+
+    fn opt_path(&mut self, key: &str, value: &Option<PathBuf>) {
+        if let Some(path) = value {
+            self.set(key, path.to_string_lossy());
+        }
+    }
+}
+
+// every setting in effect, as a config file for --config: the command line and its config files
+// still give the files that were loaded, everything else is from what they were parsed into
+pub fn resolved(args: &Args) -> String {
+    let registered = args.registered;
+    let mut config = Resolved(Map::new());
+    let mut given = Arguments::from_vec(args.raw.clone());
+    for option in FILE_OPTIONS {
+        let paths: Vec<String> = given.values_from_str(option).unwrap_or_default();
+        let key = &option[2..];
+        match paths.len() {
+            0 => {}
+            1 if option != "--modifier-map" => config.set(key, paths[0].clone()),
+            _ => config.set(key, paths),
+        }
+    }
+    if registered {
+        // the registry's curve and sensitivity replace the options again, only the resolution
+        // scaling them is still needed
+        for option in ["--screen-width", "--screen-height", "--normalize-to"] {
+            if let Ok(Some(pixels)) = given.opt_value_from_str::<_, u32>(option) {
+                config.set(&option[2..], pixels);
+            }
+        }
+    } else {
+        // including any resolution scaling, so the screen size isn't needed again
+        config.number("m", args.sens_mult);
+    }
+    match args.curve {
+        // an imported curve conflicts with the options, and a registered one ignores them
+        _ if registered || config.0.contains_key("import-curve") => {}
+        Curve::Linear { accel, cap, offset } => {
+            config.number("a", accel);
+            config.number("c", cap);
+            config.number("o", offset);
+        }
+        Curve::Expr { ref source, .. } => config.set("expr", source.clone()),
+        // the file is among the paths above, otherwise it's --curve's own map
+        Curve::VelocityMap(_) if config.0.contains_key("velocity-map") => {}
+        Curve::VelocityMap(_) => config.set("curve", "velocity-map"),
+        Curve::Breakaway {
+            threshold,
+            cap,
+            blend,
+            hysteresis,
+        } => {
+            config.number("breakaway", threshold);
+            config.number("c", cap);
+            config.number("breakaway-blend", blend);
+            config.number("breakaway-hysteresis", hysteresis);
+        }
+        Curve::Segmented(ref segments) => {
+            let segments = segments.iter().map(|(from, curve)| {
+                let segment = SegmentDef {
+                    from: *from,
+                    curve: CurveDef::from_curve(curve),
+                };
+                serde_json::to_value(segment).unwrap_or(Value::Null)
+            });
+            config.set("segment", segments.collect::<Vec<_>>());
+        }
+    }
+    config.number("lock-mult", args.lock_mult);
+    config.opt_path("dynamic-mult-file", &args.dynamic_mult_file);
+    config.opt_path("fov-file", &args.fov_file);
+    config.opt_path("observed-gain-file", &args.observed_gain_file);
+    config.opt_path("focus-file", &args.focus_file);
+    if let Ok(Value::String(metric)) = serde_json::to_value(args.speed_metric) {
+        config.set("speed-metric", metric);
+    }
+    config.number("input-scale", args.input_scale);
+    config.set("monotonic-time", args.monotonic_time);
+    config.set("directional-accel", args.directional_accel);
+    config.number("reversal-angle", args.reversal_angle);
+    config.set("min-move", args.min_move);
+    config.set("flush-on-click", args.flush_on_click);
+    if !args.panic_keys.is_empty() {
+        let keys: Vec<String> = args.panic_keys.iter().map(|key| key.to_string()).collect();
+        config.set("panic-keys", keys.join("+"));
+    }
+    config.number("accum-bound", args.accum_bound);
+    config.set("no-accum", args.no_accum);
+    if let Some(idle) = args.anti_drift {
+        config.set("anti-drift", idle.as_millis() as u64);
+    }
+    if let Some(interval) = args.min_report_interval {
+        config.set("min-report-interval", interval.as_millis() as u64);
+    }
+    if let Some(rate) = args.max_event_rate {
+        config.set("max-event-rate", rate);
+    }
+    config.opt_path("power-supply", &args.power_supply);
+    config.set("strict", args.strict);
+    if let Some(after) = args.strict_flush_after {
+        config.set("strict-flush-after", after.as_millis() as u64);
+    }
+    if let Some(timeout) = args.syn_timeout {
+        config.set("syn-timeout", timeout.as_millis() as u64);
+    }
+    config.opt_number("scroll-up-accel", args.scroll_up_accel);
+    config.opt_number("scroll-down-accel", args.scroll_down_accel);
+    config.set("touchpad-mode", args.touchpad_mode);
+    config.number("touchpad-scroll-accel", args.touchpad_scroll_accel);
+    if let Some(frames) = args.smooth_scroll {
+        config.set("smooth-scroll", frames);
+    }
+    config.opt_number("click-stabilize", args.click_stabilize);
+    config.number("click-stabilize-mult", args.click_stabilize_mult);
+    config.set("swap-xy", args.swap_xy);
+    if !args.extra_axes.is_empty() {
+        let extra = args
+            .extra_axes
+            .iter()
+            .map(|&axis| EventCode::EV_REL(axis).to_string());
+        let axes: Vec<String> = ["REL_X".to_string(), "REL_Y".to_string()]
+            .into_iter()
+            .chain(extra)
+            .collect();
+        config.set("axes", axes.join(","));
+    }
+    config.opt_number("max-x-speed", args.max_x_speed);
+    config.opt_number("max-y-speed", args.max_y_speed);
+    config.set("preserve-direction", args.preserve_direction);
+    config.opt_number("accel-after", args.accel_after);
+    config.set("path-length-speed", args.path_length_speed);
+    config.opt_number("flick-threshold", args.flick_threshold);
+    config.opt_number("flick-cooldown", args.flick_cooldown);
+    config.opt_number("accel-budget", args.accel_budget);
+    config.set("adaptive-offset", args.adaptive_offset);
+    config.number(
+        "adaptive-offset-percentile",
+        args.adaptive_offset_percentile,
+    );
+    config.number("adaptive-offset-rate", args.adaptive_offset_rate);
+    config.opt_number("static-friction", args.static_friction);
+    config.opt_number("speed-hysteresis", args.speed_hysteresis);
+    config.set("reset-accum-on-reload", args.reset_accum_on_reload);
+    config.opt_number("input-smooth", args.input_smooth);
+    config.opt_number("max-mult-slew", args.max_mult_slew);
+    if let Some(frames) = args.output_average {
+        config.set("output-average", frames);
+    }
+    config.set("one-euro", args.one_euro);
+    config.number("mincutoff", args.min_cutoff);
+    config.number("beta", args.beta);
+    config.number("x-pos-mult", args.x_pos_mult);
+    config.number("x-neg-mult", args.x_neg_mult);
+    config.number("y-pos-mult", args.y_pos_mult);
+    config.number("y-neg-mult", args.y_neg_mult);
+    config.number("diagonal-compensation", args.diagonal_compensation);
+    config.set(
+        "backend",
+        match args.backend {
+            Backend::EvdevRs => "evdev-rs",
+            #[cfg(feature = "backend-evdev")]
+            Backend::Evdev => "evdev",
+        },
+    );
+    config.set("emit-evemu", args.emit_evemu);
+    config.set("emit-raw", args.emit_raw);
+    config.set("forward-all-syn", args.forward_all_syn);
+    if let Some(ref name) = args.mirror_device {
+        config.set("mirror-device", name.clone());
+    }
+    if let Some(ref name) = args.heartbeat_device {
+        config.set("heartbeat-device", name.clone());
+    }
+    config.set(
+        "heartbeat-interval",
+        args.heartbeat_interval.as_millis() as u64,
+    );
+    config.set("dry-run", args.dry_run);
+    config.opt_path("no-create-output", &args.no_create_output);
+    config.set("batch-writes", args.batch_writes);
+    config.set("threaded", args.threaded);
+    config.set("logind", args.logind);
+    config.set("debug", args.debug);
+    config.number("debug-speed-min", *args.debug_speeds.start());
+    config.number("debug-speed-max", *args.debug_speeds.end());
+    if let Some(addr) = args.debug_udp {
+        config.set("debug-udp", addr.to_string());
+    }
+    config.opt_path("control-fifo", &args.control_fifo);
+    config.set(
+        "idle-poll-interval",
+        args.idle_poll_interval.as_millis() as u64,
+    );
+    config.opt_path("preview-stream", &args.preview_stream);
+    config.opt_path("emit-multiplier-to", &args.emit_multiplier_to);
+    config.set("no-transform", args.no_transform);
+    config.number("pattern-speed", args.pattern_speed);
+    config.number("replay-speed", args.replay_speed);
+    // the segments, the only tables, come out as [[segment]]s after everything else
+    toml::to_string(&config.0).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs};
+
+    use super::resolved;
+    use crate::args::{parse_args, Curve};
+
+    // a host file over a base one, with the command line over both; the host's expression replaces
+    // the base's linear curve rather than conflicting with it
+    #[test]
+    fn config_layers() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let base = dir.join("base.toml");
+        let host = dir.join("host.toml");
+        let result = (|| {
+            fs::write(
+                &base,
+                "m = 2\na = 0.1\naccel-budget = 50\ndiagonal-compensation = 0.5\n",
+            )
+            .map_err(|err| err.to_string())?;
+            fs::write(&host, "m = 1\nexpr = \"1 + s/10\"\n").map_err(|err| err.to_string())?;
+            let options = [
+                "--config".into(),
+                base.clone().into_os_string(),
+                "--config".into(),
+                host.clone().into_os_string(),
+                "--accel-budget".into(),
+                "100".into(),
+                "selftest-device".into(),
+            ];
+            let args =
+                parse_args(options.to_vec()).map_err(|err| format!("bad options: {}", err))?;
+            if args.sens_mult != 1.0 {
+                return Err(format!("expected the host's -m 1, got {}", args.sens_mult));
+            }
+            if !matches!(args.curve, Curve::Expr { .. }) {
+                return Err("expected the host's expression curve".to_string());
+            }
+            if args.accel_budget != Some(100.0) {
+                return Err(format!(
+                    "expected the command line's budget, got {:?}",
+                    args.accel_budget
+                ));
+            }
+            if args.diagonal_compensation != 0.5 {
+                return Err(format!(
+                    "expected the base's diagonal compensation, got {}",
+                    args.diagonal_compensation
+                ));
+            }
+            Ok(())
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    // what --print-config prints for options, which has to be the same again fed back through
+    // --config
+    fn printed_config(options: Vec<OsString>) -> Result<String, String> {
+        let args = parse_args(options).map_err(|err| format!("bad options: {}", err))?;
+        Ok(resolved(&args))
+    }
+
+    #[test]
+    fn print_config() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-print-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let path = dir.join("printed.toml");
+        let result = (|| {
+            let option_sets = [
+            "-m 1 -a 0.1 -c 3 --screen-width 1920 --normalize-to 1080 --panic-keys KEY_A+KEY_B \
+             --syn-timeout 8",
+            "-m 2 --breakaway 4 -c 3 --reset-accum-on-reload \
+             --axes REL_Z,REL_X,REL_Y,REL_RX",
+            "--expr 1+s/10 --debug-speed-min 2 --debug-udp 127.0.0.1:9",
+            concat!(
+                r#"-m 2 --segment {"from":0,"type":"linear","accel":0.1} "#,
+                r#"--segment {"from":10,"type":"breakaway","threshold":5,"cap":2}"#,
+            ),
+            "-m 1 --curve expr",
+            "-m 1 --curve velocity-map",
+            "-m 1 --curve segmented",
+        ];
+            for options in option_sets {
+                let mut given: Vec<OsString> =
+                    options.split_whitespace().map(OsString::from).collect();
+                given.extend(["--print-config".into(), "selftest-device".into()]);
+                let printed = printed_config(given)?;
+                fs::write(&path, &printed).map_err(|err| err.to_string())?;
+                let again = printed_config(vec![
+                    "--config".into(),
+                    path.clone().into_os_string(),
+                    "--print-config".into(),
+                    "selftest-device".into(),
+                ])?;
+                if again != printed {
+                    return Err(format!(
+                        "{:?} printed\n{}\nbut read back as\n{}",
+                        options, printed, again
+                    ));
+                }
+            }
+            Ok(())
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+}
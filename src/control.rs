@@ -0,0 +1,196 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    os::unix::{ffi::OsStrExt, fs::FileTypeExt},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+// requests for the main loop, which checks them between events
+#[derive(Default)]
+pub struct Control {
+    pub paused: AtomicBool,
+    pub locked: AtomicBool,
+    pub reload: AtomicBool,
+    pub quit: AtomicBool,
+}
+
+impl Control {
+    // takes a pending reload request
+    pub fn take_reload(&self) -> bool {
+        self.reload.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn quitting(&self) -> bool {
+        self.quit.load(Ordering::Relaxed) || TERMINATED.load(Ordering::Relaxed)
+    }
+
+    // takes a pending SIGUSR1
+    pub fn take_dump(&self) -> bool {
+        DUMP.swap(false, Ordering::Relaxed)
+    }
+}
+
+// set by SIGINT or SIGTERM, which can't reach any particular Control
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+// set by SIGUSR1, for the loop to print its state
+static DUMP: AtomicBool = AtomicBool::new(false);
+const TERMINATING_SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+const HANDLED_SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGUSR1];
+
+extern "C" fn on_terminate(_signal: libc::c_int) {
+    // a second one means shutting down cleanly is stuck, so give up on it
+    if TERMINATED.swap(true, Ordering::Relaxed) {
+        unsafe { libc::_exit(1) };
+    }
+}
+
+extern "C" fn on_dump(_signal: libc::c_int) {
+    DUMP.store(true, Ordering::Relaxed);
+}
+
+fn set_handler(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) -> io::Result<()> {
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handler as libc::sighandler_t;
+    if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// turns SIGINT and SIGTERM into quitting, so the device is ungrabbed and the summary printed, and
+// SIGUSR1 into printing the state; blocking reads are interrupted rather than restarted so the
+// loop gets to notice
+pub fn handle_signals() -> io::Result<()> {
+    for signal in TERMINATING_SIGNALS {
+        set_handler(signal, on_terminate)?;
+    }
+    set_handler(libc::SIGUSR1, on_dump)
+}
+
+// the signals have to land on the main thread to interrupt its read, so other threads block them
+pub fn spawn_unsignaled(f: impl FnOnce() + Send + 'static) {
+    unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        let mut old: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for signal in HANDLED_SIGNALS {
+            libc::sigaddset(&mut set, signal);
+        }
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, &mut old);
+        thread::spawn(f);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &old, std::ptr::null_mut());
+    }
+}
+
+fn mkfifo(path: &Path) -> io::Result<()> {
+    match path.metadata() {
+        Ok(metadata) if metadata.file_type().is_fifo() => return Ok(()),
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and isn't a fifo", path.display()),
+            ))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::mkfifo(path.as_ptr(), 0o600) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// creates the fifo if needed and reads commands from it on a background thread
+pub fn listen_fifo(path: PathBuf, control: Arc<Control>) -> io::Result<()> {
+    mkfifo(&path)?;
+    spawn_unsignaled(move || loop {
+        // blocks until a writer shows up, once they all close the reads hit eof and it's reopened
+        // for the next one
+        let fifo = match File::open(&path) {
+            Ok(fifo) => fifo,
+            Err(err) => {
+                eprintln!("Error: couldn't open control fifo: {}", err);
+                return;
+            }
+        };
+        for line in BufReader::new(fifo).lines() {
+            let Ok(line) = line else { break };
+            match line.trim() {
+                "pause" => control.paused.store(true, Ordering::Relaxed),
+                "resume" => control.paused.store(false, Ordering::Relaxed),
+                "lock" => control.locked.store(true, Ordering::Relaxed),
+                "unlock" => control.locked.store(false, Ordering::Relaxed),
+                "reload" => control.reload.store(true, Ordering::Relaxed),
+                "quit" => control.quit.store(true, Ordering::Relaxed),
+                "" => {}
+                command => eprintln!("Warning: unknown control command {:?}", command),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, OpenOptions},
+        io::Write,
+        sync::{atomic::Ordering, Arc},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{listen_fifo, Control};
+
+    // whether a command has been carried out
+    type Done = fn(&Control) -> bool;
+
+    // each command written to the fifo, from a writer that's closed again every time so it has to
+    // be reopened, is picked up by the main loop's flags, and a file that isn't a fifo is refused
+    #[test]
+    fn fifo_commands() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-fifo-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let path = dir.join("control");
+        let result = (|| {
+            let control = Arc::new(Control::default());
+            listen_fifo(path.clone(), control.clone()).map_err(|err| err.to_string())?;
+            let commands: [(&str, Done); 4] = [
+                ("pause", |control| control.paused.load(Ordering::Relaxed)),
+                ("resume", |control| !control.paused.load(Ordering::Relaxed)),
+                ("reload", |control| control.take_reload()),
+                ("quit", |control| control.quit.load(Ordering::Relaxed)),
+            ];
+            for (command, done) in commands {
+                let mut fifo = OpenOptions::new()
+                    .write(true)
+                    .open(&path)
+                    .map_err(|err| err.to_string())?;
+                writeln!(fifo, "{}", command).map_err(|err| err.to_string())?;
+                drop(fifo);
+                let start = Instant::now();
+                while !done(&control) {
+                    if start.elapsed() > Duration::from_secs(2) {
+                        return Err(format!("{:?} wasn't picked up", command));
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+            let file = dir.join("file");
+            fs::write(&file, "").map_err(|err| err.to_string())?;
+            match listen_fifo(file, Arc::new(Control::default())) {
+                Ok(()) => Err("listened on a plain file".to_string()),
+                Err(_) => Ok(()),
+            }
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+}
@@ -0,0 +1,311 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::{Args, Curve, SpeedMetric},
+    expr,
+    velocity::VelocityMap,
+};
+
+// bumped whenever a change would make older versions misread a file
+pub const CURVE_FILE_VERSION: u64 = 1;
+
+// just the curve and what shapes it, for curve editors to load and save
+#[derive(Serialize, Deserialize)]
+pub struct CurveFile {
+    pub version: u64,
+    pub sens_mult: f64,
+    pub curve: CurveDef,
+    #[serde(default)]
+    pub speed_metric: SpeedMetric,
+    #[serde(default)]
+    pub directions: Directions,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum CurveDef {
+    Linear {
+        accel: f64,
+        // json has no infinity, so no cap is left out
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cap: Option<f64>,
+        #[serde(default)]
+        offset: f64,
+    },
+    Expr {
+        expr: String,
+    },
+    VelocityMap {
+        points: Vec<(f64, f64)>,
+    },
+    Breakaway {
+        threshold: f64,
+        cap: f64,
+        #[serde(default)]
+        blend: f64,
+        // left out, it's the same default as --breakaway-hysteresis
+        #[serde(default)]
+        hysteresis: Option<f64>,
+    },
+    Segmented {
+        segments: Vec<SegmentDef>,
+    },
+}
+
+// one of a segmented curve's curves, from the speed it takes over at
+#[derive(Serialize, Deserialize)]
+pub struct SegmentDef {
+    pub from: f64,
+    #[serde(flatten)]
+    pub curve: CurveDef,
+}
+
+// the per-direction multipliers of each axis
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Directions {
+    pub x_pos: f64,
+    pub x_neg: f64,
+    pub y_pos: f64,
+    pub y_neg: f64,
+}
+
+impl Default for Directions {
+    fn default() -> Directions {
+        Directions {
+            x_pos: 1.0,
+            x_neg: 1.0,
+            y_pos: 1.0,
+            y_neg: 1.0,
+        }
+    }
+}
+
+impl CurveDef {
+    pub fn to_curve(&self) -> Result<Curve, String> {
+        Ok(match *self {
+            CurveDef::Linear { accel, cap, offset } => Curve::Linear {
+                accel,
+                cap: cap.unwrap_or(f64::INFINITY),
+                offset,
+            },
+            CurveDef::Expr { expr: ref source } => Curve::Expr {
+                expr: expr::parse(source)?,
+                source: source.clone(),
+            },
+            CurveDef::VelocityMap { ref points } => {
+                Curve::VelocityMap(VelocityMap::from_points(points)?)
+            }
+            CurveDef::Breakaway {
+                threshold,
+                cap,
+                blend,
+                hysteresis,
+            } => Curve::Breakaway {
+                threshold,
+                cap,
+                blend,
+                hysteresis: hysteresis.unwrap_or(threshold / 10.0),
+            },
+            CurveDef::Segmented { ref segments } => {
+                let segments = segments
+                    .iter()
+                    .map(|segment| Ok((segment.from, segment.curve.to_curve()?)))
+                    .collect::<Result<_, String>>()?;
+                segmented(segments)?
+            }
+        })
+    }
+
+    pub fn from_curve(curve: &Curve) -> CurveDef {
+        match *curve {
+            Curve::Linear { accel, cap, offset } => CurveDef::Linear {
+                accel,
+                cap: cap.is_finite().then_some(cap),
+                offset,
+            },
+            Curve::Expr { ref source, .. } => CurveDef::Expr {
+                expr: source.clone(),
+            },
+            Curve::VelocityMap(ref map) => CurveDef::VelocityMap {
+                points: map.points().to_vec(),
+            },
+            Curve::Breakaway {
+                threshold,
+                cap,
+                blend,
+                hysteresis,
+            } => CurveDef::Breakaway {
+                threshold,
+                cap,
+                blend,
+                hysteresis: Some(hysteresis),
+            },
+            Curve::Segmented(ref segments) => CurveDef::Segmented {
+                segments: segments
+                    .iter()
+                    .map(|(from, curve)| SegmentDef {
+                        from: *from,
+                        curve: CurveDef::from_curve(curve),
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+// the segments as one curve, as long as they start standing still and go up in speed, without any
+// segmented curves of their own
+pub fn segmented(segments: Vec<(f64, Curve)>) -> Result<Curve, String> {
+    if segments.first().is_none_or(|(from, _)| *from != 0.0) {
+        return Err("the first segment has to be from 0".to_string());
+    }
+    if let Some((from, _)) = segments.iter().find(|(from, _)| !from.is_finite()) {
+        return Err(format!("a segment can't be from {}", from));
+    }
+    if let Some(pair) = segments.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+        return Err(format!(
+            "segments have to go up in speed, but {} comes after {}",
+            pair[1].0, pair[0].0
+        ));
+    }
+    if segments
+        .iter()
+        .any(|(_, curve)| matches!(curve, Curve::Segmented(_)))
+    {
+        return Err("a segment can't be segmented itself".to_string());
+    }
+    Ok(Curve::Segmented(segments))
+}
+
+impl CurveFile {
+    // the sensitivity multiplier is stored as used, so after any resolution scaling
+    pub fn from_args(args: &Args) -> CurveFile {
+        CurveFile {
+            version: CURVE_FILE_VERSION,
+            sens_mult: args.sens_mult,
+            curve: CurveDef::from_curve(&args.curve),
+            speed_metric: args.speed_metric,
+            directions: Directions {
+                x_pos: args.x_pos_mult,
+                x_neg: args.x_neg_mult,
+                y_pos: args.y_pos_mult,
+                y_neg: args.y_neg_mult,
+            },
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<CurveFile, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let value: serde_json::Value =
+            serde_json::from_str(&contents).map_err(|err| err.to_string())?;
+        // checked first so a newer file fails on its version instead of some field it changed
+        match value.get("version").and_then(serde_json::Value::as_u64) {
+            None => return Err("missing a version".to_string()),
+            Some(version) if version > CURVE_FILE_VERSION => {
+                return Err(format!(
+                    "version {} is newer than the newest this understands ({})",
+                    version, CURVE_FILE_VERSION
+                ))
+            }
+            Some(_) => {}
+        }
+        serde_json::from_value(value).map_err(|err| err.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs};
+
+    use super::{CurveFile, CURVE_FILE_VERSION};
+    use crate::args::parse_args;
+
+    fn parsed(options: &str) -> Result<CurveFile, String> {
+        let given = options.split_whitespace().chain(["selftest-device"]);
+        let args = parse_args(given.map(OsString::from).collect())
+            .map_err(|err| format!("bad options {:?}: {}", options, err))?;
+        Ok(CurveFile::from_args(&args))
+    }
+
+    fn contents(file: &CurveFile) -> serde_json::Value {
+        serde_json::to_value(file).unwrap()
+    }
+
+    // every kind of curve exported and imported again is the same curve, with the same
+    // sensitivity, speed metric and direction multipliers
+    #[test]
+    fn round_trip() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-curve-{}", std::process::id()));
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let result = (|| {
+            let map = dir.join("map.csv");
+            fs::write(&map, "0,0\n10,20\n30,90\n").map_err(|err| err.to_string())?;
+            let option_sets = [
+                "-m 2 -a 0.1 -c 3 -o 1".to_string(),
+                "-m 1 -a 0.05".to_string(),
+                "-m 1 --expr 1+s/10 --speed-metric counts-per-frame".to_string(),
+                format!("-m 1.5 --velocity-map {}", map.display()),
+                "-m 1 --breakaway 5 -c 2 --breakaway-blend 1 --x-neg-mult 0.8 --y-pos-mult 1.2"
+                    .to_string(),
+                concat!(
+                    r#"-m 1 --segment {"from":0,"type":"linear","accel":0.1} "#,
+                    r#"--segment {"from":10,"type":"expr","expr":"1+s/10"}"#,
+                )
+                .to_string(),
+            ];
+            let path = dir.join("curve.json");
+            for options in option_sets {
+                let exported = parsed(&options)?;
+                exported.save(&path).map_err(|err| err.to_string())?;
+                let loaded = CurveFile::load(&path)?;
+                loaded.curve.to_curve()?;
+                let imported = parsed(&format!("--import-curve {}", path.display()))?;
+                for (read, again) in [("loaded", &loaded), ("imported", &imported)] {
+                    if contents(again) != contents(&exported) {
+                        return Err(format!(
+                            "{:?} exported {} but {} as {}",
+                            options,
+                            contents(&exported),
+                            read,
+                            contents(again)
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    // a file from a newer version fails on that rather than on whatever it changed
+    #[test]
+    fn future_version() -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!("accelerator-future-{}", std::process::id()));
+        let newer = CURVE_FILE_VERSION + 1;
+        let file = format!(
+            r#"{{"version": {}, "sens_mult": 1, "curve": {{"type": "spline"}}}}"#,
+            newer
+        );
+        fs::write(&path, file).map_err(|err| err.to_string())?;
+        let loaded = CurveFile::load(&path);
+        let _ = fs::remove_file(&path);
+        let expected = format!(
+            "version {} is newer than the newest this understands ({})",
+            newer, CURVE_FILE_VERSION
+        );
+        match loaded {
+            Err(err) if err == expected => Ok(()),
+            Err(err) => Err(format!("rejected with {:?}", err)),
+            Ok(_) => Err("a newer version was accepted".to_string()),
+        }
+    }
+}
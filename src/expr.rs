@@ -0,0 +1,363 @@
+// arithmetic over the speed s, for writing a curve out directly instead of picking parameters
+
+pub enum Expr {
+    Number(f64),
+    Speed,
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Clone, Copy)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Clone, Copy)]
+pub enum Function {
+    Min,
+    Max,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Pow,
+    Clamp,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        Some(match name {
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" => Function::Ln,
+            "pow" => Function::Pow,
+            "clamp" => Function::Clamp,
+            _ => return None,
+        })
+    }
+
+    // min and max take any number of arguments past the first
+    fn takes(self, count: usize) -> bool {
+        match self {
+            Function::Min | Function::Max => count >= 1,
+            Function::Abs | Function::Sqrt | Function::Exp | Function::Ln => count == 1,
+            Function::Pow => count == 2,
+            Function::Clamp => count == 3,
+        }
+    }
+}
+
+impl Expr {
+    pub fn eval(&self, speed: f64) -> f64 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Speed => speed,
+            Expr::Neg(inner) => -inner.eval(speed),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(speed), rhs.eval(speed));
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                    Op::Pow => lhs.powf(rhs),
+                }
+            }
+            Expr::Call(function, args) => {
+                let mut values = args.iter().map(|arg| arg.eval(speed));
+                // arity was checked when parsing
+                let first = values.next().unwrap();
+                match function {
+                    Function::Min => values.fold(first, f64::min),
+                    Function::Max => values.fold(first, f64::max),
+                    Function::Abs => first.abs(),
+                    Function::Sqrt => first.sqrt(),
+                    Function::Exp => first.exp(),
+                    Function::Ln => first.ln(),
+                    Function::Pow => first.powf(values.next().unwrap()),
+                    Function::Clamp => {
+                        let (low, high) = (values.next().unwrap(), values.next().unwrap());
+                        first.max(low).min(high)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let bytes = source.as_bytes();
+                let mut end = start;
+                while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b'.') {
+                    end += 1;
+                }
+                // an exponent, only if digits follow so 2e isn't eaten as a number
+                if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+                    let mut exponent = end + 1;
+                    if exponent < bytes.len()
+                        && (bytes[exponent] == b'+' || bytes[exponent] == b'-')
+                    {
+                        exponent += 1;
+                    }
+                    if exponent < bytes.len() && bytes[exponent].is_ascii_digit() {
+                        end = exponent;
+                        while end < bytes.len() && bytes[end].is_ascii_digit() {
+                            end += 1;
+                        }
+                    }
+                }
+                while chars.peek().is_some_and(|&(i, _)| i < end) {
+                    chars.next();
+                }
+                let number = &source[start..end];
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid number '{}'", number))?,
+                ));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Name(source[start..end].to_string()));
+            }
+            '+' | '-' | '*' | '/' | '^' => {
+                tokens.push(Token::Op(c));
+                chars.next();
+            }
+            '(' | ')' | ',' => {
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Comma,
+                });
+                chars.next();
+            }
+            c => return Err(format!("unexpected '{}' at {}", c, start)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op('+')) => Op::Add,
+                Some(Token::Op('-')) => Op::Sub,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    // product := unary (('*' | '/') unary)*
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op('*')) => Op::Mul,
+                Some(Token::Op('/')) => Op::Div,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    // unary := '-' unary | power, so -s^2 is -(s^2)
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Op('-')) {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.power()
+        }
+    }
+
+    // power := atom ('^' unary)?, right associative
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.atom()?;
+        if self.eat(&Token::Op('^')) {
+            Ok(Expr::Binary(
+                Op::Pow,
+                Box::new(base),
+                Box::new(self.unary()?),
+            ))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Open) => {
+                let inner = self.sum()?;
+                if !self.eat(&Token::Close) {
+                    return Err("missing ')'".to_string());
+                }
+                Ok(inner)
+            }
+            Some(Token::Name(name)) if self.peek() == Some(&Token::Open) => {
+                let function = Function::from_name(&name)
+                    .ok_or_else(|| format!("unknown function '{}'", name))?;
+                self.pos += 1;
+                let mut args = Vec::new();
+                if !self.eat(&Token::Close) {
+                    loop {
+                        args.push(self.sum()?);
+                        if self.eat(&Token::Close) {
+                            break;
+                        }
+                        if !self.eat(&Token::Comma) {
+                            return Err(format!("missing ')' after arguments to '{}'", name));
+                        }
+                    }
+                }
+                if !function.takes(args.len()) {
+                    return Err(format!(
+                        "wrong number of arguments to '{}', got {}",
+                        name,
+                        args.len()
+                    ));
+                }
+                Ok(Expr::Call(function, args))
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "s" => Ok(Expr::Speed),
+                _ => Err(format!(
+                    "unknown variable '{}', only s (speed) is known",
+                    name
+                )),
+            },
+            Some(_) => Err("expected a number, variable, function or '('".to_string()),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+pub fn parse(source: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    let expr = parser.sum()?;
+    if parser.pos < parser.tokens.len() {
+        return Err("unexpected trailing input".to_string());
+    }
+    Ok(expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    // each at speeds picked to land on round numbers, covering precedence, unary minus binding
+    // looser than ^, and every function
+    #[test]
+    fn known_speeds() -> Result<(), String> {
+        let cases = [
+            ("min(1 + 0.05*(s-5), 3)", 25.0, 2.0),
+            ("min(1 + 0.05*(s-5), 3)", 100.0, 3.0),
+            ("1 + 2*3^2", 0.0, 19.0),
+            ("2^3^2", 0.0, 512.0),
+            ("-s^2", 3.0, -9.0),
+            ("1 - s - 1", 2.0, -2.0),
+            ("max(s, 2, 4) / abs(-2)", 6.0, 3.0),
+            ("sqrt(s) + ln(exp(1)) + pow(2, 3)", 16.0, 13.0),
+            ("clamp(s / 10, 1, 2)", 5.0, 1.0),
+            ("clamp(s / 10, 1, 2)", 15.0, 1.5),
+        ];
+        for (source, speed, expected) in cases {
+            let value = parse(source)?.eval(speed);
+            if (value - expected).abs() > 1e-9 {
+                return Err(format!(
+                    "{:?} at {} came to {}, expected {}",
+                    source, speed, value, expected
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() -> Result<(), String> {
+        let cases = [
+            ("1 + v", "unknown variable 'v', only s (speed) is known"),
+            ("foo(s)", "unknown function 'foo'"),
+            ("(1 + s", "missing ')'"),
+            ("min(s, 2", "missing ')' after arguments to 'min'"),
+            ("pow(s)", "wrong number of arguments to 'pow', got 1"),
+            ("1 +", "unexpected end of expression"),
+            ("1 2", "unexpected trailing input"),
+            ("*", "expected a number, variable, function or '('"),
+        ];
+        for (source, expected) in cases {
+            match parse(source) {
+                Err(err) if err == expected => {}
+                Err(err) => return Err(format!("{:?} rejected with {:?}", source, err)),
+                Ok(_) => return Err(format!("{:?} accepted", source)),
+            }
+        }
+        Ok(())
+    }
+}
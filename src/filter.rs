@@ -0,0 +1,133 @@
+use std::{collections::VecDeque, f64::consts::PI};
+
+// cutoff for the derivative, the one euro paper finds 1hz works in general
+const DERIVATIVE_CUTOFF: f64 = 1.0;
+
+// exponential smoothing with cutoff in hz over a step of dt seconds
+fn smoothing(cutoff: f64, dt: f64) -> f64 {
+    let tau = 1.0 / (2.0 * PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+// low pass filter whose cutoff rises with how fast the signal changes, so slow jittery signals are
+// smoothed heavily while fast ones lag little
+// see https://gery.casiez.net/1euro/
+pub struct OneEuroFilter {
+    // last filtered value and derivative
+    last: Option<(f64, f64)>,
+}
+
+impl OneEuroFilter {
+    pub fn new() -> OneEuroFilter {
+        OneEuroFilter { last: None }
+    }
+
+    pub fn filter(&mut self, value: f64, dt: f64, min_cutoff: f64, beta: f64) -> f64 {
+        let (last_value, last_derivative) = match self.last {
+            Some(last) if dt > 0.0 => last,
+            // nothing to go on yet, or no time has passed to smooth over
+            _ => {
+                self.last = Some((value, 0.0));
+                return value;
+            }
+        };
+        let derivative = (value - last_value) / dt;
+        let a = smoothing(DERIVATIVE_CUTOFF, dt);
+        let derivative = last_derivative + a * (derivative - last_derivative);
+
+        let cutoff = min_cutoff + beta * derivative.abs();
+        let a = smoothing(cutoff, dt);
+        let value = last_value + a * (value - last_value);
+        self.last = Some((value, derivative));
+        value
+    }
+}
+
+// exponential smoothing of one axis's raw movement, which keeps what it holds back to let out
+// with later frames instead of losing it, so steady movement comes out at full size
+#[derive(Default)]
+pub struct InputSmoother {
+    left: f64,
+}
+
+impl InputSmoother {
+    // alpha of the movement and what's left from before goes out now, everything left if the axis
+    // didn't move
+    pub fn smooth(&mut self, delta: f64, alpha: f64) -> f64 {
+        let total = delta + self.left;
+        let out = if delta == 0.0 { total } else { alpha * total };
+        self.left = total - out;
+        out
+    }
+
+    pub fn holding(&self) -> bool {
+        self.left != 0.0
+    }
+}
+
+// the last frames' accelerated movement, each frame writing their average over its share of the
+// time they took instead of its own movement; what that holds back or runs ahead by is owed, and
+// paid out once the mouse stops, so nothing is lost or added
+#[derive(Default)]
+pub struct OutputAverage {
+    // movement of each frame in the window, newest last, and how long it took, None for the first
+    // after an idle gap
+    frames: VecDeque<(f64, f64, Option<f64>)>,
+    owed: (f64, f64),
+}
+
+impl OutputAverage {
+    // the average over the last window frames, ms of them this frame's, or everything owed if the
+    // frame didn't move
+    pub fn average(&mut self, x: f64, y: f64, ms: Option<f64>, window: usize) -> (f64, f64) {
+        self.owed.0 += x;
+        self.owed.1 += y;
+        if x == 0.0 && y == 0.0 {
+            return self.release();
+        }
+        if ms.is_none() {
+            self.frames.clear();
+        }
+        self.frames.push_back((x, y, ms.map(|ms| ms.max(0.0))));
+        if self.frames.len() > window {
+            self.frames.pop_front();
+        }
+        // a frame starting movement again is taken to be as long as the others
+        let known: Vec<f64> = self.frames.iter().filter_map(|frame| frame.2).collect();
+        let usual = if known.is_empty() {
+            1.0
+        } else {
+            known.iter().sum::<f64>() / known.len() as f64
+        };
+        let (sum_x, sum_y, sum_ms) =
+            self.frames
+                .iter()
+                .fold((0.0, 0.0, 0.0), |(sum_x, sum_y, sum_ms), frame| {
+                    (
+                        sum_x + frame.0,
+                        sum_y + frame.1,
+                        sum_ms + frame.2.unwrap_or(usual),
+                    )
+                });
+        // frames sharing a timestamp weigh the same
+        let share = if sum_ms > 0.0 {
+            ms.unwrap_or(usual).max(0.0) / sum_ms
+        } else {
+            1.0 / self.frames.len() as f64
+        };
+        let out = (sum_x * share, sum_y * share);
+        self.owed.0 -= out.0;
+        self.owed.1 -= out.1;
+        out
+    }
+
+    // everything owed, starting the window over
+    pub fn release(&mut self) -> (f64, f64) {
+        self.frames.clear();
+        std::mem::take(&mut self.owed)
+    }
+
+    pub fn holding(&self) -> bool {
+        self.owed != (0.0, 0.0)
+    }
+}
@@ -0,0 +1,225 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL, EV_SYN},
+    InputEvent, TimeVal,
+};
+
+use crate::{
+    accel::Accelerator,
+    args::{Args, Curve},
+    backend::MockBackend,
+    control::Control,
+    taps::Taps,
+};
+
+const FRAMES: u64 = 1_000_000;
+
+// splitmix64, plenty for reproducible garbage
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // inclusive on both ends
+    fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + (self.next() % (high - low + 1) as u64) as i64
+    }
+
+    fn one_in(&mut self, n: u64) -> bool {
+        self.next().is_multiple_of(n)
+    }
+}
+
+fn delta(rng: &mut Rng) -> i32 {
+    if rng.one_in(50) {
+        rng.range(-1000, 1000) as i32
+    } else {
+        rng.range(-20, 20) as i32
+    }
+}
+
+// time between frames in microseconds, from 8000hz polling to long idle gaps, including
+// frames that share a timestamp
+fn gap(rng: &mut Rng) -> i64 {
+    match rng.range(0, 999) {
+        0 => 0,
+        1..=10 => rng.range(20_000, 10_000_000),
+        _ => rng.range(125, 20_000),
+    }
+}
+
+fn rel(time: TimeVal, code: EV_REL, value: i32) -> InputEvent {
+    InputEvent::new(&time, &EventCode::EV_REL(code), value)
+}
+
+// runs seeded random frames through the whole event path, returns the exit code
+pub fn fuzz(args: &Args, seed: u64) -> i32 {
+    match fuzz_frames(args, seed, FRAMES) {
+        Ok(()) => {
+            println!("{} frames ok", FRAMES);
+            0
+        }
+        Err(problem) => {
+            eprintln!("Error: seed {} {}", seed, problem);
+            1
+        }
+    }
+}
+
+// the first frame that broke an invariant, with the input it broke it on
+fn fuzz_frames(args: &Args, seed: u64, frames: u64) -> Result<(), String> {
+    let mut rng = Rng(seed);
+    let mut accel = Accelerator::new();
+    let control = Control::default();
+    let mut taps = Taps::none();
+    let mut time = TimeVal::new(rng.range(0, i32::MAX as i64), 0);
+    // output can't outgrow the input by more than the largest multiplier, plus what the
+    // accumulator and rounding carry (infinite if the curve has no cap)
+    let direction_mult = [
+        args.x_pos_mult,
+        args.x_neg_mult,
+        args.y_pos_mult,
+        args.y_neg_mult,
+    ]
+    .into_iter()
+    .fold(1.0, f64::max);
+    let cap = match args.curve {
+        Curve::Linear { cap, .. } | Curve::Breakaway { cap, .. } => cap,
+        _ => f64::INFINITY,
+    };
+    let max_mult = args.sens_mult.abs() * cap.max(1.0) * direction_mult;
+    for frame in 0..frames {
+        let x = delta(&mut rng);
+        let y = delta(&mut rng);
+        time = TimeVal::new(time.tv_sec, time.tv_usec + gap(&mut rng));
+
+        let mut events = Vec::new();
+        // devices leave out axes that didn't move, but not always
+        if x != 0 || rng.one_in(8) {
+            events.push(rel(time, EV_REL::REL_X, x));
+        }
+        if y != 0 || rng.one_in(8) {
+            events.push(rel(time, EV_REL::REL_Y, y));
+        }
+        events.push(InputEvent::new(
+            &time,
+            &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            0,
+        ));
+
+        let mut backend = MockBackend::new(events);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            crate::run(&mut backend, args, &mut accel, &mut taps, &control)
+        }));
+        let problem = match result {
+            Err(_) => Some("panicked".to_string()),
+            Ok(Err(err)) => Some(format!("failed: {}", err)),
+            Ok(Ok(_)) => check(&backend.written, &accel, x, y, max_mult),
+        };
+        if let Some(problem) = problem {
+            return Err(format!(
+                "frame {} (x {}, y {}, time {}.{:06}) {}",
+                frame, x, y, time.tv_sec, time.tv_usec, problem
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn check(
+    written: &[InputEvent],
+    accel: &Accelerator,
+    x: i32,
+    y: i32,
+    max_mult: f64,
+) -> Option<String> {
+    for accum in [accel.x_accum, accel.y_accum] {
+        if !accum.is_finite() || accum.abs() >= 1.0 {
+            return Some(format!("left accumulator at {}", accum));
+        }
+    }
+    for event in written {
+        let input = match event.event_code {
+            EventCode::EV_REL(EV_REL::REL_X) => x,
+            EventCode::EV_REL(EV_REL::REL_Y) => y,
+            _ => continue,
+        };
+        let bound = (input as f64).abs() * max_mult + 2.0;
+        if (event.value as f64).abs() > bound {
+            return Some(format!(
+                "wrote {:?} {}, over the bound of {}",
+                event.event_code, event.value, bound
+            ));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use evdev_rs::{
+        enums::{EventCode, EV_REL},
+        InputEvent, TimeVal,
+    };
+
+    use super::{check, fuzz_frames};
+    use crate::{accel::Accelerator, args::parse_args};
+
+    // a few seeds against curves with and without a cap, and the options that carry movement
+    // between frames, all keep within the invariants
+    #[test]
+    fn seeds() -> Result<(), String> {
+        let option_sets = [
+            "-m 1 -a 0.1",
+            "-m 0.3 -a 0.05 -c 4 -o 2 --min-move",
+            "-m 2 --breakaway 8 -c 3 --breakaway-blend 4",
+            "--expr 1+s/10 --input-smooth 0.5 --x-pos-mult 1.5",
+        ];
+        for options in option_sets {
+            let given = options.split_whitespace().chain(["selftest-device"]);
+            let args = parse_args(given.map(OsString::from).collect())
+                .map_err(|err| format!("bad options {:?}: {}", options, err))?;
+            for seed in 0..4 {
+                fuzz_frames(&args, seed, 5_000)
+                    .map_err(|problem| format!("{:?} seed {}: {}", options, seed, problem))?;
+            }
+        }
+        Ok(())
+    }
+
+    // output past what the multiplier could make of the input, or a broken accumulator, is
+    // caught
+    #[test]
+    fn violations() -> Result<(), String> {
+        let written = [InputEvent::new(
+            &TimeVal::new(1, 0),
+            &EventCode::EV_REL(EV_REL::REL_X),
+            13,
+        )];
+        let accel = Accelerator::new();
+        if check(&written, &accel, 5, 0, 2.0).is_none() {
+            return Err("13 from 5 at a multiplier of 2 passed".to_string());
+        }
+        if let Some(problem) = check(&written, &accel, 6, 0, 2.0) {
+            return Err(format!(
+                "13 from 6 at a multiplier of 2 failed: {}",
+                problem
+            ));
+        }
+        let mut accel = Accelerator::new();
+        accel.x_accum = f64::NAN;
+        if check(&[], &accel, 0, 0, 1.0).is_none() {
+            return Err("a nan accumulator passed".to_string());
+        }
+        Ok(())
+    }
+}
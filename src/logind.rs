@@ -0,0 +1,657 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{self, Read, Write},
+    mem,
+    os::unix::{
+        fs::MetadataExt,
+        io::{AsRawFd, FromRawFd},
+        net::UnixStream,
+    },
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use crate::{backend::opener::DeviceOpener, control::spawn_unsignaled};
+
+const SYSTEM_BUS: &str = "/run/dbus/system_bus_socket";
+const BUS_NAME: &str = "org.freedesktop.DBus";
+const BUS_PATH: &str = "/org/freedesktop/DBus";
+const LOGIND: &str = "org.freedesktop.login1";
+// whichever session the process is in
+const SESSION: &str = "/org/freedesktop/login1/session/auto";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+// message types
+const METHOD_CALL: u8 = 1;
+const METHOD_RETURN: u8 = 2;
+const ERROR: u8 = 3;
+const SIGNAL: u8 = 4;
+const NO_REPLY_EXPECTED: u8 = 1;
+
+// header fields
+const PATH: u8 = 1;
+const INTERFACE: u8 = 2;
+const MEMBER: u8 = 3;
+const ERROR_NAME: u8 = 4;
+const REPLY_SERIAL: u8 = 5;
+const DESTINATION: u8 = 6;
+const SIGNATURE: u8 = 8;
+const UNIX_FDS: u8 = 9;
+
+// the most fds taken from one read off the bus
+const MAX_FDS: usize = 16;
+
+fn bad_message() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "bad message from the bus")
+}
+
+// arguments of the few types the session's methods take
+enum Arg {
+    U32(u32),
+    Bool(bool),
+}
+
+// little endian D-Bus marshalling, aligned from the start of the buffer
+#[derive(Default)]
+struct Marshal(Vec<u8>);
+
+impl Marshal {
+    fn align(&mut self, to: usize) {
+        self.0.resize(self.0.len().next_multiple_of(to), 0);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.align(4);
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.push(0);
+    }
+
+    fn signature(&mut self, value: &str) {
+        self.u8(value.len() as u8);
+        self.0.extend_from_slice(value.as_bytes());
+        self.0.push(0);
+    }
+
+    fn arg(&mut self, arg: &Arg) {
+        match *arg {
+            Arg::U32(value) => self.u32(value),
+            Arg::Bool(value) => self.u32(value as u32),
+        }
+    }
+
+    // a header field, a struct of its code and a variant
+    fn field(&mut self, code: u8, signature: &str, value: impl FnOnce(&mut Marshal)) {
+        self.align(8);
+        self.u8(code);
+        self.signature(signature);
+        value(self);
+    }
+}
+
+fn signature_of(args: &[Arg]) -> String {
+    args.iter()
+        .map(|arg| match arg {
+            Arg::U32(_) => 'u',
+            Arg::Bool(_) => 'b',
+        })
+        .collect()
+}
+
+// a method call, without a destination for one to the bus itself
+struct Call<'a> {
+    destination: &'a str,
+    path: &'a str,
+    interface: &'a str,
+    member: &'a str,
+    args: &'a [Arg],
+}
+
+fn encode_call(serial: u32, flags: u8, call: &Call) -> Vec<u8> {
+    let mut body = Marshal::default();
+    for arg in call.args {
+        body.arg(arg);
+    }
+    let mut message = Marshal::default();
+    message.0.extend_from_slice(&[b'l', METHOD_CALL, flags, 1]);
+    message.u32(body.0.len() as u32);
+    message.u32(serial);
+    // the length of the fields, filled in once they're written
+    message.u32(0);
+    message.field(PATH, "o", |m| m.str(call.path));
+    message.field(INTERFACE, "s", |m| m.str(call.interface));
+    message.field(MEMBER, "s", |m| m.str(call.member));
+    message.field(DESTINATION, "s", |m| m.str(call.destination));
+    let signature = signature_of(call.args);
+    if !signature.is_empty() {
+        message.field(SIGNATURE, "g", |m| m.signature(&signature));
+    }
+    let fields_len = message.0.len() as u32 - 16;
+    message.0[12..16].copy_from_slice(&fields_len.to_le_bytes());
+    message.align(8);
+    message.0.extend_from_slice(&body.0);
+    message.0
+}
+
+// reads values back out of a marshalled buffer
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn new(buf: &[u8]) -> Reader<'_> {
+        Reader { buf, pos: 0 }
+    }
+
+    fn align(&mut self, to: usize) {
+        self.pos = self.pos.next_multiple_of(to);
+    }
+
+    fn bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or_else(bad_message)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.align(4);
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let value = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        // the nul after it
+        self.bytes(1)?;
+        Ok(value)
+    }
+
+    fn signature(&mut self) -> io::Result<String> {
+        let len = self.u8()? as usize;
+        let value = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.bytes(1)?;
+        Ok(value)
+    }
+}
+
+// a message read off the bus, with only the parts used here
+#[derive(Default)]
+struct Message {
+    msg_type: u8,
+    reply_serial: Option<u32>,
+    interface: Option<String>,
+    member: Option<String>,
+    error_name: Option<String>,
+    body: Vec<u8>,
+    fds: Vec<File>,
+}
+
+fn decode_header(header: &[u8], message: &mut Message) -> io::Result<usize> {
+    let mut reader = Reader::new(header);
+    reader.pos = 16;
+    let mut fds = 0;
+    while reader.pos < header.len() {
+        reader.align(8);
+        let code = reader.u8()?;
+        let signature = reader.signature()?;
+        match (code, signature.as_str()) {
+            (REPLY_SERIAL, "u") => message.reply_serial = Some(reader.u32()?),
+            (UNIX_FDS, "u") => fds = reader.u32()? as usize,
+            (INTERFACE, "s") => message.interface = Some(reader.str()?),
+            (MEMBER, "s") => message.member = Some(reader.str()?),
+            (ERROR_NAME, "s") => message.error_name = Some(reader.str()?),
+            (_, "s" | "o") => drop(reader.str()?),
+            (_, "g") => drop(reader.signature()?),
+            (_, "u") => drop(reader.u32()?),
+            _ => return Err(bad_message()),
+        }
+    }
+    Ok(fds)
+}
+
+// a connection to the system bus, just enough of the protocol to talk to logind
+struct Bus {
+    stream: UnixStream,
+    serial: u32,
+    // read but not yet made into a message, along with the fds that came with it
+    buf: Vec<u8>,
+    fds: VecDeque<File>,
+}
+
+impl Bus {
+    fn system() -> io::Result<Bus> {
+        let path = std::env::var("DBUS_SYSTEM_BUS_ADDRESS")
+            .ok()
+            .and_then(|address| address.strip_prefix("unix:path=").map(str::to_string))
+            .unwrap_or_else(|| SYSTEM_BUS.to_string());
+        let mut bus = Bus {
+            stream: UnixStream::connect(path)?,
+            serial: 0,
+            buf: Vec::new(),
+            fds: VecDeque::new(),
+        };
+        bus.authenticate()?;
+        bus.call(&Call {
+            destination: BUS_NAME,
+            path: BUS_PATH,
+            interface: BUS_NAME,
+            member: "Hello",
+            args: &[],
+        })?;
+        Ok(bus)
+    }
+
+    // as our uid, with fds passed along
+    fn authenticate(&mut self) -> io::Result<()> {
+        let uid: String = unsafe { libc::getuid() }
+            .to_string()
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        self.stream
+            .write_all(format!("\0AUTH EXTERNAL {}\r\n", uid).as_bytes())?;
+        self.expect_line("OK")?;
+        self.stream.write_all(b"NEGOTIATE_UNIX_FD\r\n")?;
+        self.expect_line("AGREE_UNIX_FD")?;
+        self.stream.write_all(b"BEGIN\r\n")
+    }
+
+    // a byte at a time, so nothing after it is read
+    fn expect_line(&mut self, start: &str) -> io::Result<()> {
+        let mut line = Vec::new();
+        while !line.ends_with(b"\r\n") {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte)?;
+            line.push(byte[0]);
+        }
+        if !line.starts_with(start.as_bytes()) {
+            return Err(io::Error::other(format!(
+                "the bus refused: {}",
+                String::from_utf8_lossy(&line).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, flags: u8, call: &Call) -> io::Result<u32> {
+        self.serial += 1;
+        self.stream
+            .write_all(&encode_call(self.serial, flags, call))?;
+        Ok(self.serial)
+    }
+
+    // waits for the reply, skipping anything else that comes first
+    fn call(&mut self, call: &Call) -> io::Result<Message> {
+        let serial = self.send(0, call)?;
+        loop {
+            let reply = self.recv()?;
+            if reply.reply_serial != Some(serial) {
+                continue;
+            }
+            match reply.msg_type {
+                METHOD_RETURN => return Ok(reply),
+                ERROR => {
+                    let description = Reader::new(&reply.body).str().unwrap_or_default();
+                    return Err(io::Error::other(format!(
+                        "{} failed: {} {}",
+                        call.member,
+                        reply.error_name.unwrap_or_default(),
+                        description
+                    )));
+                }
+                _ => continue,
+            }
+        }
+    }
+
+    fn recv(&mut self) -> io::Result<Message> {
+        loop {
+            if let Some(message) = self.take_message()? {
+                return Ok(message);
+            }
+            self.read_more()?;
+        }
+    }
+
+    // the first whole message read, if there is one yet
+    fn take_message(&mut self) -> io::Result<Option<Message>> {
+        if self.buf.len() < 16 {
+            return Ok(None);
+        }
+        if self.buf[0] != b'l' {
+            return Err(io::Error::other("big endian messages aren't supported"));
+        }
+        let mut reader = Reader::new(&self.buf);
+        reader.pos = 4;
+        let body_len = reader.u32()? as usize;
+        reader.pos = 12;
+        let fields_len = reader.u32()? as usize;
+        let header_len = (16 + fields_len).next_multiple_of(8);
+        if self.buf.len() < header_len + body_len {
+            return Ok(None);
+        }
+        let mut message = Message {
+            msg_type: self.buf[1],
+            ..Message::default()
+        };
+        let fds = decode_header(&self.buf[..16 + fields_len], &mut message)?;
+        message.body = self.buf[header_len..header_len + body_len].to_vec();
+        message.fds = self.fds.drain(..fds.min(self.fds.len())).collect();
+        self.buf.drain(..header_len + body_len);
+        Ok(Some(message))
+    }
+
+    fn read_more(&mut self) -> io::Result<()> {
+        let mut data = [0u8; 4096];
+        let mut iov = libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        };
+        let space = unsafe { libc::CMSG_SPACE((MAX_FDS * mem::size_of::<libc::c_int>()) as u32) };
+        // u64s so it's aligned for the cmsghdrs in it
+        let mut control = vec![0u64; (space as usize).div_ceil(8)];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = space as usize;
+        let read =
+            unsafe { libc::recvmsg(self.stream.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+        while !cmsg.is_null() {
+            let header = unsafe { &*cmsg };
+            if header.cmsg_level == libc::SOL_SOCKET && header.cmsg_type == libc::SCM_RIGHTS {
+                let len = header.cmsg_len as usize - unsafe { libc::CMSG_LEN(0) } as usize;
+                let data: *const libc::c_int = unsafe { libc::CMSG_DATA(cmsg) }.cast();
+                for i in 0..len / mem::size_of::<libc::c_int>() {
+                    let fd = unsafe { data.add(i).read_unaligned() };
+                    self.fds.push_back(unsafe { File::from_raw_fd(fd) });
+                }
+            }
+            cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+        }
+        self.buf.extend_from_slice(&data[..read as usize]);
+        Ok(())
+    }
+}
+
+fn session_call<'a>(member: &'a str, args: &'a [Arg]) -> Call<'a> {
+    Call {
+        destination: LOGIND,
+        path: SESSION,
+        interface: SESSION_INTERFACE,
+        member,
+        args,
+    }
+}
+
+// takes devices from logind as the session's controller instead of opening them, so they're
+// revoked and handed back as the session is switched away from and back to
+pub struct LogindOpener {
+    resumed: Option<Receiver<File>>,
+}
+
+impl LogindOpener {
+    pub fn new() -> LogindOpener {
+        LogindOpener { resumed: None }
+    }
+
+    // the device's new file each time the session comes back, once it's been taken
+    pub fn take_resumed(&mut self) -> Option<Receiver<File>> {
+        self.resumed.take()
+    }
+}
+
+impl DeviceOpener for LogindOpener {
+    fn open(&mut self, path: &Path) -> io::Result<File> {
+        let rdev = fs::metadata(path)?.rdev();
+        let (major, minor) = unsafe { (libc::major(rdev), libc::minor(rdev)) };
+        let mut bus = Bus::system()?;
+        bus.call(&session_call("TakeControl", &[Arg::Bool(false)]))?;
+        let reply = bus.call(&session_call(
+            "TakeDevice",
+            &[Arg::U32(major), Arg::U32(minor)],
+        ))?;
+        let file = reply
+            .fds
+            .into_iter()
+            .next()
+            .ok_or_else(|| io::Error::other("TakeDevice didn't give a device"))?;
+        let (resumed, receiver) = mpsc::channel();
+        spawn_unsignaled(move || watch(bus, major, minor, resumed));
+        self.resumed = Some(receiver);
+        Ok(file)
+    }
+}
+
+// answers logind pausing the device, and passes on the file it gives back on resuming; the
+// connection stays open for as long as the device is wanted, since logind takes back control
+// once it closes
+fn watch(mut bus: Bus, major: u32, minor: u32, resumed: Sender<File>) {
+    loop {
+        let message = match bus.recv() {
+            Ok(message) => message,
+            Err(err) => {
+                eprintln!(
+                    "Warning: lost the connection to logind, the device won't come back after \
+                     switching sessions: {}",
+                    err
+                );
+                return;
+            }
+        };
+        if message.msg_type != SIGNAL || message.interface.as_deref() != Some(SESSION_INTERFACE) {
+            continue;
+        }
+        let mut body = Reader::new(&message.body);
+        let (Ok(signal_major), Ok(signal_minor)) = (body.u32(), body.u32()) else {
+            continue;
+        };
+        if (signal_major, signal_minor) != (major, minor) {
+            continue;
+        }
+        match message.member.as_deref() {
+            // "force" and "gone" don't wait to be answered
+            Some("PauseDevice") if body.str().is_ok_and(|kind| kind == "pause") => {
+                let args = [Arg::U32(major), Arg::U32(minor)];
+                let complete = session_call("PauseDeviceComplete", &args);
+                if let Err(err) = bus.send(NO_REPLY_EXPECTED, &complete) {
+                    eprintln!("Warning: couldn't let logind pause the device: {}", err);
+                }
+            }
+            Some("ResumeDevice") => {
+                let Some(file) = message.fds.into_iter().next() else {
+                    continue;
+                };
+                if resumed.send(file).is_err() {
+                    return;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        fs::{self, File},
+        io::{Read, Write},
+        mem,
+        os::unix::{io::AsRawFd, net::UnixStream},
+    };
+
+    use super::{
+        decode_header, encode_call, Arg, Bus, Call, Marshal, Message, Reader, METHOD_CALL,
+        METHOD_RETURN, REPLY_SERIAL, SIGNATURE, UNIX_FDS,
+    };
+
+    // a bus reading what's written to the other end of the pair
+    fn bus() -> (Bus, UnixStream) {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let bus = Bus {
+            stream: ours,
+            serial: 0,
+            buf: Vec::new(),
+            fds: VecDeque::new(),
+        };
+        (bus, theirs)
+    }
+
+    // a method return for serial carrying one fd, whose index is the body
+    fn fd_reply(serial: u32) -> Vec<u8> {
+        let mut message = Marshal::default();
+        message.0.extend_from_slice(&[b'l', METHOD_RETURN, 0, 1]);
+        message.u32(4);
+        message.u32(100);
+        message.u32(0);
+        message.field(REPLY_SERIAL, "u", |m| m.u32(serial));
+        message.field(SIGNATURE, "g", |m| m.signature("h"));
+        message.field(UNIX_FDS, "u", |m| m.u32(1));
+        let fields_len = message.0.len() as u32 - 16;
+        message.0[12..16].copy_from_slice(&fields_len.to_le_bytes());
+        message.align(8);
+        message.u32(0);
+        message.0
+    }
+
+    fn send_with_fd(stream: &UnixStream, bytes: &[u8], file: &File) {
+        let mut iov = libc::iovec {
+            iov_base: bytes.as_ptr() as *mut libc::c_void,
+            iov_len: bytes.len(),
+        };
+        let fd_len = mem::size_of::<libc::c_int>() as u32;
+        let space = unsafe { libc::CMSG_SPACE(fd_len) } as usize;
+        let mut control = vec![0u64; space.div_ceil(8)];
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = space;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(fd_len) as usize;
+            libc::CMSG_DATA(cmsg)
+                .cast::<libc::c_int>()
+                .write_unaligned(file.as_raw_fd());
+            assert_eq!(
+                libc::sendmsg(stream.as_raw_fd(), &msg, 0),
+                bytes.len() as isize
+            );
+        }
+    }
+
+    // a call's fields each start 8 aligned after padding, its body starts 8 aligned after the
+    // header, and everything reads back as it was written
+    #[test]
+    fn call_round_trip() {
+        let call = Call {
+            destination: "org.freedesktop.login1",
+            path: "/org/freedesktop/login1/session/auto",
+            interface: "org.freedesktop.login1.Session",
+            member: "TakeDevice",
+            args: &[Arg::U32(13), Arg::U32(64), Arg::Bool(true)],
+        };
+        let encoded = encode_call(7, 0, &call);
+        assert_eq!(&encoded[..4], &[b'l', METHOD_CALL, 0, 1]);
+        let mut reader = Reader::new(&encoded);
+        reader.pos = 4;
+        let body_len = reader.u32().unwrap() as usize;
+        assert_eq!(reader.u32().unwrap(), 7);
+        let fields_len = reader.u32().unwrap() as usize;
+        let header_len = (16 + fields_len).next_multiple_of(8);
+        assert_eq!(header_len % 8, 0);
+        assert_eq!(encoded.len(), header_len + body_len);
+        assert!(encoded[16 + fields_len..header_len]
+            .iter()
+            .all(|&byte| byte == 0));
+
+        let mut message = Message::default();
+        let fds = decode_header(&encoded[..16 + fields_len], &mut message).unwrap();
+        assert_eq!(fds, 0);
+        assert_eq!(message.interface.as_deref(), Some(call.interface));
+        assert_eq!(message.member.as_deref(), Some(call.member));
+        let mut body = Reader::new(&encoded[header_len..]);
+        let args = [body.u32(), body.u32(), body.u32()].map(Result::unwrap);
+        assert_eq!(args, [13, 64, 1]);
+    }
+
+    // a header's fd count, and the fds that came with it are handed to it
+    #[test]
+    fn fd_header() {
+        let (mut bus, theirs) = bus();
+        let path = std::env::temp_dir().join(format!("accelerator-fd-{}", std::process::id()));
+        fs::write(&path, "the device").unwrap();
+        let file = File::open(&path).unwrap();
+        send_with_fd(&theirs, &fd_reply(3), &file);
+        let message = bus.recv().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(message.msg_type, METHOD_RETURN);
+        assert_eq!(message.reply_serial, Some(3));
+        assert_eq!(Reader::new(&message.body).u32().unwrap(), 0);
+        assert_eq!(message.fds.len(), 1);
+        let mut contents = String::new();
+        (&message.fds[0]).read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "the device");
+    }
+
+    // a message split across reads is only taken once it's whole, and two in one read are both
+    // taken, in order
+    #[test]
+    fn partial_reads() {
+        let (mut bus, mut theirs) = bus();
+        let call = Call {
+            destination: "org.freedesktop.DBus",
+            path: "/org/freedesktop/DBus",
+            interface: "org.freedesktop.DBus",
+            member: "Hello",
+            args: &[],
+        };
+        let first = encode_call(1, 0, &call);
+        theirs.write_all(&first[..20]).unwrap();
+        bus.read_more().unwrap();
+        assert!(bus.take_message().unwrap().is_none());
+        let mut rest = first[20..].to_vec();
+        rest.extend(encode_call(
+            2,
+            0,
+            &Call {
+                member: "Again",
+                ..call
+            },
+        ));
+        theirs.write_all(&rest).unwrap();
+        let members = [bus.recv().unwrap(), bus.recv().unwrap()].map(|message| message.member);
+        assert_eq!(
+            members,
+            [Some("Hello".to_string()), Some("Again".to_string())]
+        );
+        assert!(bus.buf.is_empty());
+    }
+}
@@ -1,139 +1,1772 @@
-use std::{ffi::OsString, fs::File};
+mod accel;
+mod advice;
+mod args;
+mod backend;
+mod bench;
+mod calibrate;
+mod config;
+mod control;
+mod curve_file;
+mod expr;
+mod filter;
+mod fuzz;
+#[cfg(feature = "logind")]
+mod logind;
+mod notify;
+mod power;
+mod registry;
+mod scroll;
+mod selftest;
+mod stats;
+mod strict;
+mod taps;
+#[cfg(test)]
+mod testing;
+mod throttle;
+mod velocity;
 
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::ErrorKind,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
+
+use accel::Accelerator;
+use advice::{libinput_advice, DeviceMetadata};
+use args::{parse_args, Args, Backend, Mode, HELP};
+#[cfg(feature = "backend-evdev")]
+use backend::evdev::EvdevBackend;
+#[cfg(feature = "logind")]
+use backend::opener::open_or_direct;
+use backend::{
+    evemu::EvemuTee,
+    mirror::Mirror,
+    pattern::{demo_range, Pattern, PatternBackend},
+    raw::RawTee,
+    replay::ReplayBackend,
+    EvdevRsBackend, InputBackend, Output,
+};
+use control::Control;
+use curve_file::CurveFile;
 use evdev_rs::{
-    enums::{EventCode, EV_REL, EV_SYN},
-    Device, GrabMode, InputEvent, ReadFlag, ReadStatus, UInputDevice,
+    enums::{EventCode, EV_KEY, EV_REL, EV_SYN},
+    Device, InputEvent, TimeVal,
 };
+#[cfg(feature = "logind")]
+use logind::LogindOpener;
 use pico_args::Arguments;
+use power::PowerSupply;
+use scroll::{ScrollAccel, SmoothScroll};
+use strict::{Anomaly, Strict};
+use taps::Taps;
+use throttle::Throttle;
 
-fn factor(sens_multiplier: f64, accel: f64, cap: f64, offset: f64, speed: f64) -> f64 {
-    if speed < offset {
-        sens_multiplier
-    } else {
-        sens_multiplier * f64::min(accel.mul_add(speed - offset, 1.0), cap)
+fn main() -> Result<(), std::io::Error> {
+    if Arguments::from_env().contains("-h") {
+        println!("{}", HELP);
+    }
+
+    // these bring their own options, so none are needed
+    if command_line().iter().any(|arg| arg == "--selftest") {
+        std::process::exit(selftest::selftest());
+    }
+    if command_line().iter().any(|arg| arg == "--benchmark-writes") {
+        return bench::benchmark_writes();
+    }
+
+    let args = match parse_args(command_line()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("Error: {}\n{}", err, HELP);
+            std::process::exit(1);
+        }
+    };
+    if args.print_config {
+        println!("{}", config::resolved(&args));
+        if !args.print_config_continue {
+            return Ok(());
+        }
+    }
+
+    let filename = match args.mode {
+        Mode::Device(ref filename) => filename.clone(),
+        Mode::TestPattern(pattern) => {
+            let control = start_control(&args)?;
+            let output = output_for(&args, None);
+            let backend = PatternBackend::open(pattern, args.pattern_speed, &output)?;
+            return run_device(backend, args, &control);
+        }
+        Mode::DemoCurve => {
+            let (from, to) = demo_range(&args.curve);
+            let pattern = Pattern::Sweep { from, to };
+            let narration = pattern
+                .stroke_speeds()
+                .into_iter()
+                .map(|speed| {
+                    let sensitivity =
+                        accel::sensitivity(args.sens_mult, &args.curve, speed, &mut false);
+                    format!(
+                        "demo: {:.3} counts/ms, sensitivity {:.3}",
+                        speed, sensitivity
+                    )
+                })
+                .collect();
+            let control = start_control(&args)?;
+            let output = output_for(&args, None);
+            let backend = PatternBackend::open(pattern, args.pattern_speed, &output)?
+                .with_narration(narration);
+            return run_device(backend, args, &control);
+        }
+        Mode::Replay(ref path) => {
+            let control = start_control(&args)?;
+            let output = output_for(&args, None);
+            let backend = ReplayBackend::open(path, args.replay_speed, &output)?;
+            return run_device(backend, args, &control);
+        }
+        Mode::Fuzz(seed) => std::process::exit(fuzz::fuzz(&args, seed)),
+        Mode::SensAt(speed) => {
+            println!("{}", Accelerator::new().sensitivity_for(&args, speed));
+            return Ok(());
+        }
+        Mode::BenchmarkCurves => {
+            bench::benchmark_curves(&args);
+            return Ok(());
+        }
+        Mode::ExportCurve(ref path) => return CurveFile::from_args(&args).save(path),
+        Mode::CalibrateGain(ref path) => {
+            return calibrate::calibrate_gain(path, args.pattern_speed)
+        }
+        Mode::LibinputAdvice(ref filename) => {
+            let device = Device::new_from_file(File::open(filename)?)?;
+            print!("{}", libinput_advice(&DeviceMetadata::from_device(&device)));
+            return Ok(());
+        }
+    };
+    let control = start_control(&args)?;
+    let output = output_for(&args, Some(&filename));
+    match args.backend {
+        Backend::EvdevRs => {
+            let backend = open_evdev_rs(&args, &filename, &output)?;
+            if args.threaded {
+                run_device(backend.threaded(), args, &control)
+            } else {
+                run_device(backend, args, &control)
+            }
+        }
+        #[cfg(feature = "backend-evdev")]
+        Backend::Evdev => run_device(EvdevBackend::open(filename, &output)?, args, &control),
     }
 }
 
-struct Args {
-    sens_mult: f64,
-    accel: f64,
-    cap: f64,
-    offset: f64,
-    filename: OsString,
+// the arguments, less the program and -h
+fn command_line() -> Vec<OsString> {
+    std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "-h")
+        .collect()
 }
 
-fn parse_args(arguments: &mut Arguments) -> Result<Args, pico_args::Error> {
-    Ok(Args {
-        sens_mult: arguments.value_from_fn("-m", str::parse)?,
-        accel: arguments.value_from_fn("-a", str::parse)?,
-        cap: arguments
-            .value_from_fn("-c", str::parse)
-            .unwrap_or(f64::INFINITY),
-        offset: arguments.value_from_fn("-o", str::parse).unwrap_or(0.0),
-        filename: arguments.free_from_str()?,
-    })
+fn start_control(args: &Args) -> Result<Arc<Control>, std::io::Error> {
+    let control = Arc::new(Control::default());
+    control::handle_signals()?;
+    if let Some(ref path) = args.control_fifo {
+        control::listen_fifo(path.clone(), control.clone())?;
+    }
+    Ok(control)
 }
 
-fn main() -> Result<(), std::io::Error> {
-    let mut arguments = Arguments::from_env();
-    let help_message = r#"
-USAGE: accelerator [OPTIONS] <device-file>
-
-OPTIONS:
-  -m SENS_MULTIPLIER    The amount graph of sensitivity is scaled by
-  -a ACCELERATION       Slope of sensitivity graph before scaling
-  -c SENS_CAP           Sets the maximum sensitivity
-                        Default: infinity
-  -o INPUT_OFFSET       Maximum cursor speed before sensitivity
-                        begins increasing
-                        Default: 0"#;
-    if arguments.contains("-h") {
-        println!("{}", help_message);
-    }
-
-    let args = match parse_args(&mut arguments) {
-        Ok(args) => args,
-        Err(err) => {
-            eprintln!("Error: {}\n{}", err, help_message);
+// taken from logind with --logind, if it gives it
+fn open_evdev_rs(
+    args: &Args,
+    filename: &OsString,
+    output: &Output,
+) -> Result<EvdevRsBackend, std::io::Error> {
+    #[cfg(feature = "logind")]
+    if args.logind {
+        let mut opener = LogindOpener::new();
+        let file = open_or_direct(&mut opener, "logind", filename.as_ref())?;
+        let backend = EvdevRsBackend::from_file(file, output, args.batch_writes)?;
+        return Ok(match opener.take_resumed() {
+            Some(resumed) => backend.with_resumed(resumed),
+            None => backend,
+        });
+    }
+    EvdevRsBackend::open(filename, output, args.batch_writes)
+}
+
+// source is the device being read, if there is one
+fn output_for(args: &Args, source: Option<&OsString>) -> Output {
+    match args.no_create_output {
+        // the process reading stdout makes the device
+        _ if args.dry_run || args.emit_raw => Output::Discard,
+        // the grab would send everything written straight back in
+        Some(ref path) if source.is_some_and(|source| same_file(path, source)) => {
+            eprintln!("Error: '--no-create-output' can't be the device being read");
             std::process::exit(1);
         }
+        Some(ref path) => Output::Existing(path.clone()),
+        None => Output::Create {
+            hi_res_scroll: args.smooth_scroll.is_some(),
+        },
+    }
+}
+
+fn same_file(a: impl AsRef<Path>, b: impl AsRef<Path>) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn run_device<B: InputBackend>(
+    backend: B,
+    args: Args,
+    control: &Control,
+) -> Result<(), std::io::Error> {
+    match args.mirror_device {
+        Some(ref name) => {
+            let mirror = Mirror::new(backend, name)?;
+            run_teed(mirror, args, control)
+        }
+        None => run_teed(backend, args, control),
+    }
+}
+
+fn run_teed<B: InputBackend>(
+    backend: B,
+    args: Args,
+    control: &Control,
+) -> Result<(), std::io::Error> {
+    if args.emit_evemu {
+        run_grabbed(EvemuTee::new(backend), args, control)
+    } else if args.emit_raw {
+        run_grabbed(RawTee::new(backend), args, control)
+    } else {
+        run_grabbed(backend, args, control)
+    }
+}
+
+fn run_grabbed<B: InputBackend>(
+    mut backend: B,
+    mut args: Args,
+    control: &Control,
+) -> Result<(), std::io::Error> {
+    // with nothing written anywhere, grabbing would leave the device dead
+    if !args.dry_run {
+        backend.grab()?;
+    }
+    let mut accel = Accelerator::new();
+    let mut taps = Taps::open(&args)?;
+    notify::ready();
+    while let Stop::Reload = run(&mut backend, &args, &mut accel, &mut taps, control)? {
+        // the device and virtual device stay as they are, only the parameters change
+        match parse_args(command_line()) {
+            Ok(reloaded) => {
+                args = reloaded;
+                accel = reloaded_accel(&args, &accel);
+            }
+            Err(err) => eprintln!("Error: reload failed, keeping current parameters: {}", err),
+        }
+    }
+    notify::stopping();
+    eprintln!("{}", taps.stats.summary());
+    tear_down(&mut backend, &args, &mut taps)
+}
+
+// starts the accelerator over for the reloaded parameters, carrying the sub-pixel remainder over if
+// they say to
+fn reloaded_accel(args: &Args, accel: &Accelerator) -> Accelerator {
+    let mut reloaded = Accelerator::new();
+    // without accumulating, a carried remainder would be added to every frame
+    if !args.reset_accum_on_reload && !args.no_accum {
+        let bound = args.accum_bound;
+        reloaded.x_accum = accel.x_accum.clamp(-bound, bound);
+        reloaded.y_accum = accel.y_accum.clamp(-bound, bound);
+    }
+    reloaded
+}
+
+// gives the device back and gets rid of the virtual devices, rather than leaving them for the
+// kernel to clean up once the process is gone
+pub fn tear_down<B: InputBackend>(
+    backend: &mut B,
+    args: &Args,
+    taps: &mut Taps,
+) -> Result<(), std::io::Error> {
+    taps.shutdown();
+    // the virtual device goes even if the device can't be given back
+    let ungrabbed = if args.dry_run {
+        Ok(())
+    } else {
+        backend.ungrab()
+    };
+    backend.destroy();
+    ungrabbed
+}
+
+// a full uinput buffer or a signal, which go away on their own
+fn is_transient(err: &std::io::Error) -> bool {
+    matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::Interrupted)
+}
+
+const WRITE_RETRIES: u32 = 4;
+// doubled after every retry
+const WRITE_BACKOFF: Duration = Duration::from_micros(250);
+// frames in a row that can be dropped before giving up on the virtual device
+const DROPPED_FRAMES_LIMIT: u32 = 50;
+
+fn write_retrying<B: InputBackend>(
+    backend: &mut B,
+    event: &InputEvent,
+) -> Result<(), std::io::Error> {
+    let mut backoff = WRITE_BACKOFF;
+    for _ in 0..WRITE_RETRIES {
+        match backend.write_event(event) {
+            Err(err) if is_transient(&err) => {
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+    backend.write_event(event)
+}
+
+// what the movement on the other --axes comes out as, once accelerated
+fn axes_out(
+    args: &Args,
+    accel: &Accelerator,
+    axes: &[f64],
+    control: &Control,
+) -> Vec<(EV_REL, i32)> {
+    let axes_out: Vec<_> = if control.paused.load(Ordering::Relaxed) || args.no_transform {
+        axes.iter().map(|&value| value as i32).collect()
+    } else {
+        accel.axes_out.clone()
+    };
+    args.extra_axes.iter().copied().zip(axes_out).collect()
+}
+
+// axes is the movement on any other --axes
+fn write_frame<B: InputBackend>(
+    backend: &mut B,
+    time: TimeVal,
+    x: i32,
+    y: i32,
+    axes: &[(EV_REL, i32)],
+    dropped: &mut u32,
+) -> Result<(), std::io::Error> {
+    // frames with nothing to move (heartbeats, or movement all held back in the accumulators)
+    // are just the SYN_REPORT
+    let skip = if x == 0 && y == 0 { 2 } else { 0 };
+    let moved = axes
+        .iter()
+        .filter(|&&(_, value)| value != 0)
+        .map(|&(axis, value)| (EventCode::EV_REL(axis), value));
+    let result = [
+        (EventCode::EV_REL(EV_REL::REL_X), x),
+        (EventCode::EV_REL(EV_REL::REL_Y), y),
+    ]
+    .into_iter()
+    .skip(skip)
+    .chain(moved)
+    .chain([(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)])
+    .try_for_each(|(code, value)| write_retrying(backend, &InputEvent::new(&time, &code, value)));
+    match result {
+        Ok(()) => *dropped = 0,
+        Err(err) if is_transient(&err) && *dropped < DROPPED_FRAMES_LIMIT => {
+            *dropped += 1;
+            eprintln!(
+                "Warning: dropped frame, writing to the virtual device failed: {}",
+                err
+            );
+        }
+        Err(err) => return Err(err),
+    }
+    Ok(())
+}
+
+// writes the smooth scrolling due without ending the frame, false if there was none
+fn write_scroll<B: InputBackend>(
+    backend: &mut B,
+    scroll: &mut SmoothScroll,
+    time: TimeVal,
+) -> Result<bool, std::io::Error> {
+    let steps = scroll.steps(Instant::now());
+    for &(code, step) in &steps {
+        write_retrying(
+            backend,
+            &InputEvent::new(&time, &EventCode::EV_REL(code), step),
+        )?;
+    }
+    Ok(!steps.is_empty())
+}
+
+fn syn_report(time: TimeVal) -> InputEvent {
+    InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)
+}
+
+// updates which of keys are held, false if the event isn't for any of them
+fn track_held<'a>(
+    keys: impl IntoIterator<Item = &'a EventCode>,
+    held: &mut [bool],
+    event: &InputEvent,
+) -> bool {
+    let mut tracked = false;
+    for (key, held) in keys.into_iter().zip(held) {
+        if *key == event.event_code {
+            // repeats (2) keep the key held
+            *held = event.value != 0;
+            tracked = true;
+        }
+    }
+    tracked
+}
+
+// when the frame is processed rather than when the kernel saw it, for timing that can't be stepped
+// by the realtime clock changing
+fn monotonic_now() -> TimeVal {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
     };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    TimeVal::new(now.tv_sec, now.tv_nsec / 1000)
+}
 
-    let file = File::open(args.filename)?;
-    let mut source = Device::new_from_file(file)?;
-    let out = UInputDevice::create_from_device(&source)?;
-    source.grab(GrabMode::Grab)?;
+// what a SIGUSR1 prints: every setting in effect as --print-config prints them, what the last
+// frame was processed with and the stats so far
+fn dump(args: &Args, accel: &Accelerator, taps: &Taps, control: &Control) -> String {
+    format!(
+        "state dump\n{}\npaused {} locked {} profile {} speed {:.3} sens {:.3}{}\n{}",
+        config::resolved(args).trim_end(),
+        control.paused.load(Ordering::Relaxed),
+        control.locked.load(Ordering::Relaxed),
+        accel
+            .profile
+            .map_or("none".to_string(), |profile| profile.to_string()),
+        accel.speed,
+        accel.sensitivity,
+        accel
+            .adapted_offset()
+            .map_or(String::new(), |offset| format!(" offset {:.3}", offset)),
+        taps.stats.summary()
+    )
+}
 
+fn is_button(key: &EV_KEY) -> bool {
+    matches!(
+        key,
+        EV_KEY::BTN_LEFT
+            | EV_KEY::BTN_RIGHT
+            | EV_KEY::BTN_MIDDLE
+            | EV_KEY::BTN_SIDE
+            | EV_KEY::BTN_EXTRA
+            | EV_KEY::BTN_FORWARD
+            | EV_KEY::BTN_BACK
+            | EV_KEY::BTN_TASK
+    )
+}
+
+// how long the mouse has to stop for before what --input-smooth held back is let out
+const SMOOTH_RELEASE: Duration = Duration::from_millis(10);
+
+enum Stop {
+    SourceEnded,
+    Quit,
+    Reload,
+}
+
+fn run<B: InputBackend>(
+    backend: &mut B,
+    args: &Args,
+    accel: &mut Accelerator,
+    taps: &mut Taps,
+    control: &Control,
+) -> Result<Stop, std::io::Error> {
+    let watchdog = notify::watchdog_interval();
+    // without anywhere for requests to come from or a watchdog to ping, there's no reason to
+    // wake up
+    let poll_interval = [
+        args.control_fifo.as_ref().map(|_| args.idle_poll_interval),
+        watchdog,
+    ]
+    .into_iter()
+    .flatten()
+    .min();
+    let mut last_ping = Instant::now();
     let mut x = 0.0;
     let mut y = 0.0;
-    let mut x_accum = 0.0;
-    let mut y_accum = 0.0;
-    let mut frame_last_sec = 0;
-    let mut frame_last_us = 0;
+    let mut axes = vec![0.0; args.extra_axes.len()];
+    // rel events in the frame, including any already flushed
+    let mut rel_events = 0;
+    let mut dropped = 0;
+    let mut panic_held = vec![false; args.panic_keys.len()];
+    let mut modifiers_held = vec![false; args.modifier_map.len()];
+    let mut throttle = args.max_event_rate.map(Throttle::new);
+    // whether anything but movement was written since the last SYN_REPORT, which then can't be
+    // held back
+    let mut passed = false;
+    let mut smooth = args.smooth_scroll.map(SmoothScroll::new);
+    let mut scroll_accel = ScrollAccel::from_args(args);
+    let mut strict = args.strict.then(|| Strict::new(args.strict_flush_after));
+    let mut power = args
+        .power_supply
+        .clone()
+        .map(|path| PowerSupply::new(path, Instant::now()));
+    // when the last event read was sent, for a made up SYN_REPORT to end its frame then
+    let mut last_read = TimeVal::new(0, 0);
+    // when movement was last written, and whether there's movement held back since too soon
+    // after it for the minimum report interval
+    let mut last_report: Option<Instant> = None;
+    let mut held = false;
+    // whether events of a frame have been read without its SYN_REPORT
+    let mut in_frame = false;
+    // when the first movement of the frame was read
+    let mut motion_since: Option<Instant> = None;
+    // the movement of the report being read, and how far the reports coalesced into the frame
+    // moved one after the other
+    let mut segment = (0.0, 0.0);
+    let mut path = 0.0;
+    // when an event was last read, and whether the remainders have been cleared since
+    let mut last_input = Instant::now();
+    let mut drift_cleared = false;
     loop {
-        let event = source.next_event(ReadFlag::NORMAL | ReadFlag::BLOCKING);
-        match event {
-            Ok((status, event)) => {
-                if status == ReadStatus::Sync {
-                    // eat syncs until done (we probably don't need whats in it)
-                    eprintln!("Warning: got SYN_DROPPED");
-                    loop {
-                        let sync_event = source.next_event(ReadFlag::SYNC | ReadFlag::BLOCKING);
-                        if sync_event.is_err() {
-                            break;
-                        }
+        if watchdog.is_some_and(|interval| last_ping.elapsed() >= interval) {
+            notify::watchdog();
+            last_ping = Instant::now();
+        }
+        if let Some((old, new)) = power.as_mut().and_then(|power| power.poll(Instant::now())) {
+            eprintln!(
+                "Warning: the mouse's power supply went from {} to {}, acceleration may feel \
+                 different if the mouse changes its report rate with it",
+                old.as_deref().unwrap_or("unknown"),
+                new
+            );
+        }
+        if control.take_dump() {
+            eprintln!("{}", dump(args, accel, taps, control));
+        }
+        if control.quitting() {
+            // the frame the signal cut short still gets written, so its movement isn't lost
+            if x != 0.0 || y != 0.0 || passed {
+                let (x_out, y_out) = if control.paused.load(Ordering::Relaxed) || args.no_transform
+                {
+                    (x as i32, y as i32)
+                } else {
+                    accel.process_partial(args, x, y)
+                };
+                write_frame(backend, last_read, x_out, y_out, &[], &mut dropped)?;
+            }
+            return Ok(Stop::Quit);
+        }
+        if control.take_reload() {
+            return Ok(Stop::Reload);
+        }
+        accel.locked = control.locked.load(Ordering::Relaxed);
+        // scrolling due in the middle of a frame goes out with the frame's SYN_REPORT instead
+        let between_frames = rel_events == 0 && !passed;
+        let mut scroll_due = None;
+        if let Some(scroll) = smooth.as_mut().filter(|_| between_frames) {
+            if write_scroll(backend, scroll, monotonic_now())? {
+                write_retrying(backend, &syn_report(monotonic_now()))?;
+            }
+            scroll_due = scroll.due_in(Instant::now());
+        }
+        let mut flush = false;
+        let mut stuck_due = None;
+        if let Some(ref mut strict) = strict {
+            if let Some(anomaly) = strict.check(Instant::now()) {
+                eprintln!("Warning: {}", anomaly);
+                flush = matches!(anomaly, Anomaly::StuckMotion(_, true));
+            }
+            stuck_due = strict.due_in(Instant::now());
+        }
+        let since_report = last_report.map_or(Duration::MAX, |last| last.elapsed());
+        // a frame that's started gets the held movement written with it instead
+        let report_due = args
+            .min_report_interval
+            .filter(|_| held && !in_frame)
+            .map(|interval| interval.saturating_sub(since_report));
+        // the same for movement --input-smooth or --output-average is holding back, which an empty
+        // frame lets out
+        let smooth_due =
+            (accel.smoothing() && !in_frame && !control.paused.load(Ordering::Relaxed))
+                .then(|| SMOOTH_RELEASE.saturating_sub(since_report));
+        let heartbeat_due = taps.heartbeat(Instant::now());
+        let mut drift_due = args
+            .anti_drift
+            .filter(|_| !drift_cleared)
+            .map(|idle| idle.saturating_sub(last_input.elapsed()));
+        if drift_due.is_some_and(|due| due.is_zero()) {
+            // rounding leaves at most half a count, or a whole one nudging it with min-move
+            let rounding = if args.min_move { 1.0 } else { 0.5 };
+            let largest = accel.clear_remainders();
+            if largest > rounding {
+                eprintln!(
+                    "Warning: cleared a remainder of {} counts after going idle, something built \
+                     up a bias",
+                    largest
+                );
+            }
+            drift_cleared = true;
+            drift_due = None;
+        }
+        // movement the device is never going to end the frame of
+        let syn_due = args
+            .syn_timeout
+            .zip(motion_since)
+            .map(|(timeout, since)| timeout.saturating_sub(since.elapsed()));
+        // the mouse stopped, so there's no later frame to write the movement with
+        if report_due
+            .into_iter()
+            .chain(smooth_due)
+            .chain(syn_due)
+            .any(|due| due.is_zero())
+        {
+            flush = true;
+        }
+        let timeout = [
+            poll_interval,
+            scroll_due,
+            stuck_due,
+            report_due,
+            smooth_due,
+            syn_due,
+            heartbeat_due,
+            drift_due,
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        if let (false, Some(timeout)) = (flush, timeout) {
+            if !backend.wait(timeout)? {
+                continue;
+            }
+        }
+
+        let event = if flush {
+            syn_report(last_read)
+        } else {
+            match backend.next_event() {
+                Ok(Some(event)) => event,
+                Ok(None) => return Ok(Stop::SourceEnded),
+                // a signal, which is checked for at the top
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                // should never be err? it is blocking
+                Err(_) => {
+                    eprintln!("Error: got back Err from next_event (has the device been closed?)");
+                    std::process::exit(2);
+                }
+            }
+        };
+        if !flush {
+            taps.event(&event);
+            last_read = event.time;
+            last_input = Instant::now();
+            drift_cleared = false;
+        }
+        in_frame = event.event_code != EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+        match event.event_code {
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                motion_since = None;
+                let (segment_x, segment_y): (f64, f64) = std::mem::take(&mut segment);
+                path += segment_x.hypot(segment_y);
+            }
+            EventCode::EV_REL(EV_REL::REL_X | EV_REL::REL_Y) => {
+                motion_since.get_or_insert_with(Instant::now);
+            }
+            EventCode::EV_REL(axis) if args.extra_axes.contains(&axis) => {
+                motion_since.get_or_insert_with(Instant::now);
+            }
+            _ => {}
+        }
+        if let Some(ref mut strict) = strict {
+            if let Some(anomaly) = strict.event(&event, Instant::now()) {
+                eprintln!("Warning: {}", anomaly);
+            }
+        }
+        let event = match scroll_accel {
+            Some(ref mut scroll_accel) => match scroll_accel.event(event) {
+                Some(event) => event,
+                None => continue,
+            },
+            None => event,
+        };
+        if let Some(ref mut scroll) = smooth {
+            scroll.event(&event, Instant::now());
+        }
+        let throttled = throttle.as_mut().is_some_and(Throttle::over);
+        let too_soon = args
+            .min_report_interval
+            .is_some_and(|interval| last_report.is_some_and(|last| last.elapsed() < interval));
+        if track_held(&args.panic_keys, &mut panic_held, &event)
+            && panic_held.iter().all(|&held| held)
+        {
+            eprintln!("Warning: panic keys pressed, exiting");
+            return Ok(Stop::Quit);
+        }
+        // the keys still go through like any other
+        let modifier_keys = args.modifier_map.iter().map(|(key, _)| key);
+        if track_held(modifier_keys, &mut modifiers_held, &event) {
+            accel.profile = modifiers_held.iter().position(|&held| held);
+        }
+        if let (Some(budget), EventCode::EV_KEY(key)) = (args.click_stabilize, &event.event_code) {
+            // a release of any button ends it, autorepeat leaves it be
+            if is_button(key) && event.value != 2 {
+                accel.click_budget = (event.value == 1).then_some(budget);
+            }
+        }
+        match event.event_code {
+            EventCode::EV_REL(EV_REL::REL_X) => {
+                x += event.value as f64;
+                segment.0 += event.value as f64;
+                rel_events += 1;
+            }
+            EventCode::EV_REL(EV_REL::REL_Y) => {
+                y += event.value as f64;
+                segment.1 += event.value as f64;
+                rel_events += 1;
+            }
+            EventCode::EV_REL(axis) if args.extra_axes.contains(&axis) => {
+                let i = args.extra_axes.iter().position(|&extra| extra == axis);
+                axes[i.unwrap()] += event.value as f64;
+                rel_events += 1;
+            }
+            EventCode::EV_SYN(EV_SYN::SYN_DROPPED) => eprintln!("Warning: got SYN_DROPPED"),
+            // the movement is carried into the next frame written instead, which makes up for
+            // it with a longer frame time
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) if throttled && !passed => {}
+            // the same, but written once the interval's up even if nothing else comes
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT)
+                if too_soon && !passed && (x != 0.0 || y != 0.0) =>
+            {
+                held = true;
+            }
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                let write_calls = backend.write_calls();
+                accel.axes_in.clone_from(&axes);
+                let (x_out, y_out) = if control.paused.load(Ordering::Relaxed) {
+                    (x as i32, y as i32)
+                } else {
+                    let frame_time = if args.monotonic_time {
+                        monotonic_now()
+                    } else {
+                        event.time
+                    };
+                    let out = accel.process_frame(args, x, y, rel_events, Some(path), frame_time);
+                    // still worked out for the multiplier
+                    if args.no_transform {
+                        (x as i32, y as i32)
+                    } else {
+                        out
                     }
-                    continue;
+                };
+
+                let axes_out = axes_out(args, accel, &axes, control);
+                if let Some(ref mut scroll) = smooth {
+                    write_scroll(backend, scroll, event.time)?;
+                }
+                write_frame(backend, event.time, x_out, y_out, &axes_out, &mut dropped)?;
+                if x != 0.0 || y != 0.0 {
+                    last_report = Some(Instant::now());
                 }
+                held = false;
 
-                match event.event_code {
-                    EventCode::EV_REL(EV_REL::REL_X) => x = event.value as f64,
-                    EventCode::EV_REL(EV_REL::REL_Y) => y = event.value as f64,
-                    EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
-                        let change_ms = (event.time.tv_sec as f64 - frame_last_sec as f64) * 1000.0
-                            + (event.time.tv_usec as f64 - frame_last_us as f64) / 1000.0;
-                        let dist = (x * x + y * y).sqrt();
-                        let sensitivity = factor(
-                            args.sens_mult,
-                            args.accel,
-                            args.cap,
-                            args.offset,
-                            dist / change_ms as f64,
-                        );
-                        x = x.mul_add(sensitivity, x_accum);
-                        y = y.mul_add(sensitivity, y_accum);
-
-                        let x_rounded = x.round() as i32;
-                        let y_rounded = y.round() as i32;
-                        x_accum = x - x_rounded as f64;
-                        y_accum = y - y_rounded as f64;
-
-                        out.write_event(&InputEvent {
-                            time: event.time,
-                            event_code: EventCode::EV_REL(EV_REL::REL_X),
-                            value: x_rounded,
-                        })?;
-                        out.write_event(&InputEvent {
-                            time: event.time,
-                            event_code: EventCode::EV_REL(EV_REL::REL_Y),
-                            value: y_rounded,
-                        })?;
-
-                        out.write_event(&event)?;
-
-                        x = 0.0;
-                        y = 0.0;
-                        frame_last_sec = event.time.tv_sec;
-                        frame_last_us = event.time.tv_usec;
+                taps.frame(accel, x, y, x_out, y_out);
+                if args.debug && args.debug_speeds.contains(&accel.speed) {
+                    let mut line = format!(
+                        "speed {:.3} sens {:.3} in {} {} out {} {} writes {}",
+                        accel.speed,
+                        accel.sensitivity,
+                        x,
+                        y,
+                        x_out,
+                        y_out,
+                        backend.write_calls() - write_calls
+                    );
+                    if let Some(queued) = backend.queued() {
+                        line += &format!(" queued {}us", queued.as_micros());
                     }
-                    _ => out.write_event(&event)?,
+                    taps.debug(&line);
                 }
+
+                x = 0.0;
+                y = 0.0;
+                axes.fill(0.0);
+                rel_events = 0;
+                path = 0.0;
+                passed = false;
+            }
+            // get the pointer to where it should be before the click lands
+            EventCode::EV_KEY(_)
+                if args.flush_on_click
+                    && event.value == 1
+                    && (x != 0.0 || y != 0.0 || axes.iter().any(|&value| value != 0.0)) =>
+            {
+                accel.axes_in.clone_from(&axes);
+                let (x_out, y_out) = if control.paused.load(Ordering::Relaxed) || args.no_transform
+                {
+                    (x as i32, y as i32)
+                } else {
+                    accel.process_partial(args, x, y)
+                };
+                let axes_out = axes_out(args, accel, &axes, control);
+                write_frame(backend, event.time, x_out, y_out, &axes_out, &mut dropped)?;
+                write_retrying(backend, &event)?;
+                x = 0.0;
+                y = 0.0;
+                axes.fill(0.0);
+                passed = true;
             }
-            // should never be err? it is blocking
-            Err(_) => {
-                eprintln!("Error: got back Err from next_event (has the device been closed?)");
-                std::process::exit(2);
+            // replaced by the spread out scrolling
+            EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES | EV_REL::REL_HWHEEL_HI_RES)
+                if smooth.is_some() => {}
+            // SYN_MT_REPORT and SYN_CONFIG mean nothing to a mouse
+            EventCode::EV_SYN(_) if !args.forward_all_syn => {}
+            _ => {
+                write_retrying(backend, &event)?;
+                passed = true;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::ErrorKind,
+        sync::atomic::Ordering,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use evdev_rs::{
+        enums::{EventCode, EV_KEY, EV_REL, EV_SYN},
+        InputEvent, TimeVal,
+    };
+
+    use super::{reloaded_accel, Stop, DROPPED_FRAMES_LIMIT, WRITE_RETRIES};
+    use crate::{
+        accel::Accelerator,
+        backend::{
+            replay::{RealClock, ReplayBackend},
+            InputBackend, MockBackend,
+        },
+        control::Control,
+        taps::Taps,
+        testing::{
+            debug_lines, event, frames, mock_args, moved, run_backend, run_controlled, run_mock,
+            run_replay, smoothed_frames,
+        },
+    };
+
+    // a sensor jittering back and forth comes out shaking less than half as far, movement keeping
+    // up comes out at full size once the smoothing catches up, and what's held back when the mouse
+    // stops still gets written
+    #[test]
+    fn input_smoothing() -> Result<(), String> {
+        let options = "-m 1 -a 0 --input-smooth 0.25";
+        let jitter: Vec<_> = (0..40).map(|i| if i % 2 == 0 { 3 } else { -3 }).collect();
+        let smoothed = smoothed_frames(options, &jitter)?;
+        let shaking: i32 = smoothed[20..].iter().map(|x| x.abs()).sum();
+        if shaking * 2 >= 3 * 20 {
+            return Err(format!("jitter came out as {:?}", &smoothed[20..]));
+        }
+        let sustained = smoothed_frames(options, &[4; 40])?;
+        if sustained[30..].iter().any(|&x| x != 4) {
+            return Err(format!(
+                "sustained movement came out as {:?}",
+                &sustained[30..]
+            ));
+        }
+
+        let mut moves: Vec<(i64, &[(EV_REL, i32)])> =
+            (1..=20).map(|ms| (ms, &[(EV_REL::REL_X, 4)][..])).collect();
+        // so the replay doesn't end before the held back movement is due
+        moves.push((60, &[]));
+        let written = run_replay(options, frames(&moves))?;
+        let total: i32 = written
+            .iter()
+            .filter(|(_, event)| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+            .map(|(_, event)| event.value)
+            .sum();
+        if total != 80 {
+            return Err(format!("80 counts in, {} out", total));
+        }
+        Ok(())
+    }
+
+    // movement every 2ms without any SYN_REPORTs comes out in frames ended every 5ms or so, and all
+    // of it gets written
+    #[test]
+    fn syn_timeout() -> Result<(), String> {
+        let mut events: Vec<_> = (1..=20)
+            .map(|i| {
+                let time = TimeVal::new(100, i * 2000);
+                InputEvent::new(&time, &EventCode::EV_REL(EV_REL::REL_X), 1)
+            })
+            .collect();
+        // so the replay doesn't end before the last of it is due
+        events.push(InputEvent::new(
+            &TimeVal::new(100, 80_000),
+            &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            0,
+        ));
+        let written = run_replay("-m 1 -a 0 --syn-timeout 5", events)?;
+        let total: i32 = written
+            .iter()
+            .filter(|(_, event)| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+            .map(|(_, event)| event.value)
+            .sum();
+        if total != 20 {
+            return Err(format!("20 counts in, {} out", total));
+        }
+        let syns: Vec<_> = written
+            .iter()
+            .filter(|(_, event)| event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT))
+            .map(|&(at, _)| at)
+            .collect();
+        // the last is the recording's own
+        let flushes = &syns[..syns.len().saturating_sub(1)];
+        if flushes.len() < 5 {
+            return Err(format!("expected a frame every 5ms, got {}", flushes.len()));
+        }
+        for pair in flushes.windows(2) {
+            let gap = pair[1] - pair[0];
+            if !(Duration::from_micros(4500)..Duration::from_millis(20)).contains(&gap) {
+                return Err(format!("frames {}us apart", gap.as_micros()));
+            }
+        }
+        Ok(())
+    }
+
+    // reports going right then up, coalesced by --min-report-interval into one frame 2ms after the
+    // one before: 20 counts along the way is 10 counts per ms for 1 + 0.1 * 10 = 2, but the frame
+    // only ends up 14.1 away, 7.07 counts per ms for 1.707
+    fn coalesced_curve(path_length: bool) -> Result<(i32, i32), String> {
+        let events = frames(&[
+            (1, &[(EV_REL::REL_X, 1)]),
+            (2, &[(EV_REL::REL_X, 10)]),
+            (3, &[(EV_REL::REL_Y, 10)]),
+            // so the replay doesn't end before the held back movement is due
+            (100, &[]),
+        ]);
+        let mut options = "-m 1 -a 0.1 --min-report-interval 50".to_string();
+        if path_length {
+            options.push_str(" --path-length-speed");
+        }
+        let written = run_replay(&options, events)?;
+        let written: Vec<_> = written
+            .into_iter()
+            .map(|(_, event)| (event.event_code, event.value))
+            .collect();
+        // the first frame's movement, then the coalesced frame's
+        match (
+            &moved(&written, EV_REL::REL_X)[..],
+            &moved(&written, EV_REL::REL_Y)[..],
+        ) {
+            ([1, x], [0, y]) => Ok((*x, *y)),
+            (x, y) => Err(format!("expected two frames, got {:?} and {:?}", x, y)),
+        }
+    }
+
+    #[test]
+    fn path_length_speed() -> Result<(), String> {
+        let net = coalesced_curve(false)?;
+        if net != (17, 17) {
+            return Err(format!("by net movement expected (17, 17), got {:?}", net));
+        }
+        let path = coalesced_curve(true)?;
+        if path != (20, 20) {
+            return Err(format!("by path length expected (20, 20), got {:?}", path));
+        }
+        Ok(())
+    }
+
+    // a mock device interrupted by a signal to quit once it's out of events
+    struct Quitting<'a> {
+        inner: MockBackend,
+        control: &'a Control,
+    }
+
+    impl InputBackend for Quitting<'_> {
+        fn next_event(&mut self) -> std::io::Result<Option<InputEvent>> {
+            match self.inner.next_event()? {
+                Some(event) => Ok(Some(event)),
+                None => {
+                    self.control.quit.store(true, Ordering::Relaxed);
+                    Err(ErrorKind::Interrupted.into())
+                }
+            }
+        }
+
+        fn write_event(&mut self, event: &InputEvent) -> std::io::Result<()> {
+            self.inner.write_event(event)
+        }
+
+        fn grab(&mut self) -> std::io::Result<()> {
+            self.inner.grab()
+        }
+
+        fn ungrab(&mut self) -> std::io::Result<()> {
+            self.inner.ungrab()
+        }
+    }
+
+    // panic keys pressed one after the other are forwarded like any keys, but held together they
+    // stop the loop before the last of them or anything after goes through
+    #[test]
+    fn panic_keys() -> Result<(), String> {
+        let (a, b) = (
+            EventCode::EV_KEY(EV_KEY::KEY_A),
+            EventCode::EV_KEY(EV_KEY::KEY_B),
+        );
+        let run = |events: Vec<(EventCode, i32)>| {
+            let events = events.into_iter().map(|(code, value)| event(code, value));
+            let options = "-m 1 -a 0 --panic-keys KEY_A+KEY_B";
+            run_controlled(options, events.collect(), &Control::default())
+        };
+        let apart = vec![(a, 1), (a, 0), (b, 1), (b, 0)];
+        match run(apart.clone())? {
+            (Stop::SourceEnded, written) if written == apart => {}
+            (_, written) => return Err(format!("pressed apart, wrote {:?}", written)),
+        }
+        let x = EventCode::EV_REL(EV_REL::REL_X);
+        let syn = EventCode::EV_SYN(EV_SYN::SYN_REPORT);
+        match run(vec![(a, 1), (b, 1), (x, 3), (syn, 0)])? {
+            (Stop::Quit, written) if written == [(a, 1)] => Ok(()),
+            (_, written) => Err(format!("pressed together, wrote {:?}", written)),
+        }
+    }
+
+    // movement still waiting on its SYN_REPORT when told to quit is written, accelerated, with one
+    // made up to end it
+    #[test]
+    fn shutdown_flush() -> Result<(), String> {
+        let control = Control::default();
+        let mut backend = Quitting {
+            inner: MockBackend::new([
+                event(EventCode::EV_REL(EV_REL::REL_X), 5),
+                event(EventCode::EV_REL(EV_REL::REL_Y), 3),
+            ]),
+            control: &control,
+        };
+        run_backend(&mut backend, &mock_args("-m 2 -a 0")?, &control)?;
+        let written: Vec<_> = backend
+            .inner
+            .written
+            .iter()
+            .map(|event| (event.event_code, event.value))
+            .collect();
+        let expected = [
+            (EventCode::EV_REL(EV_REL::REL_X), 10),
+            (EventCode::EV_REL(EV_REL::REL_Y), 6),
+            (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+        ];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        Ok(())
+    }
+
+    // 20 frames a millisecond apart come out at most every 5ms, all of the movement still gets
+    // written once the mouse stops, and the click after goes through on its own
+    #[test]
+    fn min_report_interval() -> Result<(), String> {
+        let moves: Vec<(i64, &[(EV_REL, i32)])> =
+            (1..=20).map(|ms| (ms, &[(EV_REL::REL_X, 1)][..])).collect();
+        let mut events = frames(&moves);
+        let mut click = frames(&[(60, &[])]);
+        let pressed = EventCode::EV_KEY(EV_KEY::BTN_LEFT);
+        click.insert(0, InputEvent::new(&click[0].time, &pressed, 1));
+        events.extend(click);
+
+        let written = run_replay("-m 1 -a 0 --min-report-interval 5", events)?;
+        let moves: Vec<_> = written
+            .iter()
+            .filter(|(_, event)| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+            .collect();
+        let total: i32 = moves.iter().map(|(_, event)| event.value).sum();
+        if total != 20 {
+            return Err(format!("moved {} of 20", total));
+        }
+        // a little short of 5ms between frames written is only as late as one was read
+        for pair in moves.windows(2) {
+            let gap = pair[1].0 - pair[0].0;
+            if gap < Duration::from_millis(4) {
+                return Err(format!("wrote movement {:?} apart", gap));
+            }
+        }
+        match written.last() {
+            Some((_, event)) if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {}
+            other => return Err(format!("didn't end with the click's frame: {:?}", other)),
+        }
+        let clicked = written.iter().rev().nth(1);
+        if !clicked.is_some_and(|(_, event)| event.event_code == pressed) {
+            return Err("the click was held back with movement".to_string());
+        }
+        Ok(())
+    }
+
+    // a 3D mouse moving in X and Z: the first frame has nothing before it to give a speed, the
+    // second is 5 counts per ms across both and gets 1 + 0.2 * 5 on each, and the third, only in Z,
+    // is 2 per ms for 1.4, leaving 2.8 to round
+    #[test]
+    fn extra_axes() -> Result<(), String> {
+        let events = frames(&[
+            (1, &[(EV_REL::REL_X, 3), (EV_REL::REL_Z, 4)]),
+            (2, &[(EV_REL::REL_X, 3), (EV_REL::REL_Z, 4)]),
+            (3, &[(EV_REL::REL_Z, 2)]),
+        ]);
+        let written = run_mock("-m 1 -a 0.2 --axes REL_X,REL_Y,REL_Z", events)?;
+        let (x, z) = (
+            moved(&written, EV_REL::REL_X),
+            moved(&written, EV_REL::REL_Z),
+        );
+        if x != [3, 6] || z != [4, 8, 3] {
+            return Err(format!(
+                "expected X [3, 6] and Z [4, 8, 3], got {:?} and {:?}",
+                x, z
+            ));
+        }
+        Ok(())
+    }
+
+    // a click partway through a frame first writes what's moved so far on every axis, at the last
+    // frame's sensitivity (1, with nothing before it), and the rest of the frame still has its
+    // speed counted from all of it: sqrt(5^2 + 4^2) counts in 1ms gets 1 + 0.2 * 6.4 for the 2
+    #[test]
+    fn flush_on_click() -> Result<(), String> {
+        let mut events = frames(&[
+            (1, &[(EV_REL::REL_X, 3), (EV_REL::REL_Z, 4)]),
+            (
+                2,
+                &[(EV_REL::REL_X, 3), (EV_REL::REL_Z, 4), (EV_REL::REL_X, 2)],
+            ),
+        ]);
+        let click = InputEvent::new(&events[5].time, &EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1);
+        events.insert(5, click);
+        let options = "-m 1 -a 0.2 --axes REL_X,REL_Y,REL_Z --flush-on-click";
+        let written = run_mock(options, events)?;
+        let (x, y, z, syn) = (
+            EventCode::EV_REL(EV_REL::REL_X),
+            EventCode::EV_REL(EV_REL::REL_Y),
+            EventCode::EV_REL(EV_REL::REL_Z),
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        );
+        let expected = [
+            (x, 3),
+            (y, 0),
+            (z, 4),
+            (syn, 0),
+            (x, 3),
+            (y, 0),
+            (z, 4),
+            (syn, 0),
+            (EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1),
+            (x, 5),
+            (y, 0),
+            (syn, 0),
+        ];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        Ok(())
+    }
+
+    // paused from the control fifo, movement goes through as it came whatever the curve
+    #[test]
+    fn paused() -> Result<(), String> {
+        let events = frames(&[
+            (1, &[(EV_REL::REL_X, 3), (EV_REL::REL_Y, 4)]),
+            (2, &[(EV_REL::REL_X, 6), (EV_REL::REL_Y, 8)]),
+        ]);
+        let control = Control::default();
+        control.paused.store(true, Ordering::Relaxed);
+        let (_, written) = run_controlled("-m 2 -a 0.1", events, &control)?;
+        let moved: Vec<_> = written
+            .iter()
+            .filter(|(code, _)| matches!(code, EventCode::EV_REL(_)))
+            .map(|&(_, value)| value)
+            .collect();
+        if moved != [3, 4, 6, 8] {
+            return Err(format!("expected [3, 4, 6, 8] paused, got {:?}", moved));
+        }
+        Ok(())
+    }
+
+    // locked, every speed gets the lock multiplier rather than the curve's, and unlocked again the
+    // curve's back
+    #[test]
+    fn locked() -> Result<(), String> {
+        let events = || {
+            frames(&[
+                (1, &[(EV_REL::REL_X, 1)]),
+                (2, &[(EV_REL::REL_X, 10)]),
+                (3, &[(EV_REL::REL_X, 40)]),
+            ])
+        };
+        let run = |locked: bool| {
+            let control = Control::default();
+            control.locked.store(locked, Ordering::Relaxed);
+            let (_, written) = run_controlled("-m 1 -a 0.5 --lock-mult 2", events(), &control)?;
+            Ok::<_, String>(moved(&written, EV_REL::REL_X))
+        };
+        let locked = run(true)?;
+        if locked != [2, 20, 80] {
+            return Err(format!("expected [2, 20, 80] locked, got {:?}", locked));
+        }
+        // 1 + 0.5 * 10 and 1 + 0.5 * 40
+        let unlocked = run(false)?;
+        if unlocked != [1, 60, 840] {
+            return Err(format!(
+                "expected [1, 60, 840] unlocked, got {:?}",
+                unlocked
+            ));
+        }
+        Ok(())
+    }
+
+    // the event clock stepping an hour forward and then back makes the frames after look idle, while
+    // timed by the monotonic clock they're still a burst well past the cap; a ms apart is 1 + 0.1 *
+    // 100 capped at 2
+    #[test]
+    fn monotonic_time() -> Result<(), String> {
+        let events = || {
+            frames(&[
+                (1, &[(EV_REL::REL_X, 100)]),
+                (2, &[(EV_REL::REL_X, 100)]),
+                (3_600_002, &[(EV_REL::REL_X, 100)]),
+                (3, &[(EV_REL::REL_X, 100)]),
+            ])
+        };
+        let cases = [
+            ("-m 1 -a 0.1 -c 2", [100, 200, 100, 100]),
+            ("-m 1 -a 0.1 -c 2 --monotonic-time", [100, 200, 200, 200]),
+        ];
+        for (options, expected) in cases {
+            let moved = moved(&run_mock(options, events())?, EV_REL::REL_X);
+            if moved != expected {
+                return Err(format!(
+                    "{}: expected {:?}, got {:?}",
+                    options, expected, moved
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // at 0.2x a count a frame rounds to nothing for two frames; reloading then carries the 0.4 over
+    // so the next frame moves, unless the remainder's dropped or not accumulated at all
+    #[test]
+    fn reload_accum() -> Result<(), String> {
+        let cases = [
+            ("", 0.4, 1),
+            ("--reset-accum-on-reload", 0.0, 0),
+            ("--no-accum", 0.0, 0),
+        ];
+        for (options, carried, moved) in cases {
+            let args = mock_args("-m 0.2 -a 0")?;
+            let mut accel = Accelerator::new();
+            for ms in [1, 2] {
+                let time = TimeVal::new(100, ms * 1000);
+                if accel.process_frame(&args, 1.0, 0.0, 1, None, time) != (0, 0) {
+                    return Err(format!("{}: moved before the reload", options));
+                }
+            }
+            let args = mock_args(&format!("-m 0.2 -a 0 {}", options))?;
+            let mut accel = reloaded_accel(&args, &accel);
+            if (accel.x_accum - carried).abs() > 1e-9 {
+                return Err(format!(
+                    "{}: expected {} carried, got {}",
+                    options, carried, accel.x_accum
+                ));
+            }
+            let (x, _) = accel.process_frame(&args, 1.0, 0.0, 1, None, TimeVal::new(100, 3000));
+            if x != moved {
+                return Err(format!("{}: expected {}, got {}", options, moved, x));
+            }
+        }
+        Ok(())
+    }
+
+    // a thousand frames flooding in at once past 100 events/s are written as the 50 frames the
+    // budget covers and the rest held back, which the click then flushes all together
+    #[test]
+    fn max_event_rate() -> Result<(), String> {
+        let moves: Vec<(i64, &[(EV_REL, i32)])> = (1..=1000)
+            .map(|ms| (ms, &[(EV_REL::REL_X, 1)][..]))
+            .collect();
+        let events = || {
+            let mut events = frames(&moves);
+            events.push(event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1));
+            events
+        };
+        let run = |options| Ok::<_, String>(moved(&run_mock(options, events())?, EV_REL::REL_X));
+        let unlimited = run("-m 1 -a 0 --flush-on-click")?;
+        if unlimited.len() != 1000 {
+            return Err(format!(
+                "expected 1000 frames unlimited, got {}",
+                unlimited.len()
+            ));
+        }
+        let limited = run("-m 1 -a 0 --flush-on-click --max-event-rate 100")?;
+        let mut expected = vec![1; 50];
+        expected.push(950);
+        if limited != expected {
+            return Err(format!(
+                "expected {:?} limited, got {:?}",
+                expected, limited
+            ));
+        }
+        Ok(())
+    }
+
+    // SYN_MT_REPORT and SYN_CONFIG within a frame are dropped, and passed on as they come with
+    // --forward-all-syn, ahead of the movement written at the SYN_REPORT; the zero REL_Y written
+    // with it is left out since the kernel drops it anyway
+    #[test]
+    fn forward_all_syn() -> Result<(), String> {
+        let events = || {
+            vec![
+                event(EventCode::EV_REL(EV_REL::REL_X), 1),
+                event(EventCode::EV_SYN(EV_SYN::SYN_MT_REPORT), 0),
+                event(EventCode::EV_SYN(EV_SYN::SYN_CONFIG), 0),
+                event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+            ]
+        };
+        let filtered = |options| {
+            let written = run_mock(options, events())?;
+            let moved = written
+                .into_iter()
+                .filter(|&(code, value)| !(matches!(code, EventCode::EV_REL(_)) && value == 0));
+            Ok::<_, String>(moved.collect::<Vec<_>>())
+        };
+        let frame = [
+            (EventCode::EV_REL(EV_REL::REL_X), 1),
+            (EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0),
+        ];
+        let written = filtered("-m 1 -a 0")?;
+        if written != frame {
+            return Err(format!("expected {:?}, got {:?}", frame, written));
+        }
+        let mut forwarded = vec![
+            (EventCode::EV_SYN(EV_SYN::SYN_MT_REPORT), 0),
+            (EventCode::EV_SYN(EV_SYN::SYN_CONFIG), 0),
+        ];
+        forwarded.extend(frame);
+        let written = filtered("-m 1 -a 0 --forward-all-syn")?;
+        if written != forwarded {
+            return Err(format!("expected {:?}, got {:?}", forwarded, written));
+        }
+        Ok(())
+    }
+
+    // 10 counts a ms get 1 + 0.1 * 10, except for frames starting within 25 counts of the button
+    // going down, which are only halved; the release ends it, and the next press starts it again
+    #[test]
+    fn click_stabilize() -> Result<(), String> {
+        let events = || {
+            let moved: &[(EV_REL, i32)] = &[(EV_REL::REL_X, 10)];
+            let mut events = frames(&[(0, &[]), (1, moved)]);
+            events.push(event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1));
+            events.extend(frames(&[(2, moved), (3, moved), (4, moved), (5, moved)]));
+            events.push(event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 0));
+            events.push(event(EventCode::EV_KEY(EV_KEY::BTN_LEFT), 1));
+            events.extend(frames(&[(6, moved)]));
+            events
+        };
+        let cases = [
+            ("-m 1 -a 0.1", [20, 20, 20, 20, 20, 20]),
+            ("-m 1 -a 0.1 --click-stabilize 25", [20, 5, 5, 5, 20, 5]),
+        ];
+        for (options, expected) in cases {
+            let moved = moved(&run_mock(options, events())?, EV_REL::REL_X);
+            if moved != expected {
+                return Err(format!(
+                    "{}: expected {:?}, got {:?}",
+                    options, expected, moved
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // with --debug only frames between the speeds given are printed, here 10 counts a ms out of 1,
+    // 10 and 40
+    #[test]
+    fn debug_speeds() -> Result<(), String> {
+        let events = frames(&[
+            (0, &[]),
+            (1, &[(EV_REL::REL_X, 1)]),
+            (2, &[(EV_REL::REL_X, 10)]),
+            (3, &[(EV_REL::REL_X, 40)]),
+        ]);
+        let options = "-m 1 -a 0 --debug --debug-speed-min 5 --debug-speed-max 20";
+        let lines = debug_lines(options, events)?;
+        let expected = ["speed 10.000 sens 1.000 in 10 0 out 10 0 writes 0"];
+        if lines != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, lines));
+        }
+        Ok(())
+    }
+
+    // a device sending nothing until it goes away after 500ms
+    struct Idle {
+        until: Instant,
+        waits: u32,
+    }
+
+    impl InputBackend for Idle {
+        fn next_event(&mut self) -> std::io::Result<Option<InputEvent>> {
+            thread::sleep(self.until.saturating_duration_since(Instant::now()));
+            Ok(None)
+        }
+
+        fn wait(&mut self, timeout: Duration) -> std::io::Result<bool> {
+            self.waits += 1;
+            let left = self.until.saturating_duration_since(Instant::now());
+            thread::sleep(timeout.min(left));
+            Ok(left <= timeout)
+        }
+
+        fn write_event(&mut self, _event: &InputEvent) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn grab(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+
+        fn ungrab(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // idle with a control fifo, the loop only wakes up once every idle poll interval rather than
+    // spinning, and without anything to check it doesn't wake up at all
+    #[test]
+    fn idle_poll_interval() -> Result<(), String> {
+        let cases = [
+            (
+                "--control-fifo /nonexistent --idle-poll-interval 50",
+                8..=11,
+            ),
+            ("--control-fifo /nonexistent", 4..=6),
+            ("", 0..=0),
+        ];
+        for (options, expected) in cases {
+            let args = mock_args(&format!("-m 1 -a 0 {}", options))?;
+            let mut backend = Idle {
+                until: Instant::now() + Duration::from_millis(500),
+                waits: 0,
+            };
+            run_backend(&mut backend, &args, &Control::default())?;
+            if !expected.contains(&backend.waits) {
+                return Err(format!(
+                    "{}: expected {:?} waits, got {}",
+                    options, expected, backend.waits
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // a count a frame gets 2x with shift held and 3x with ctrl, and with both held shift's still
+    // wins for being given first until it's let go; the keys are all forwarded
+    #[test]
+    fn modifier_map() -> Result<(), String> {
+        let dir =
+            std::env::temp_dir().join(format!("accelerator-modifiers-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let result = (|| {
+            let mut options = "-m 1 -a 0".to_string();
+            for (key, mult) in [("KEY_LEFTSHIFT", 2), ("KEY_LEFTCTRL", 3)] {
+                let path = dir.join(format!("{}.json", key));
+                let curve = format!(
+                    r#"{{"version": 1, "sens_mult": {}, "curve": {}}}"#,
+                    mult, r#"{"type": "linear", "accel": 0}"#
+                );
+                std::fs::write(&path, curve).map_err(|err| err.to_string())?;
+                options.push_str(&format!(" --modifier-map {}:{}", key, path.display()));
+            }
+            let (shift, ctrl) = (
+                EventCode::EV_KEY(EV_KEY::KEY_LEFTSHIFT),
+                EventCode::EV_KEY(EV_KEY::KEY_LEFTCTRL),
+            );
+            let x = EventCode::EV_REL(EV_REL::REL_X);
+            let mut events = Vec::new();
+            for key in [
+                None,
+                Some((shift, 1)),
+                Some((ctrl, 1)),
+                Some((shift, 0)),
+                Some((ctrl, 0)),
+            ] {
+                events.extend(key.map(|(key, value)| event(key, value)));
+                events.extend([event(x, 1), event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0)]);
+            }
+            let written: Vec<_> = run_mock(&options, events)?
+                .into_iter()
+                .filter(|&(code, _)| matches!(code, EventCode::EV_KEY(_)) || code == x)
+                .collect();
+            let expected = [
+                (x, 1),
+                (shift, 1),
+                (x, 2),
+                (ctrl, 1),
+                (x, 2),
+                (shift, 0),
+                (x, 3),
+                (ctrl, 0),
+                (x, 1),
+            ];
+            if written != expected {
+                return Err(format!("expected {:?}, got {:?}", expected, written));
+            }
+            Ok(())
+        })();
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    // a frame with nothing before it goes out as it came, 10 counts per ms gets 1 + 0.1 * 10 on
+    // both axes, and 20 counts over 2ms gets the same, with x still written as it's always written
+    // alongside y
+    #[test]
+    fn mock_frames() -> Result<(), String> {
+        let events = frames(&[
+            (1, &[(EV_REL::REL_X, 3), (EV_REL::REL_Y, 4)]),
+            (2, &[(EV_REL::REL_X, 6), (EV_REL::REL_Y, 8)]),
+            (4, &[(EV_REL::REL_Y, 20)]),
+        ]);
+        let written = run_mock("-m 1 -a 0.1", events)?;
+        let (x, y, syn) = (
+            EventCode::EV_REL(EV_REL::REL_X),
+            EventCode::EV_REL(EV_REL::REL_Y),
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        );
+        let expected = [
+            (x, 3),
+            (y, 4),
+            (syn, 0),
+            (x, 12),
+            (y, 16),
+            (syn, 0),
+            (x, 0),
+            (y, 40),
+            (syn, 0),
+        ];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        Ok(())
+    }
+
+    // two REL_X events in a frame are moved as one of their sum, each frame's still only written
+    // once
+    #[test]
+    fn same_axis_events() -> Result<(), String> {
+        let events = frames(&[
+            (1, &[(EV_REL::REL_X, 2), (EV_REL::REL_X, 3)]),
+            (
+                2,
+                &[(EV_REL::REL_X, -1), (EV_REL::REL_Y, 4), (EV_REL::REL_X, -2)],
+            ),
+        ]);
+        let written = run_mock("-m 2 -a 0", events)?;
+        let (x, y, syn) = (
+            EventCode::EV_REL(EV_REL::REL_X),
+            EventCode::EV_REL(EV_REL::REL_Y),
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        );
+        let expected = [(x, 10), (y, 0), (syn, 0), (x, -6), (y, 8), (syn, 0)];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        Ok(())
+    }
+
+    // SYN_REPORTs with nothing before them go out on their own, even with a remainder carried
+    // that doesn't round to a count
+    #[test]
+    fn empty_frames() -> Result<(), String> {
+        let events = frames(&[(1, &[(EV_REL::REL_X, 3)]), (2, &[]), (3, &[])]);
+        let written = run_mock("-m 0.4 -a 0", events)?;
+        let (x, y, syn) = (
+            EventCode::EV_REL(EV_REL::REL_X),
+            EventCode::EV_REL(EV_REL::REL_Y),
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+        );
+        let expected = [(x, 1), (y, 0), (syn, 0), (syn, 0), (syn, 0)];
+        if written != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, written));
+        }
+        Ok(())
+    }
+
+    // a virtual device whose next writes fail with kind, as a full buffer or a signal would make
+    // them
+    struct Flaky {
+        inner: MockBackend,
+        failures: u32,
+        kind: ErrorKind,
+        tries: u32,
+    }
+
+    impl Flaky {
+        fn new(failures: u32, kind: ErrorKind) -> Flaky {
+            Flaky {
+                inner: MockBackend::default(),
+                failures,
+                kind,
+                tries: 0,
+            }
+        }
+    }
+
+    impl InputBackend for Flaky {
+        fn next_event(&mut self) -> std::io::Result<Option<InputEvent>> {
+            self.inner.next_event()
+        }
+
+        fn write_event(&mut self, event: &InputEvent) -> std::io::Result<()> {
+            self.tries += 1;
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(self.kind.into());
+            }
+            self.inner.write_event(event)
+        }
+
+        fn grab(&mut self) -> std::io::Result<()> {
+            self.inner.grab()
+        }
+
+        fn ungrab(&mut self) -> std::io::Result<()> {
+            self.inner.ungrab()
+        }
+    }
+
+    // a write failing with EAGAIN or EINTR is retried WRITE_RETRIES times after the first try,
+    // and written once one of them goes through; failing every time gives the error
+    #[test]
+    fn write_retries() -> Result<(), String> {
+        let moved = event(EventCode::EV_REL(EV_REL::REL_X), 3);
+        for kind in [ErrorKind::WouldBlock, ErrorKind::Interrupted] {
+            let mut recovering = Flaky::new(WRITE_RETRIES, kind);
+            crate::write_retrying(&mut recovering, &moved)
+                .map_err(|err| format!("{:?} retried and still failed: {}", kind, err))?;
+            if recovering.tries != WRITE_RETRIES + 1 || recovering.inner.written.len() != 1 {
+                return Err(format!(
+                    "{:?}: expected {} tries and one write, got {} and {}",
+                    kind,
+                    WRITE_RETRIES + 1,
+                    recovering.tries,
+                    recovering.inner.written.len()
+                ));
+            }
+            let mut failing = Flaky::new(WRITE_RETRIES + 1, kind);
+            match crate::write_retrying(&mut failing, &moved) {
+                Err(err) if err.kind() == kind && failing.tries == WRITE_RETRIES + 1 => {}
+                result => {
+                    return Err(format!(
+                        "{:?}: expected the error after {} tries, got {:?} after {}",
+                        kind,
+                        WRITE_RETRIES + 1,
+                        result,
+                        failing.tries
+                    ))
+                }
+            }
+        }
+        // anything else isn't going to go away
+        let mut broken = Flaky::new(1, ErrorKind::PermissionDenied);
+        if crate::write_retrying(&mut broken, &moved).is_ok() || broken.tries != 1 {
+            return Err(format!("expected no retries, got {}", broken.tries - 1));
+        }
+        Ok(())
+    }
+
+    // a frame that can't be written for a full buffer is dropped and counted, until too many in a
+    // row have been
+    #[test]
+    fn dropped_frames() -> Result<(), String> {
+        let mut full = Flaky::new(u32::MAX, ErrorKind::WouldBlock);
+        let mut dropped = 0;
+        let time = TimeVal::new(0, 0);
+        for frame in 1..=DROPPED_FRAMES_LIMIT {
+            crate::write_frame(&mut full, time, 1, 0, &[], &mut dropped)
+                .map_err(|err| format!("frame {} wasn't dropped: {}", frame, err))?;
+        }
+        if dropped != DROPPED_FRAMES_LIMIT {
+            return Err(format!(
+                "expected {} dropped, got {}",
+                DROPPED_FRAMES_LIMIT, dropped
+            ));
+        }
+        if crate::write_frame(&mut full, time, 1, 0, &[], &mut dropped).is_ok() {
+            return Err("expected giving up after too many dropped".to_string());
+        }
+        let mut recovered = Flaky::new(0, ErrorKind::WouldBlock);
+        crate::write_frame(&mut recovered, time, 1, 0, &[], &mut dropped)
+            .map_err(|err| err.to_string())?;
+        if dropped != 0 {
+            return Err(format!(
+                "expected the count reset by a frame written, got {}",
+                dropped
+            ));
+        }
+        Ok(())
+    }
+
+    // running until the source ends and then tearing down gives the device back and destroys the
+    // virtual device, which writes afterwards find gone
+    #[test]
+    fn tear_down() -> Result<(), String> {
+        let events = frames(&[(0, &[(EV_REL::REL_X, 3)])]);
+        let args = mock_args("-m 1 -a 0")?;
+        let mut backend = MockBackend::new(events.clone());
+        backend.grab().map_err(|err| err.to_string())?;
+        run_backend(&mut backend, &args, &Control::default())?;
+        if backend.destroyed {
+            return Err("destroyed before shutting down".to_string());
+        }
+        crate::tear_down(&mut backend, &args, &mut Taps::none()).map_err(|err| err.to_string())?;
+        if backend.grabbed || !backend.destroyed {
+            return Err(format!(
+                "grabbed {} and destroyed {} after tearing down",
+                backend.grabbed, backend.destroyed
+            ));
+        }
+        match backend.write_event(&events[0]) {
+            Err(err) if err.raw_os_error() == Some(libc::ENODEV) => Ok(()),
+            written => Err(format!("writing after tearing down gave {:?}", written)),
+        }
+    }
+
+    // 3 counts at 0.4x is 1.2, leaving 0.2 over; the empty frame after the idle gap would carry it
+    // on, unless it's been cleared while idle
+    #[test]
+    fn anti_drift() -> Result<(), String> {
+        let remainder_after = |options: &str| -> Result<f64, String> {
+            let events = frames(&[(1, &[(EV_REL::REL_X, 3)]), (100, &[])]);
+            let mut backend = ReplayBackend::with_clock(events, 1.0, RealClock);
+            let (_, accel) = run_backend(&mut backend, &mock_args(options)?, &Control::default())?;
+            Ok(accel.x_accum)
+        };
+        let kept = remainder_after("-m 0.4 -a 0")?;
+        if (kept - 0.2).abs() > 1e-9 {
+            return Err(format!("expected 0.2 carried without it, got {}", kept));
+        }
+        let cleared = remainder_after("-m 0.4 -a 0 --anti-drift 30")?;
+        if cleared != 0.0 {
+            return Err(format!("expected the remainder cleared, got {}", cleared));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+// service manager notifications for running as a Type=notify systemd unit, which do nothing
+// without the systemd feature or outside of systemd
+use std::time::Duration;
+
+#[cfg(feature = "systemd")]
+use sd_notify::NotifyState;
+
+#[cfg(feature = "systemd")]
+fn send(state: NotifyState) {
+    if let Err(err) = sd_notify::notify(false, &[state]) {
+        eprintln!("Warning: couldn't notify systemd: {}", err);
+    }
+}
+
+// the device is grabbed and the virtual device is up
+pub fn ready() {
+    #[cfg(feature = "systemd")]
+    send(NotifyState::Ready);
+}
+
+pub fn stopping() {
+    #[cfg(feature = "systemd")]
+    send(NotifyState::Stopping);
+}
+
+pub fn watchdog() {
+    #[cfg(feature = "systemd")]
+    send(NotifyState::Watchdog);
+}
+
+// how often to ping the watchdog, half its timeout as systemd suggests, None if it isn't on
+pub fn watchdog_interval() -> Option<Duration> {
+    #[cfg(feature = "systemd")]
+    {
+        // meant for another process
+        let pid = std::env::var("WATCHDOG_PID").ok();
+        if pid.is_none_or(|pid| pid == std::process::id().to_string()) {
+            return interval_from(std::env::var("WATCHDOG_USEC").ok().as_deref());
+        }
+    }
+    None
+}
+
+// half of the timeout WATCHDOG_USEC gives, None if it's unset, 0 or not a number
+#[cfg(any(feature = "systemd", test))]
+fn interval_from(usec: Option<&str>) -> Option<Duration> {
+    match usec?.parse() {
+        Ok(0) | Err(_) => None,
+        Ok(usec) => Some(Duration::from_micros(usec) / 2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::interval_from;
+
+    // the watchdog's only on with a timeout, and is pinged twice as often
+    #[test]
+    fn watchdog_usec() -> Result<(), String> {
+        let cases = [
+            (None, None),
+            (Some("0"), None),
+            (Some("soon"), None),
+            (Some("-5"), None),
+            (Some("30000000"), Some(Duration::from_secs(15))),
+        ];
+        for (usec, expected) in cases {
+            let interval = interval_from(usec);
+            if interval != expected {
+                return Err(format!(
+                    "{:?}: expected {:?}, got {:?}",
+                    usec, expected, interval
+                ));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+// sysfs doesn't change the modification time of these, so they're reread on a timer
+pub const POWER_POLL: Duration = Duration::from_secs(10);
+// the attributes making up the state, whichever the device has
+const ATTRIBUTES: [&str; 2] = ["status", "capacity_level"];
+
+// a wireless mouse's battery, which some mice drop their report rate with when it runs low
+pub struct PowerSupply {
+    path: PathBuf,
+    state: Option<String>,
+    checked: Instant,
+}
+
+// like "Discharging Low", None if none of the attributes could be read
+fn read_state(path: &Path) -> Option<String> {
+    let values: Vec<_> = ATTRIBUTES
+        .iter()
+        .filter_map(|attribute| fs::read_to_string(path.join(attribute)).ok())
+        .map(|value| value.trim().to_string())
+        .collect();
+    (!values.is_empty()).then(|| values.join(" "))
+}
+
+impl PowerSupply {
+    // path is the device's directory in /sys/class/power_supply
+    pub fn new(path: PathBuf, now: Instant) -> PowerSupply {
+        let state = read_state(&path);
+        if state.is_none() {
+            eprintln!(
+                "Warning: couldn't read a status from the power supply {}",
+                path.display()
+            );
+        }
+        PowerSupply {
+            path,
+            state,
+            checked: now,
+        }
+    }
+
+    // the old and new state when it's changed since it was last read, at most once a POWER_POLL
+    pub fn poll(&mut self, now: Instant) -> Option<(Option<String>, String)> {
+        if now.saturating_duration_since(self.checked) < POWER_POLL {
+            return None;
+        }
+        self.checked = now;
+        // a mouse that's turned off takes its power supply with it, which isn't a change to warn of
+        let state = read_state(&self.path)?;
+        if self.state.as_ref() == Some(&state) {
+            return None;
+        }
+        Some((self.state.replace(state.clone()), state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Instant};
+
+    use super::{PowerSupply, POWER_POLL};
+
+    // a made up sysfs node, warned about when it changes
+    #[test]
+    fn power_supply() -> Result<(), String> {
+        let dir = std::env::temp_dir().join(format!("accelerator-selftest-{}", std::process::id()));
+        let write = |attribute: &str, value: &str| {
+            fs::write(dir.join(attribute), format!("{}\n", value)).map_err(|err| err.to_string())
+        };
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let result = (|| {
+            write("status", "Discharging")?;
+            write("capacity_level", "Normal")?;
+            let start = Instant::now();
+            let mut power = PowerSupply::new(dir.clone(), start);
+            if let Some(change) = power.poll(start + POWER_POLL) {
+                return Err(format!("nothing changed, got {:?}", change));
+            }
+            write("capacity_level", "Low")?;
+            // not time to look yet
+            if let Some(change) = power.poll(start + POWER_POLL + POWER_POLL / 2) {
+                return Err(format!("read too soon, got {:?}", change));
+            }
+            match power.poll(start + POWER_POLL * 2) {
+                Some((Some(old), new))
+                    if old == "Discharging Normal" && new == "Discharging Low" =>
+                {
+                    Ok(())
+                }
+                other => Err(format!("expected the battery running low, got {:?}", other)),
+            }
+        })();
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+}
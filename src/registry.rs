@@ -0,0 +1,120 @@
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use evdev_rs::Device;
+use serde::Deserialize;
+
+use crate::{advice::DeviceMetadata, curve_file::CurveFile};
+
+// which curve file each device uses, so switching mice switches curves
+#[derive(Deserialize)]
+struct Registry {
+    devices: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    // the device's name, or its vendor:product ids in hex
+    #[serde(rename = "match")]
+    pattern: String,
+    // relative to the registry
+    curve: PathBuf,
+}
+
+impl Entry {
+    fn matches(&self, device: &DeviceMetadata) -> bool {
+        let ids = self.pattern.split_once(':').and_then(|(vendor, product)| {
+            Some((
+                u16::from_str_radix(vendor, 16).ok()?,
+                u16::from_str_radix(product, 16).ok()?,
+            ))
+        });
+        match ids {
+            Some((vendor, product)) => device.vendor == vendor && device.product == product,
+            None => device.name == self.pattern,
+        }
+    }
+}
+
+impl Registry {
+    fn parse(contents: &str) -> Result<Registry, String> {
+        toml::from_str(contents).map_err(|err| err.to_string())
+    }
+
+    // the curve file the first entry matching the device gives, relative to the registry's
+    // directory dir
+    fn curve_for(&self, dir: &Path, device: &DeviceMetadata) -> Option<PathBuf> {
+        let entry = self.devices.iter().find(|entry| entry.matches(device))?;
+        Some(dir.join(&entry.curve))
+    }
+}
+
+// the curve file of the first entry matching the device, None if none do or the device can't be
+// opened (which is reported when it's opened for real)
+pub fn lookup(path: &Path, device: &OsStr) -> Result<Option<CurveFile>, String> {
+    let registry = Registry::parse(&fs::read_to_string(path).map_err(|err| err.to_string())?)?;
+    let Ok(device) = File::open(device).and_then(Device::new_from_file) else {
+        return Ok(None);
+    };
+    let device = DeviceMetadata::from_device(&device);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let Some(curve) = registry.curve_for(dir, &device) else {
+        return Ok(None);
+    };
+    CurveFile::load(&curve)
+        .map(Some)
+        .map_err(|err| format!("{}: {}", curve.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::Registry;
+    use crate::advice::DeviceMetadata;
+
+    fn device(name: &str, vendor: u16, product: u16) -> DeviceMetadata {
+        DeviceMetadata {
+            name: name.to_string(),
+            bustype: 3,
+            vendor,
+            product,
+        }
+    }
+
+    // one mouse by its ids and another by its name each get their own curve, and one the registry
+    // doesn't list gets none
+    #[test]
+    fn two_devices() -> Result<(), String> {
+        let registry = Registry::parse(
+            r#"
+[[devices]]
+match = "046d:c08b"
+curve = "g-pro.json"
+
+[[devices]]
+match = "Razer Viper"
+curve = "curves/viper.json"
+"#,
+        )?;
+        let dir = Path::new("/etc/accelerator");
+        let found = [
+            device("Logitech G Pro", 0x046d, 0xc08b),
+            device("Razer Viper", 0x1532, 0x0078),
+            device("Some Mouse", 0x1234, 0x5678),
+        ]
+        .map(|device| registry.curve_for(dir, &device));
+        let expected = [
+            Some(PathBuf::from("/etc/accelerator/g-pro.json")),
+            Some(PathBuf::from("/etc/accelerator/curves/viper.json")),
+            None,
+        ];
+        if found != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, found));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL},
+    InputEvent, TimeVal,
+};
+
+use crate::{
+    accel::{elapsed_ms, factor},
+    args::Args,
+};
+
+// a longer gap between detents than this starts the scrolling over from standing still
+const SCROLL_IDLE_MS: f64 = 1000.0;
+// a touchpad scrolls in a steady stream of small steps while fingers are down, so a short gap
+// means they've been lifted
+const TOUCHPAD_IDLE_MS: f64 = 100.0;
+
+// one wheel axis's scrolling so far, the REL_WHEEL detents and the REL_WHEEL_HI_RES counts kept
+// apart since programs only read one or the other
+#[derive(Default)]
+struct Wheel {
+    last: Option<TimeVal>,
+    // sign of the last scroll
+    direction: i32,
+    // what rounding left over, scrolling up and scrolling down
+    up_accum: f64,
+    down_accum: f64,
+}
+
+impl Wheel {
+    // value counts of scrolling at time, unit of them making a detent
+    fn scroll(
+        &mut self,
+        accel: (f64, f64),
+        idle_ms: f64,
+        value: i32,
+        unit: f64,
+        time: TimeVal,
+    ) -> i32 {
+        if value.signum() != self.direction {
+            // neither direction's remainder carries over a reversal, nor does the speed
+            *self = Wheel {
+                direction: value.signum(),
+                ..Wheel::default()
+            };
+        }
+        let gap_ms = self
+            .last
+            .map_or(f64::INFINITY, |last| elapsed_ms(last, time));
+        self.last = Some(time);
+        let speed = if gap_ms > 0.0 && gap_ms <= idle_ms {
+            value.abs() as f64 / unit * 1000.0 / gap_ms
+        } else {
+            0.0
+        };
+        let (accel, accum) = if value > 0 {
+            (accel.0, &mut self.up_accum)
+        } else {
+            (accel.1, &mut self.down_accum)
+        };
+        let scrolled = value as f64 * factor(1.0, accel, f64::INFINITY, 0.0, speed) + *accum;
+        let rounded = scrolled.round();
+        *accum = scrolled - rounded;
+        rounded as i32
+    }
+}
+
+// accelerates the vertical wheel by how many detents a second it's turning, up and down each
+// with their own acceleration, or a touchpad's scrolling both ways
+pub struct ScrollAccel {
+    // for scrolling up (positive), then down
+    accel: (f64, f64),
+    idle_ms: f64,
+    // whether the horizontal wheel is accelerated too
+    both_axes: bool,
+    wheel: Wheel,
+    hi_res: Wheel,
+    hwheel: Wheel,
+    hwheel_hi_res: Wheel,
+}
+
+impl ScrollAccel {
+    pub fn new(up: f64, down: f64) -> ScrollAccel {
+        ScrollAccel {
+            accel: (up, down),
+            idle_ms: SCROLL_IDLE_MS,
+            both_axes: false,
+            wheel: Wheel::default(),
+            hi_res: Wheel::default(),
+            hwheel: Wheel::default(),
+            hwheel_hi_res: Wheel::default(),
+        }
+    }
+
+    // two finger scrolling goes either way along either axis, and stops as soon as the fingers lift
+    pub fn touchpad(accel: f64) -> ScrollAccel {
+        ScrollAccel {
+            idle_ms: TOUCHPAD_IDLE_MS,
+            both_axes: true,
+            ..ScrollAccel::new(accel, accel)
+        }
+    }
+
+    // the touchpad's settings with --touchpad-mode, the wheel's otherwise, None if the wheel isn't
+    // accelerated
+    pub fn from_args(args: &Args) -> Option<ScrollAccel> {
+        if args.touchpad_mode {
+            return Some(ScrollAccel::touchpad(args.touchpad_scroll_accel));
+        }
+        (args.scroll_up_accel.is_some() || args.scroll_down_accel.is_some()).then(|| {
+            ScrollAccel::new(
+                args.scroll_up_accel.unwrap_or(0.0),
+                args.scroll_down_accel.unwrap_or(0.0),
+            )
+        })
+    }
+
+    // the event with its scrolling accelerated, or None if it all went to the remainder
+    pub fn event(&mut self, event: InputEvent) -> Option<InputEvent> {
+        let (wheel, unit) = match event.event_code {
+            EventCode::EV_REL(EV_REL::REL_WHEEL) => (&mut self.wheel, 1.0),
+            EventCode::EV_REL(EV_REL::REL_WHEEL_HI_RES) => (&mut self.hi_res, DETENT as f64),
+            EventCode::EV_REL(EV_REL::REL_HWHEEL) if self.both_axes => (&mut self.hwheel, 1.0),
+            EventCode::EV_REL(EV_REL::REL_HWHEEL_HI_RES) if self.both_axes => {
+                (&mut self.hwheel_hi_res, DETENT as f64)
+            }
+            _ => return Some(event),
+        };
+        if event.value == 0 {
+            return Some(event);
+        }
+        let value = wheel.scroll(self.accel, self.idle_ms, event.value, unit, event.time);
+        (value != 0).then_some(InputEvent { value, ..event })
+    }
+}
+
+// the hi-res units in a detent, as the kernel counts them
+pub const DETENT: i32 = 120;
+// time between the frames a detent is spread over
+pub const SCROLL_FRAME: Duration = Duration::from_millis(4);
+
+// scrolling on one wheel still to be written
+#[derive(Default)]
+struct Spread {
+    left: i32,
+    frames_left: u32,
+}
+
+impl Spread {
+    // the next frame's share, the last frame taking whatever doesn't divide evenly
+    fn step(&mut self) -> i32 {
+        if self.frames_left == 0 {
+            return 0;
+        }
+        // rounds toward 0, so it never overshoots in either direction
+        let step = self.left / self.frames_left as i32;
+        self.left -= step;
+        self.frames_left -= 1;
+        step
+    }
+}
+
+// spreads each detent of a wheel out over several frames of hi-res scrolling
+pub struct SmoothScroll {
+    frames: u32,
+    wheel: Spread,
+    hwheel: Spread,
+    // when the next step is to be written, None with nothing left
+    next_due: Option<Instant>,
+}
+
+impl SmoothScroll {
+    pub fn new(frames: u32) -> SmoothScroll {
+        SmoothScroll {
+            frames,
+            wheel: Spread::default(),
+            hwheel: Spread::default(),
+            next_due: None,
+        }
+    }
+
+    // takes note of the detents in an event read at now
+    pub fn event(&mut self, event: &InputEvent, now: Instant) {
+        let spread = match event.event_code {
+            EventCode::EV_REL(EV_REL::REL_WHEEL) => &mut self.wheel,
+            EventCode::EV_REL(EV_REL::REL_HWHEEL) => &mut self.hwheel,
+            _ => return,
+        };
+        // anything still left is spread out again along with the new detents
+        spread.left += event.value * DETENT;
+        spread.frames_left = self.frames;
+        // the first step goes out with the detent's own frame
+        self.next_due.get_or_insert(now);
+    }
+
+    // how long until steps are due, None with nothing left to scroll
+    pub fn due_in(&self, now: Instant) -> Option<Duration> {
+        self.next_due.map(|due| due.saturating_duration_since(now))
+    }
+
+    // the hi-res scrolling due by now, nothing if it isn't time yet
+    pub fn steps(&mut self, now: Instant) -> Vec<(EV_REL, i32)> {
+        if self.next_due.is_none_or(|due| due > now) {
+            return Vec::new();
+        }
+        let steps: Vec<_> = [
+            (EV_REL::REL_WHEEL_HI_RES, self.wheel.step()),
+            (EV_REL::REL_HWHEEL_HI_RES, self.hwheel.step()),
+        ]
+        .into_iter()
+        .filter(|&(_, step)| step != 0)
+        .collect();
+        let done = self.wheel.frames_left == 0 && self.hwheel.frames_left == 0;
+        self.next_due = (!done).then(|| now + SCROLL_FRAME);
+        steps
+    }
+}
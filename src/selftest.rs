@@ -0,0 +1,463 @@
+use std::{ffi::OsString, time::Instant};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL},
+    InputEvent, TimeVal,
+};
+
+use crate::{
+    accel::Accelerator,
+    args::parse_args,
+    scroll::{ScrollAccel, SmoothScroll, DETENT, SCROLL_FRAME},
+};
+
+struct Case {
+    name: &'static str,
+    options: &'static [&'static str],
+    // raw movement and the microseconds since the frame before
+    frames: &'static [(i32, i32, i64)],
+    // worked out by hand from the curve's formula
+    expected: &'static [(i32, i32)],
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "linear",
+        options: &["-m", "1", "-a", "0.1"],
+        // 10 counts/ms: 1 + 0.1 * 10 = 2
+        frames: &[(10, 0, 1000)],
+        expected: &[(20, 0)],
+    },
+    Case {
+        name: "linear capped",
+        options: &["-m", "2", "-a", "1", "-c", "3"],
+        // 2 * min(1 + 10, 3) = 6
+        frames: &[(10, 0, 1000)],
+        expected: &[(60, 0)],
+    },
+    Case {
+        name: "relative cap",
+        options: &["-m", "2", "-a", "1", "-c", "3", "--cap-mode", "relative"],
+        // the same as without the mode, at most 2 * 3
+        frames: &[(10, 0, 1000)],
+        expected: &[(60, 0)],
+    },
+    Case {
+        name: "absolute cap",
+        options: &["-m", "2", "-a", "1", "-c", "3", "--cap-mode", "absolute"],
+        // min(2 * (1 + 10), 3) = 3, and below the cap 2 * (1 + 0.25) = 2.5
+        frames: &[(10, 0, 1000), (2, 0, 8000)],
+        expected: &[(30, 0), (5, 0)],
+    },
+    Case {
+        name: "linear offset",
+        options: &["-m", "1", "-a", "0.2", "-o", "4"],
+        // 5 counts/ms diagonally: 1 + 0.2 * (5 - 4) = 1.2, then 2 at 9 counts/ms
+        frames: &[(3, 4, 1000), (0, 9, 1000)],
+        expected: &[(4, 5), (0, 18)],
+    },
+    Case {
+        name: "below offset",
+        options: &["-m", "1.5", "-a", "1", "-o", "10"],
+        frames: &[(4, -2, 1000)],
+        expected: &[(6, -3)],
+    },
+    Case {
+        name: "slow frames",
+        options: &["-m", "1", "-a", "0.1"],
+        // 10 counts over 5ms is 2 counts/ms: 1.2
+        frames: &[(10, 0, 5000)],
+        expected: &[(12, 0)],
+    },
+    Case {
+        name: "expression",
+        options: &["--expr", "1 + s/10"],
+        // 20 counts over 2ms: 1 + 10/10 = 2
+        frames: &[(20, 0, 2000)],
+        expected: &[(40, 0)],
+    },
+    Case {
+        name: "breakaway",
+        options: &["-m", "1", "--breakaway", "5", "-c", "3"],
+        frames: &[(4, 0, 1000), (6, 0, 1000)],
+        expected: &[(4, 0), (18, 0)],
+    },
+    Case {
+        name: "linear defaults",
+        options: &["-m", "1", "--curve", "linear"],
+        // -a 0.1: 10 counts over 1ms is 1 + 10*0.1 = 2
+        frames: &[(10, 0, 1000)],
+        expected: &[(20, 0)],
+    },
+    Case {
+        name: "breakaway defaults",
+        options: &["-m", "1", "--curve", "breakaway"],
+        // --breakaway 10 -c 2
+        frames: &[(4, 0, 1000), (20, 0, 1000)],
+        expected: &[(4, 0), (40, 0)],
+    },
+    Case {
+        name: "expr defaults",
+        options: &["-m", "1", "--curve", "expr"],
+        // min(1 + 0.01*s^2, 3): 1 + 0.01*10^2 = 2, then capped at 3
+        frames: &[(10, 0, 1000), (30, 0, 1000)],
+        expected: &[(20, 0), (90, 0)],
+    },
+    Case {
+        name: "velocity map defaults",
+        options: &["-m", "1", "--curve", "velocity-map"],
+        // 1x below 5, 20 maps to 40 and 35 halfway to 50's 150
+        frames: &[(4, 0, 1000), (20, 0, 1000), (35, 0, 1000)],
+        expected: &[(4, 0), (40, 0), (95, 0)],
+    },
+    Case {
+        name: "segmented defaults",
+        options: &["-m", "1", "--curve", "segmented"],
+        // flat below 5, then 1 + 0.1*(15 - 5) = 2
+        frames: &[(4, 0, 1000), (15, 0, 1000)],
+        expected: &[(4, 0), (30, 0)],
+    },
+    Case {
+        name: "accumulation",
+        options: &["-m", "0.5", "-a", "0"],
+        // 0.5 rounds up, and the -0.5 left over cancels the next half
+        frames: &[(1, 0, 1000), (1, 0, 1000), (1, 0, 1000), (1, 0, 1000)],
+        expected: &[(1, 0), (0, 0), (1, 0), (0, 0)],
+    },
+    Case {
+        name: "direction multipliers",
+        options: &[
+            "-m",
+            "1",
+            "-a",
+            "0",
+            "--x-neg-mult",
+            "2",
+            "--y-pos-mult",
+            "3",
+        ],
+        frames: &[(-3, 2, 1000)],
+        expected: &[(-6, 6)],
+    },
+    Case {
+        name: "diagonals uncompensated",
+        options: &["-m", "1", "-a", "0"],
+        frames: &[(10, 0, 1000), (10, 10, 1000), (0, 10, 1000)],
+        expected: &[(10, 0), (10, 10), (0, 10)],
+    },
+    Case {
+        name: "diagonal compensation",
+        options: &["-m", "1", "-a", "0", "--diagonal-compensation", "0.1"],
+        // only 45 degrees gets all of it
+        frames: &[(10, 0, 1000), (10, 10, 1000), (0, 10, 1000)],
+        expected: &[(10, 0), (11, 11), (0, 10)],
+    },
+    Case {
+        name: "swapped axes",
+        options: &["-m", "1", "-a", "0.1", "--swap-xy", "--y-pos-mult", "2"],
+        // the direction multiplier is for the axis written
+        frames: &[(10, 0, 1000)],
+        expected: &[(0, 40)],
+    },
+    Case {
+        name: "flick cooldown",
+        options: &[
+            "-m",
+            "1",
+            "-a",
+            "0.1",
+            "--flick-threshold",
+            "10",
+            "--flick-cooldown",
+            "100",
+        ],
+        // a flick at 3x, slowing down and flicking again within 100ms of it ending just get the
+        // multiplier, and the cooldown's over in time for the flick after standing still
+        frames: &[
+            (20, 0, 1000),
+            (20, 0, 1000),
+            (5, 0, 1000),
+            (20, 0, 1000),
+            (5, 0, 150_000),
+            (20, 0, 1000),
+        ],
+        expected: &[(60, 0), (60, 0), (5, 0), (20, 0), (5, 0), (60, 0)],
+    },
+    Case {
+        name: "accel budget burst",
+        options: &["-m", "1", "-a", "0.1", "--accel-budget", "1000"],
+        // adds 10 counts of the 1000 there are
+        frames: &[(10, 0, 1000), (10, 0, 1000)],
+        expected: &[(20, 0), (20, 0)],
+    },
+    Case {
+        name: "accel budget sustained",
+        options: &["-m", "1", "-a", "0.1", "--accel-budget", "20"],
+        // each frame adds 10 and refills 0.02, so the third only has 0.04 to add
+        frames: &[(10, 0, 1000), (10, 0, 1000), (10, 0, 1000)],
+        expected: &[(20, 0), (20, 0), (10, 0)],
+    },
+    Case {
+        name: "counts per frame",
+        options: &["-m", "1", "-a", "0.1", "--speed-metric", "counts-per-frame"],
+        // timing ignored: 1 + 0.1 * 10 = 2
+        frames: &[(10, 0, 5000)],
+        expected: &[(20, 0)],
+    },
+    Case {
+        name: "late timestamps",
+        options: &["-m", "1", "-a", "0.1"],
+        // 10 counts/ms, with a microsecond borrow, so long after 0 that the whole time in
+        // microseconds doesn't fit in a double
+        frames: &[(0, 0, 10_000_000_000_999_600), (10, 0, 1000)],
+        expected: &[(0, 0), (20, 0)],
+    },
+    Case {
+        name: "accel after",
+        options: &["-m", "1", "-a", "0.1", "--accel-after", "2"],
+        // moving for 1ms rounds to standing still, then 2ms is enough
+        frames: &[(10, 0, 1000), (10, 0, 1000)],
+        expected: &[(10, 0), (20, 0)],
+    },
+    Case {
+        name: "second boundary",
+        options: &["-m", "1", "-a", "0.1"],
+        // 10 counts/ms, with the timestamp carrying into the next second
+        frames: &[(0, 0, 999_600), (10, 0, 1000)],
+        expected: &[(0, 0), (20, 0)],
+    },
+];
+
+struct ScrollCase {
+    name: &'static str,
+    frames: u32,
+    detents: i32,
+    // hi-res scrolling written each frame, adding up to the detents
+    expected: &'static [i32],
+}
+
+const SCROLL_CASES: &[ScrollCase] = &[
+    ScrollCase {
+        name: "smooth scroll",
+        frames: 4,
+        detents: 3,
+        expected: &[90, 90, 90, 90],
+    },
+    ScrollCase {
+        name: "smooth scroll down",
+        frames: 3,
+        detents: -1,
+        expected: &[-40, -40, -40],
+    },
+    ScrollCase {
+        name: "smooth scroll remainder",
+        frames: 7,
+        detents: 1,
+        // 120 doesn't divide by 7, the last frame makes up the difference
+        expected: &[17, 17, 17, 17, 17, 17, 18],
+    },
+];
+
+struct ScrollAccelCase {
+    name: &'static str,
+    options: &'static [&'static str],
+    wheel: EV_REL,
+    // detents and the milliseconds since the detents before
+    detents: &'static [(i32, i64)],
+    expected: &'static [i32],
+}
+
+const SCROLL_ACCEL_CASES: &[ScrollAccelCase] = &[
+    ScrollAccelCase {
+        name: "scroll up and down curves",
+        options: &["--scroll-up-accel", "0.1"],
+        wheel: EV_REL::REL_WHEEL,
+        // 10 detents a second: 1 + 0.1 * 10 = 2 up, down isn't accelerated
+        detents: &[(1, 0), (1, 100), (-1, 100), (-1, 100)],
+        expected: &[1, 2, -1, -1],
+    },
+    ScrollAccelCase {
+        name: "scroll reversal",
+        options: &["--scroll-up-accel", "0.04", "--scroll-down-accel", "0.04"],
+        wheel: EV_REL::REL_WHEEL,
+        // 1.4 leaves 0.4, which would make the last 1.8 if it survived scrolling down
+        detents: &[(1, 0), (1, 100), (-1, 100), (1, 100), (1, 100)],
+        expected: &[1, 1, -1, 1, 1],
+    },
+    ScrollAccelCase {
+        name: "wheel scroll",
+        options: &["--scroll-up-accel", "0.1"],
+        wheel: EV_REL::REL_WHEEL,
+        // 100 detents a second: 1 + 0.1 * 100 = 11, then 25 a second still counts on a wheel: 3.5
+        detents: &[(5, 0), (5, 50), (5, 200)],
+        expected: &[5, 55, 18],
+    },
+    ScrollAccelCase {
+        name: "touchpad scroll",
+        options: &["--touchpad-mode", "--scroll-up-accel", "0.1"],
+        wheel: EV_REL::REL_WHEEL,
+        // 1 + 0.02 * 100 = 3, the wheel's acceleration is left to wheels, and the fingers lifting
+        // for 200ms starts the speed over
+        detents: &[(5, 0), (5, 50), (5, 200)],
+        expected: &[5, 15, 5],
+    },
+    ScrollAccelCase {
+        name: "touchpad horizontal scroll",
+        options: &["--touchpad-mode"],
+        wheel: EV_REL::REL_HWHEEL,
+        detents: &[(-5, 0), (-5, 50)],
+        expected: &[-5, -15],
+    },
+    ScrollAccelCase {
+        name: "wheel horizontal scroll",
+        options: &["--scroll-up-accel", "0.1"],
+        wheel: EV_REL::REL_HWHEEL,
+        detents: &[(5, 0), (5, 50)],
+        expected: &[5, 5],
+    },
+];
+
+fn run_scroll_accel_case(case: &ScrollAccelCase) -> Result<(), String> {
+    // the curve for movement has to be there, but doesn't matter
+    let options = ["-m", "1", "-a", "0"]
+        .into_iter()
+        .chain(case.options.iter().copied())
+        .chain(["selftest-device"]);
+    let args = parse_args(options.map(OsString::from).collect())
+        .map_err(|err| format!("bad options: {}", err))?;
+    let mut scroll_accel = ScrollAccel::from_args(&args).ok_or("scrolling isn't accelerated")?;
+    let mut time_ms = 1000;
+    let mut written = Vec::new();
+    for &(detents, gap) in case.detents {
+        time_ms += gap;
+        let time = TimeVal::new(time_ms / 1000, time_ms % 1000 * 1000);
+        let event = InputEvent::new(&time, &EventCode::EV_REL(case.wheel), detents);
+        written.push(scroll_accel.event(event).map_or(0, |event| event.value));
+    }
+    if written != case.expected {
+        return Err(format!("expected {:?}, got {:?}", case.expected, written));
+    }
+    Ok(())
+}
+
+fn run_scroll_case(case: &ScrollCase) -> Result<(), String> {
+    let mut scroll = SmoothScroll::new(case.frames);
+    let mut now = Instant::now();
+    let detents = InputEvent::new(
+        &TimeVal::new(0, 0),
+        &EventCode::EV_REL(EV_REL::REL_WHEEL),
+        case.detents,
+    );
+    scroll.event(&detents, now);
+    let mut written = Vec::new();
+    while scroll.due_in(now).is_some() {
+        for (code, step) in scroll.steps(now) {
+            if code != EV_REL::REL_WHEEL_HI_RES {
+                return Err(format!("scrolled on {:?}", code));
+            }
+            written.push(step);
+        }
+        now += SCROLL_FRAME;
+    }
+    if written != case.expected {
+        return Err(format!("expected {:?}, got {:?}", case.expected, written));
+    }
+    // checked apart from the steps, in case the expected steps are wrong
+    let total: i32 = written.iter().sum();
+    if total != case.detents * DETENT {
+        return Err(format!("scrolled {} for {} detents", total, case.detents));
+    }
+    Ok(())
+}
+
+fn run_case(case: &Case) -> Result<(), String> {
+    // never opened, it only has to be there
+    let options = case.options.iter().copied().chain(["selftest-device"]);
+    let args = parse_args(options.map(OsString::from).collect())
+        .map_err(|err| format!("bad options: {}", err))?;
+    let mut accel = Accelerator::new();
+    // the first frame after starting has no speed, so it only sets the time
+    let mut time_us = 1_000_000;
+    accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+    for (i, (&(x, y, gap), &expected)) in case.frames.iter().zip(case.expected).enumerate() {
+        time_us += gap;
+        let time = TimeVal::new(time_us / 1_000_000, time_us % 1_000_000);
+        let rel_events = (x != 0) as u32 + (y != 0) as u32;
+        let out = accel.process_frame(&args, x as f64, y as f64, rel_events, None, time);
+        if out != expected {
+            return Err(format!(
+                "frame {}: expected {:?}, got {:?}",
+                i + 1,
+                expected,
+                out
+            ));
+        }
+    }
+    Ok(())
+}
+
+// checks the processing against known answers, returns the exit code
+pub fn selftest() -> i32 {
+    let results = CASES
+        .iter()
+        .map(|case| (case.name, run_case(case)))
+        .chain(
+            SCROLL_CASES
+                .iter()
+                .map(|case| (case.name, run_scroll_case(case))),
+        )
+        .chain(
+            SCROLL_ACCEL_CASES
+                .iter()
+                .map(|case| (case.name, run_scroll_accel_case(case))),
+        );
+    let mut failed = 0;
+    let mut total = 0;
+    for (name, result) in results {
+        total += 1;
+        match result {
+            Ok(()) => println!("pass {}", name),
+            Err(err) => {
+                println!("FAIL {}: {}", name, err);
+                failed += 1;
+            }
+        }
+    }
+    println!("{} of {} passed", total - failed, total);
+    (failed > 0) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        run_case, run_scroll_accel_case, run_scroll_case, CASES, SCROLL_ACCEL_CASES, SCROLL_CASES,
+    };
+
+    // every case is run, so one failing doesn't hide the rest
+    fn failures<T>(cases: &[T], name: fn(&T) -> &str, run: fn(&T) -> Result<(), String>) -> String {
+        let failed = cases.iter().filter_map(|case| {
+            run(case)
+                .err()
+                .map(|err| format!("{}: {}", name(case), err))
+        });
+        failed.collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn cases() {
+        let failed = failures(CASES, |case| case.name, run_case);
+        assert!(failed.is_empty(), "{}", failed);
+    }
+
+    #[test]
+    fn scroll_cases() {
+        let failed = failures(SCROLL_CASES, |case| case.name, run_scroll_case);
+        assert!(failed.is_empty(), "{}", failed);
+    }
+
+    #[test]
+    fn scroll_accel_cases() {
+        let failed = failures(SCROLL_ACCEL_CASES, |case| case.name, run_scroll_accel_case);
+        assert!(failed.is_empty(), "{}", failed);
+    }
+}
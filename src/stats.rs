@@ -0,0 +1,154 @@
+use std::time::Instant;
+
+use evdev_rs::{
+    enums::{EventCode, EV_SYN},
+    InputEvent,
+};
+
+// speeds are only kept as a histogram so long sessions don't grow memory, which makes the median
+// approximate: buckets are a 16th of an octave wide (about 4%), from 2^-10 up to 2^20
+const BUCKETS_PER_OCTAVE: f64 = 16.0;
+const LOWEST_OCTAVE: f64 = -10.0;
+const OCTAVES: usize = 30;
+
+// counters for the summary printed when the session ends
+pub struct Stats {
+    start: Instant,
+    frames: u64,
+    events: u64,
+    syn_dropped: u64,
+    min_speed: f64,
+    max_speed: f64,
+    // the first bucket holds everything below the lowest octave, including standing still
+    speeds: Vec<u64>,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            start: Instant::now(),
+            frames: 0,
+            events: 0,
+            syn_dropped: 0,
+            min_speed: f64::INFINITY,
+            max_speed: f64::NEG_INFINITY,
+            speeds: vec![0; OCTAVES * BUCKETS_PER_OCTAVE as usize + 1],
+        }
+    }
+
+    pub fn event(&mut self, event: &InputEvent) {
+        self.events += 1;
+        if event.event_code == EventCode::EV_SYN(EV_SYN::SYN_DROPPED) {
+            self.syn_dropped += 1;
+        }
+    }
+
+    pub fn frame(&mut self, speed: f64) {
+        self.frames += 1;
+        self.min_speed = self.min_speed.min(speed);
+        self.max_speed = self.max_speed.max(speed);
+        let octave = speed.log2() - LOWEST_OCTAVE;
+        // nan (from a speed of 0) fails the comparison too
+        let bucket = if octave >= 0.0 {
+            ((octave * BUCKETS_PER_OCTAVE) as usize + 1).min(self.speeds.len() - 1)
+        } else {
+            0
+        };
+        self.speeds[bucket] += 1;
+    }
+
+    fn median_speed(&self) -> f64 {
+        let half = self.frames.div_ceil(2);
+        let mut seen = 0;
+        let bucket = self
+            .speeds
+            .iter()
+            .position(|&count| {
+                seen += count;
+                seen >= half
+            })
+            .unwrap_or(0);
+        if bucket == 0 {
+            return self.min_speed;
+        }
+        // the middle of the bucket, on a log scale
+        let octave = LOWEST_OCTAVE + (bucket as f64 - 0.5) / BUCKETS_PER_OCTAVE;
+        octave.exp2().clamp(self.min_speed, self.max_speed)
+    }
+
+    pub fn summary(&self) -> String {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let mut summary = format!(
+            "{} frames, {} events, {} SYN_DROPPED, {:.1} frames/s over {:.1}s",
+            self.frames,
+            self.events,
+            self.syn_dropped,
+            self.frames as f64 / elapsed,
+            elapsed
+        );
+        if self.frames > 0 {
+            summary += &format!(
+                "\nspeed min {:.3} median ~{:.3} max {:.3}",
+                self.min_speed,
+                self.median_speed(),
+                self.max_speed
+            );
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use evdev_rs::{
+        enums::{EventCode, EV_REL, EV_SYN},
+        InputEvent,
+    };
+
+    use crate::{
+        accel::Accelerator, args::parse_args, backend::MockBackend, control::Control, taps::Taps,
+        testing::frames,
+    };
+
+    // a session of three frames and a SYN_DROPPED through the whole loop counts every event read
+    // and every frame; the first frame, from nothing, is standing still, and the other two are 10
+    // counts a ms, which the median lands near
+    #[test]
+    fn session_summary() -> Result<(), String> {
+        let mut events = frames(&[
+            (1, &[(EV_REL::REL_X, 3), (EV_REL::REL_Y, 4)]),
+            (2, &[(EV_REL::REL_X, 10)]),
+            (4, &[(EV_REL::REL_Y, 20)]),
+        ]);
+        let time = events[3].time;
+        let dropped = InputEvent::new(&time, &EventCode::EV_SYN(EV_SYN::SYN_DROPPED), 0);
+        events.insert(3, dropped);
+        let options = ["-m", "1", "-a", "0", "selftest-device"];
+        let args = parse_args(options.map(OsString::from).to_vec())
+            .map_err(|err| format!("bad options: {}", err))?;
+        let mut taps = Taps::none();
+        crate::run(
+            &mut MockBackend::new(events),
+            &args,
+            &mut Accelerator::new(),
+            &mut taps,
+            &Control::default(),
+        )
+        .map_err(|err| err.to_string())?;
+        let summary = taps.stats.summary();
+        let (counts, speeds) = summary.split_once('\n').ok_or("no speeds in the summary")?;
+        if !counts.starts_with("3 frames, 8 events, 1 SYN_DROPPED, ") {
+            return Err(format!("counted {:?}", counts));
+        }
+        // a bucket is about 4% wide
+        let median = taps.stats.median_speed();
+        let printed =
+            speeds.starts_with("speed min 0.000 median ~") && speeds.ends_with(" max 10.000");
+        if !printed || !(9.6..=10.0).contains(&median) {
+            return Err(format!("{:?}, median {}", speeds, median));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL, EV_SYN},
+    InputEvent,
+};
+
+// how long motion can wait for its SYN_REPORT before it's logged, without a flush timeout
+const STUCK_MOTION: Duration = Duration::from_millis(50);
+
+// ways a device breaks the protocol
+#[derive(Debug, PartialEq)]
+pub enum Anomaly {
+    // motion that waited this long for a SYN_REPORT, flushed with a made up one if true
+    StuckMotion(Duration, bool),
+    // SYN_REPORTs in a row with nothing between them
+    EmptyFrames,
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Anomaly::StuckMotion(waited, flush) => {
+                write!(f, "motion waited {}ms for a SYN_REPORT", waited.as_millis())?;
+                if *flush {
+                    write!(f, ", flushing it")?;
+                }
+                Ok(())
+            }
+            Anomaly::EmptyFrames => write!(f, "got SYN_REPORTs with nothing between them"),
+        }
+    }
+}
+
+// watches the order of events from the source for --strict
+pub struct Strict {
+    flush_after: Option<Duration>,
+    // when the motion waiting on a SYN_REPORT was read
+    motion_since: Option<Instant>,
+    stuck_logged: bool,
+    // events read since the last SYN_REPORT
+    events: u32,
+    empty_frames: u32,
+}
+
+impl Strict {
+    pub fn new(flush_after: Option<Duration>) -> Strict {
+        Strict {
+            flush_after,
+            motion_since: None,
+            stuck_logged: false,
+            events: 0,
+            empty_frames: 0,
+        }
+    }
+
+    fn stuck_after(&self) -> Duration {
+        self.flush_after.unwrap_or(STUCK_MOTION)
+    }
+
+    // every event read (or made up) at now
+    pub fn event(&mut self, event: &InputEvent, now: Instant) -> Option<Anomaly> {
+        match event.event_code {
+            EventCode::EV_SYN(EV_SYN::SYN_REPORT) => {
+                self.empty_frames = if self.events == 0 {
+                    self.empty_frames + 1
+                } else {
+                    0
+                };
+                self.events = 0;
+                self.motion_since = None;
+                self.stuck_logged = false;
+                // only once for each run of them
+                (self.empty_frames == 2).then_some(Anomaly::EmptyFrames)
+            }
+            EventCode::EV_REL(EV_REL::REL_X | EV_REL::REL_Y) => {
+                self.events += 1;
+                self.motion_since.get_or_insert(now);
+                None
+            }
+            _ => {
+                self.events += 1;
+                None
+            }
+        }
+    }
+
+    // motion that's waited too long by now, once for each time it gets stuck
+    pub fn check(&mut self, now: Instant) -> Option<Anomaly> {
+        let waited = now.saturating_duration_since(self.motion_since?);
+        if self.stuck_logged || waited < self.stuck_after() {
+            return None;
+        }
+        self.stuck_logged = true;
+        Some(Anomaly::StuckMotion(waited, self.flush_after.is_some()))
+    }
+
+    // how long until check has something, None if nothing's waiting
+    pub fn due_in(&self, now: Instant) -> Option<Duration> {
+        if self.stuck_logged {
+            return None;
+        }
+        let due = self.motion_since? + self.stuck_after();
+        Some(due.saturating_duration_since(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use evdev_rs::enums::{EventCode, EV_REL, EV_SYN};
+
+    use super::{Anomaly, Strict};
+    use crate::testing::event;
+
+    // movement without a SYN_REPORT is flushed once, after the timeout
+    #[test]
+    fn strict_flush() -> Result<(), String> {
+        let mut strict = Strict::new(Some(Duration::from_millis(20)));
+        let start = Instant::now();
+        strict.event(&event(EventCode::EV_REL(EV_REL::REL_X), 3), start);
+        if let Some(anomaly) = strict.check(start + Duration::from_millis(19)) {
+            return Err(format!("early: {}", anomaly));
+        }
+        let waited = Duration::from_millis(20);
+        match strict.check(start + waited) {
+            Some(Anomaly::StuckMotion(got, true)) if got == waited => {}
+            other => return Err(format!("expected a flush, got {:?}", other)),
+        }
+        if let Some(anomaly) = strict.check(start + Duration::from_millis(40)) {
+            return Err(format!("flushed twice: {}", anomaly));
+        }
+        Ok(())
+    }
+
+    // SYN_REPORTs with nothing between them are logged once for each run of them
+    #[test]
+    fn strict_empty_frames() -> Result<(), String> {
+        let mut strict = Strict::new(None);
+        let now = Instant::now();
+        let syn = event(EventCode::EV_SYN(EV_SYN::SYN_REPORT), 0);
+        let motion = event(EventCode::EV_REL(EV_REL::REL_Y), -1);
+        let anomalies: Vec<_> = [&motion, &syn, &syn, &syn, &syn, &motion, &syn, &syn, &syn]
+            .into_iter()
+            .map(|event| strict.event(event, now))
+            .collect();
+        let logged: Vec<_> = anomalies
+            .iter()
+            .enumerate()
+            .filter(|(_, anomaly)| **anomaly == Some(Anomaly::EmptyFrames))
+            .map(|(i, _)| i)
+            .collect();
+        // the first SYN_REPORT ends the motion's frame, so it's the third that makes two empty
+        if logged != [3, 8] || anomalies.iter().flatten().count() != logged.len() {
+            return Err(format!("expected logging at 3 and 8, got {:?}", anomalies));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,517 @@
+use std::{
+    io::{self, Write},
+    net::{SocketAddr, UdpSocket},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use evdev_rs::InputEvent;
+
+use crate::{accel::Accelerator, args::Args, backend::heartbeat::HeartbeatDevice, stats::Stats};
+
+// outside observers of the processed frames, none of which may hold up the input loop
+pub struct Taps {
+    preview: Option<FrameStream>,
+    multiplier: Option<FrameStream>,
+    udp: Option<Telemetry>,
+    heartbeat: Option<HeartbeatDevice>,
+    // where --debug prints frames to
+    debug: Box<dyn Write + Send>,
+    pub stats: Stats,
+}
+
+impl Taps {
+    pub fn open(args: &Args) -> io::Result<Taps> {
+        Ok(Taps {
+            preview: match args.preview_stream {
+                Some(ref path) => Some(FrameStream::bind(path)?),
+                None => None,
+            },
+            multiplier: match args.emit_multiplier_to {
+                Some(ref path) => Some(FrameStream::bind(path)?),
+                None => None,
+            },
+            udp: match args.debug_udp {
+                Some(addr) => Some(Telemetry::connect(addr)?),
+                None => None,
+            },
+            heartbeat: match args.heartbeat_device {
+                Some(ref name) => Some(HeartbeatDevice::create(name, args.heartbeat_interval)?),
+                None => None,
+            },
+            debug: Box::new(io::stderr()),
+            stats: Stats::new(),
+        })
+    }
+
+    // nothing to observe
+    pub fn none() -> Taps {
+        Taps {
+            preview: None,
+            multiplier: None,
+            udp: None,
+            heartbeat: None,
+            debug: Box::new(io::stderr()),
+            stats: Stats::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_debug(self, debug: impl Write + Send + 'static) -> Taps {
+        Taps {
+            debug: Box::new(debug),
+            ..self
+        }
+    }
+
+    // like eprintln, a debug line that can't be written is lost
+    pub fn debug(&mut self, line: &str) {
+        let _ = writeln!(self.debug, "{}", line);
+    }
+
+    // writes a heartbeat if one's due by now, and says when the next is
+    pub fn heartbeat(&mut self, now: Instant) -> Option<Duration> {
+        self.heartbeat.as_mut().map(|heartbeat| heartbeat.poll(now))
+    }
+
+    // the heartbeat device goes away after its last beat, along with the virtual device
+    pub fn shutdown(&mut self) {
+        if let Some(mut heartbeat) = self.heartbeat.take() {
+            heartbeat.shutdown();
+        }
+    }
+
+    // every event read, before it's processed
+    pub fn event(&mut self, event: &InputEvent) {
+        self.stats.event(event);
+    }
+
+    // x_in and y_in are the movement read, x_out and y_out what was written
+    pub fn frame(&mut self, accel: &Accelerator, x_in: f64, y_in: f64, x_out: i32, y_out: i32) {
+        self.stats.frame(accel.speed);
+        if let Some(ref mut preview) = self.preview {
+            preview.send(&encode_preview(
+                accel.speed,
+                accel.sensitivity,
+                x_out,
+                y_out,
+            ));
+        }
+        if let Some(ref mut multiplier) = self.multiplier {
+            multiplier.send(&encode_multiplier(accel.sensitivity, x_in, y_in));
+        }
+        if let Some(ref mut udp) = self.udp {
+            udp.send(&encode_telemetry(
+                accel.speed,
+                accel.sensitivity,
+                (x_in, y_in),
+                (x_out, y_out),
+            ));
+        }
+    }
+}
+
+// frame layout, all little endian:
+//   0  f64  speed, in counts per ms
+//   8  f64  sensitivity multiplier applied
+//   16 i32  x movement written out
+//   20 i32  y movement written out
+pub const PREVIEW_FRAME_LEN: usize = 24;
+
+fn encode_preview(speed: f64, sensitivity: f64, x: i32, y: i32) -> [u8; PREVIEW_FRAME_LEN] {
+    let mut frame = [0; PREVIEW_FRAME_LEN];
+    frame[0..8].copy_from_slice(&speed.to_le_bytes());
+    frame[8..16].copy_from_slice(&sensitivity.to_le_bytes());
+    frame[16..20].copy_from_slice(&x.to_le_bytes());
+    frame[20..24].copy_from_slice(&y.to_le_bytes());
+    frame
+}
+
+// frame layout, all little endian:
+//   0  f64  sensitivity multiplier the frame was worked out with
+//   8  i32  x movement read from the device
+//   12 i32  y movement read from the device
+pub const MULTIPLIER_FRAME_LEN: usize = 16;
+
+fn encode_multiplier(sensitivity: f64, x: f64, y: f64) -> [u8; MULTIPLIER_FRAME_LEN] {
+    let mut frame = [0; MULTIPLIER_FRAME_LEN];
+    frame[0..8].copy_from_slice(&sensitivity.to_le_bytes());
+    frame[8..12].copy_from_slice(&(x as i32).to_le_bytes());
+    frame[12..16].copy_from_slice(&(y as i32).to_le_bytes());
+    frame
+}
+
+// datagram layout, all little endian:
+//   0  f64  speed, in counts per ms
+//   8  f64  sensitivity multiplier applied
+//   16 i32  x movement read from the device
+//   20 i32  y movement read from the device
+//   24 i32  x movement written out
+//   28 i32  y movement written out
+pub const TELEMETRY_LEN: usize = 32;
+
+fn encode_telemetry(
+    speed: f64,
+    sensitivity: f64,
+    (x_in, y_in): (f64, f64),
+    (x_out, y_out): (i32, i32),
+) -> [u8; TELEMETRY_LEN] {
+    let mut datagram = [0; TELEMETRY_LEN];
+    datagram[0..8].copy_from_slice(&speed.to_le_bytes());
+    datagram[8..16].copy_from_slice(&sensitivity.to_le_bytes());
+    datagram[16..20].copy_from_slice(&(x_in as i32).to_le_bytes());
+    datagram[20..24].copy_from_slice(&(y_in as i32).to_le_bytes());
+    datagram[24..28].copy_from_slice(&x_out.to_le_bytes());
+    datagram[28..32].copy_from_slice(&y_out.to_le_bytes());
+    datagram
+}
+
+// the least time between datagrams, frames in between aren't sent
+pub const TELEMETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+// frames sent to a collector on another machine, which may well not be listening
+struct Telemetry {
+    socket: UdpSocket,
+    last_sent: Option<Instant>,
+}
+
+impl Telemetry {
+    fn connect(addr: SocketAddr) -> io::Result<Telemetry> {
+        let local: SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0; 16], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Telemetry {
+            socket,
+            last_sent: None,
+        })
+    }
+
+    fn send(&mut self, datagram: &[u8]) {
+        if self
+            .last_sent
+            .is_some_and(|last| last.elapsed() < TELEMETRY_INTERVAL)
+        {
+            return;
+        }
+        self.last_sent = Some(Instant::now());
+        // a full buffer or nobody listening (refused, from an earlier datagram) just loses it
+        let _ = self.socket.send(datagram);
+    }
+}
+
+struct Client {
+    stream: UnixStream,
+    // the rest of a frame the socket only took part of, which has to go out before anything else
+    // so the reader stays in step
+    unsent: Vec<u8>,
+}
+
+impl Client {
+    // false once the client is gone
+    fn send(&mut self, frame: &[u8]) -> bool {
+        if !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(written) => drop(self.unsent.drain(..written)),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return true,
+                Err(_) => return false,
+            }
+            // frames the client is too slow for are dropped
+            if !self.unsent.is_empty() {
+                return true;
+            }
+        }
+        match self.stream.write(frame) {
+            Ok(written) => {
+                self.unsent.extend_from_slice(&frame[written..]);
+                true
+            }
+            Err(err) => err.kind() == io::ErrorKind::WouldBlock,
+        }
+    }
+}
+
+// a unix socket streaming frames to whoever connects, like a gui plotting the curve live
+pub struct FrameStream {
+    listener: UnixListener,
+    clients: Vec<Client>,
+}
+
+impl FrameStream {
+    pub fn bind(path: &Path) -> io::Result<FrameStream> {
+        // a socket left behind by an earlier run
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(FrameStream {
+            listener,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn send(&mut self, frame: &[u8]) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Client {
+                    stream,
+                    unsent: Vec::new(),
+                });
+            }
+        }
+        self.clients.retain_mut(|client| client.send(frame));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        io::{ErrorKind, Read},
+        net::UdpSocket,
+        os::unix::net::UnixStream,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use evdev_rs::enums::{EventCode, EV_REL};
+
+    use super::{Taps, MULTIPLIER_FRAME_LEN, PREVIEW_FRAME_LEN, TELEMETRY_INTERVAL, TELEMETRY_LEN};
+    use crate::{
+        accel::{factor, Accelerator},
+        args::parse_args,
+        backend::MockBackend,
+        control::Control,
+        testing::frames,
+    };
+
+    // every frame's multiplier comes through as the curve gives it for the frame's speed, 3, 10 and
+    // 40 counts a ms after a frame starting the timing, alongside the movement read; that's what's
+    // written too with --no-transform, and accelerated without
+    #[test]
+    fn emit_multiplier() -> Result<(), String> {
+        let path =
+            std::env::temp_dir().join(format!("accelerator-multiplier-{}", std::process::id()));
+        let moves = [3, 10, 40];
+        let result = (|| {
+            for (transform, written_moves) in [("", [8, 40, 240]), ("--no-transform", moves)] {
+                let path = path.to_string_lossy();
+                let options = format!(
+                    "-m 2 -a 0.1 -c 3 --emit-multiplier-to {} {} selftest-device",
+                    path, transform
+                );
+                let args = parse_args(options.split_whitespace().map(OsString::from).collect())
+                    .map_err(|err| format!("bad options: {}", err))?;
+                let mut taps = Taps::open(&args).map_err(|err| err.to_string())?;
+                let mut client = UnixStream::connect(&*path).map_err(|err| err.to_string())?;
+                client
+                    .set_read_timeout(Some(Duration::from_secs(1)))
+                    .map_err(|err| err.to_string())?;
+                let events = frames(&[
+                    (0, &[]),
+                    (1, &[(EV_REL::REL_X, moves[0])]),
+                    (2, &[(EV_REL::REL_X, moves[1])]),
+                    (3, &[(EV_REL::REL_X, moves[2])]),
+                ]);
+                let mut backend = MockBackend::new(events);
+                let control = Control::default();
+                crate::run(
+                    &mut backend,
+                    &args,
+                    &mut Accelerator::new(),
+                    &mut taps,
+                    &control,
+                )
+                .map_err(|err| err.to_string())?;
+                let mut records = [0; 4 * MULTIPLIER_FRAME_LEN];
+                client
+                    .read_exact(&mut records)
+                    .map_err(|err| err.to_string())?;
+                let received: Vec<_> = records
+                    .chunks_exact(MULTIPLIER_FRAME_LEN)
+                    .map(|record| {
+                        let sensitivity = f64::from_le_bytes(record[0..8].try_into().unwrap());
+                        let x = i32::from_le_bytes(record[8..12].try_into().unwrap());
+                        let y = i32::from_le_bytes(record[12..16].try_into().unwrap());
+                        (sensitivity, x, y)
+                    })
+                    .collect();
+                let expected: Vec<_> = [0]
+                    .iter()
+                    .chain(&moves)
+                    .map(|&x| (factor(2.0, 0.1, 3.0, 0.0, x as f64), x, 0))
+                    .collect();
+                if received != expected {
+                    return Err(format!(
+                        "{}: expected {:?}, got {:?}",
+                        transform, expected, received
+                    ));
+                }
+                let written: Vec<_> = backend
+                    .written
+                    .iter()
+                    .filter(|event| event.event_code == EventCode::EV_REL(EV_REL::REL_X))
+                    .map(|event| event.value)
+                    .collect();
+                if written != written_moves {
+                    return Err(format!(
+                        "{}: expected {:?} written, got {:?}",
+                        transform, written_moves, written
+                    ));
+                }
+            }
+            Ok(())
+        })();
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    // a connected reader gets every frame as laid out; one that never reads loses frames instead of
+    // holding up the sender, and what it does get is still whole frames in order
+    #[test]
+    fn preview_stream() -> Result<(), String> {
+        let path = std::env::temp_dir().join(format!("accelerator-preview-{}", std::process::id()));
+        let result = (|| {
+            let options = format!(
+                "-m 1 -a 0 --preview-stream {} selftest-device",
+                path.display()
+            );
+            let args = parse_args(options.split_whitespace().map(OsString::from).collect())
+                .map_err(|err| format!("bad options: {}", err))?;
+            let mut taps = Taps::open(&args).map_err(|err| err.to_string())?;
+            let decode = |record: &[u8]| {
+                let f64_at = |at: usize| f64::from_le_bytes(record[at..at + 8].try_into().unwrap());
+                let i32_at = |at: usize| i32::from_le_bytes(record[at..at + 4].try_into().unwrap());
+                (f64_at(0), f64_at(8), i32_at(16), i32_at(20))
+            };
+
+            let mut reader = UnixStream::connect(&path).map_err(|err| err.to_string())?;
+            reader
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .map_err(|err| err.to_string())?;
+            let mut accel = Accelerator::new();
+            accel.sensitivity = 1.5;
+            for i in 1..=3 {
+                accel.speed = i as f64;
+                taps.frame(&accel, i as f64, 0.0, 2 * i, -i);
+            }
+            let mut records = [0; 3 * PREVIEW_FRAME_LEN];
+            reader
+                .read_exact(&mut records)
+                .map_err(|err| err.to_string())?;
+            let received: Vec<_> = records
+                .chunks_exact(PREVIEW_FRAME_LEN)
+                .map(decode)
+                .collect();
+            let expected: Vec<_> = (1..=3).map(|i| (i as f64, 1.5, 2 * i, -i)).collect();
+            if received != expected {
+                return Err(format!("expected {:?}, got {:?}", expected, received));
+            }
+
+            // far more than a socket buffer holds
+            let sent = 200_000;
+            let start = Instant::now();
+            for i in 1..=sent {
+                accel.speed = i as f64;
+                taps.frame(&accel, 0.0, 0.0, 0, 0);
+            }
+            if start.elapsed() > Duration::from_secs(5) {
+                return Err(format!("sending took {:?}", start.elapsed()));
+            }
+            reader
+                .set_nonblocking(true)
+                .map_err(|err| err.to_string())?;
+            let mut bytes = Vec::new();
+            match reader.read_to_end(&mut bytes) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                other => return Err(format!("expected the reader to catch up, got {:?}", other)),
+            }
+            let speeds: Vec<_> = bytes
+                .chunks_exact(PREVIEW_FRAME_LEN)
+                .map(|record| decode(record).0)
+                .collect();
+            if speeds.len() >= sent as usize || speeds.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(format!(
+                    "expected fewer than {} frames in order, got {}",
+                    sent,
+                    speeds.len()
+                ));
+            }
+            Ok(())
+        })();
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    // frames sent to a local listener come through whole, apart from those too soon after the last
+    #[test]
+    fn debug_udp() -> Result<(), String> {
+        let listener = UdpSocket::bind("127.0.0.1:0").map_err(|err| err.to_string())?;
+        listener
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .map_err(|err| err.to_string())?;
+        let addr = listener.local_addr().map_err(|err| err.to_string())?;
+        let options = [
+            "-m",
+            "1",
+            "-a",
+            "0",
+            "--debug-udp",
+            &addr.to_string(),
+            "selftest-device",
+        ];
+        let args = parse_args(options.map(OsString::from).to_vec())
+            .map_err(|err| format!("bad options: {}", err))?;
+        let mut taps = Taps::open(&args).map_err(|err| err.to_string())?;
+        let mut accel = Accelerator::new();
+        accel.sensitivity = 2.0;
+        for i in 1..=3 {
+            accel.speed = i as f64;
+            taps.frame(&accel, i as f64, -i as f64, 2 * i, -2 * i);
+            // rate limited away
+            accel.speed = 99.0;
+            taps.frame(&accel, 0.0, 0.0, 0, 0);
+            thread::sleep(TELEMETRY_INTERVAL + Duration::from_millis(1));
+        }
+        let mut received = Vec::new();
+        let mut datagram = [0; 64];
+        for _ in 1..=3 {
+            let len = listener
+                .recv(&mut datagram)
+                .map_err(|err| err.to_string())?;
+            if len != TELEMETRY_LEN {
+                return Err(format!("got a {} byte datagram", len));
+            }
+            let f64_at = |at: usize| f64::from_le_bytes(datagram[at..at + 8].try_into().unwrap());
+            let i32_at = |at: usize| i32::from_le_bytes(datagram[at..at + 4].try_into().unwrap());
+            received.push((
+                f64_at(0),
+                f64_at(8),
+                i32_at(16),
+                i32_at(20),
+                i32_at(24),
+                i32_at(28),
+            ));
+        }
+        let expected: Vec<_> = (1..=3)
+            .map(|i| (i as f64, 2.0, i, -i, 2 * i, -2 * i))
+            .collect();
+        if received != expected {
+            return Err(format!("expected {:?}, got {:?}", expected, received));
+        }
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        match listener.recv(&mut datagram) {
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(()),
+            _ => Err("a rate limited frame was sent".to_string()),
+        }
+    }
+}
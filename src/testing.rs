@@ -0,0 +1,204 @@
+// helpers shared by the tests
+
+use std::{
+    ffi::OsString,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use evdev_rs::{
+    enums::{EventCode, EV_REL, EV_SYN},
+    InputEvent, TimeVal,
+};
+
+use crate::{
+    accel::Accelerator,
+    args::{parse_args, Args},
+    backend::{
+        replay::{RealClock, ReplayBackend},
+        InputBackend, MockBackend,
+    },
+    control::Control,
+    taps::Taps,
+    Stop,
+};
+
+pub fn event(code: EventCode, value: i32) -> InputEvent {
+    InputEvent::new(&TimeVal::new(0, 0), &code, value)
+}
+
+// keeps everything written to the backend it wraps, along with when it was written
+pub struct Recorder<B> {
+    pub inner: B,
+    pub written: Vec<(Instant, InputEvent)>,
+}
+
+impl<B: InputBackend> InputBackend for Recorder<B> {
+    fn next_event(&mut self) -> std::io::Result<Option<InputEvent>> {
+        self.inner.next_event()
+    }
+
+    fn wait(&mut self, timeout: Duration) -> std::io::Result<bool> {
+        self.inner.wait(timeout)
+    }
+
+    fn write_event(&mut self, event: &InputEvent) -> std::io::Result<()> {
+        self.written.push((Instant::now(), event.clone()));
+        self.inner.write_event(event)
+    }
+
+    fn grab(&mut self) -> std::io::Result<()> {
+        self.inner.grab()
+    }
+
+    fn ungrab(&mut self) -> std::io::Result<()> {
+        self.inner.ungrab()
+    }
+}
+
+// frames of raw movement on x only, a millisecond apart
+pub fn smoothed_frames(options: &str, frames: &[i32]) -> Result<Vec<i32>, String> {
+    let args = mock_args(options)?;
+    let mut accel = Accelerator::new();
+    accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+    let outputs = frames.iter().zip(1..).map(|(&x, ms)| {
+        let time = TimeVal::new(1, ms * 1000);
+        accel.process_frame(&args, x as f64, 0.0, 1, None, time).0
+    });
+    Ok(outputs.collect())
+}
+
+// frames of raw movement a millisecond apart through an Accelerator, options as on the command line
+// without the device
+pub fn accelerated(options: &str, frames: &[(f64, f64)]) -> Result<Vec<(i32, i32)>, String> {
+    let args = mock_args(options)?;
+    let mut accel = Accelerator::new();
+    accel.process_frame(&args, 0.0, 0.0, 0, None, TimeVal::new(1, 0));
+    let outputs = frames.iter().zip(1..).map(|(&(x, y), ms)| {
+        let rel_events = (x != 0.0) as u32 + (y != 0.0) as u32;
+        accel.process_frame(&args, x, y, rel_events, None, TimeVal::new(1, ms * 1000))
+    });
+    Ok(outputs.collect())
+}
+
+// frames of relative movement, each at its ms after a start and ended by a SYN_REPORT
+pub fn frames(frames: &[(i64, &[(EV_REL, i32)])]) -> Vec<InputEvent> {
+    let mut events = Vec::new();
+    for &(ms, moved) in frames {
+        let time = TimeVal::new(100 + ms / 1000, ms % 1000 * 1000);
+        for &(axis, value) in moved {
+            events.push(InputEvent::new(&time, &EventCode::EV_REL(axis), value));
+        }
+        events.push(InputEvent::new(
+            &time,
+            &EventCode::EV_SYN(EV_SYN::SYN_REPORT),
+            0,
+        ));
+    }
+    events
+}
+
+// a writer whose output can still be read once it's been handed off
+#[derive(Clone, Default)]
+pub struct Captured(pub Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    pub fn lines(&self) -> Vec<String> {
+        let captured = self.0.lock().unwrap();
+        String::from_utf8_lossy(&captured)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+// options as on the command line without the device
+pub fn mock_args(options: &str) -> Result<Args, String> {
+    let options = options.split_whitespace().chain(["selftest-device"]);
+    parse_args(options.map(OsString::from).collect()).map_err(|err| format!("bad options: {}", err))
+}
+
+// runs a backend through the whole processing with a fresh accelerator and nothing tapped, giving
+// back why it stopped and the accelerator as it was left
+pub fn run_backend<B: InputBackend>(
+    backend: &mut B,
+    args: &Args,
+    control: &Control,
+) -> Result<(Stop, Accelerator), String> {
+    let mut accel = Accelerator::new();
+    let stop = crate::run(backend, args, &mut accel, &mut Taps::none(), control)
+        .map_err(|err| err.to_string())?;
+    Ok((stop, accel))
+}
+
+// the values written on one axis
+pub fn moved(written: &[(EventCode, i32)], axis: EV_REL) -> Vec<i32> {
+    let moved = written
+        .iter()
+        .filter(|&&(code, _)| code == EventCode::EV_REL(axis));
+    moved.map(|&(_, value)| value).collect()
+}
+
+// what a recording replayed in real time comes out as, with when each event was written
+pub fn run_replay(
+    options: &str,
+    events: Vec<InputEvent>,
+) -> Result<Vec<(Instant, InputEvent)>, String> {
+    let mut backend = Recorder {
+        inner: ReplayBackend::with_clock(events, 1.0, RealClock),
+        written: Vec::new(),
+    };
+    run_backend(&mut backend, &mock_args(options)?, &Control::default())?;
+    Ok(backend.written)
+}
+
+// the lines --debug prints for a mock device's events
+pub fn debug_lines(options: &str, events: Vec<InputEvent>) -> Result<Vec<String>, String> {
+    let args = mock_args(options)?;
+    let captured = Captured::default();
+    crate::run(
+        &mut MockBackend::new(events),
+        &args,
+        &mut Accelerator::new(),
+        &mut Taps::none().with_debug(captured.clone()),
+        &Control::default(),
+    )
+    .map_err(|err| err.to_string())?;
+    Ok(captured.lines())
+}
+
+// what a mock device's events come out as through the whole processing, options as on the command
+// line without the device
+pub fn run_mock(options: &str, events: Vec<InputEvent>) -> Result<Vec<(EventCode, i32)>, String> {
+    let (_, written) = run_controlled(options, events, &Control::default())?;
+    Ok(written)
+}
+
+// run_mock under the control given, along with why the loop stopped
+pub fn run_controlled(
+    options: &str,
+    events: Vec<InputEvent>,
+    control: &Control,
+) -> Result<(Stop, Vec<(EventCode, i32)>), String> {
+    let mut backend = MockBackend::new(events);
+    let (stop, _) = run_backend(&mut backend, &mock_args(options)?, control)?;
+    let written = backend.written.into_iter();
+    Ok((
+        stop,
+        written
+            .map(|event| (event.event_code, event.value))
+            .collect(),
+    ))
+}
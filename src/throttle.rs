@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+// counts events against a budget per second, by the wall clock since a runaway device's
+// timestamps can't be trusted either
+pub struct Throttle {
+    max: u32,
+    window_start: Instant,
+    events: u32,
+    // whether the budget ran out in this window or the one before, so a flood is only logged
+    // when it starts
+    engaged: bool,
+}
+
+impl Throttle {
+    pub fn new(max: u32) -> Throttle {
+        Throttle {
+            max,
+            window_start: Instant::now(),
+            events: 0,
+            engaged: false,
+        }
+    }
+
+    // counts an event, and returns whether the budget for this second is used up
+    pub fn over(&mut self) -> bool {
+        if self.window_start.elapsed() >= WINDOW {
+            self.engaged = self.events > self.max;
+            self.window_start = Instant::now();
+            self.events = 0;
+        }
+        self.events += 1;
+        let over = self.events > self.max;
+        if over && !self.engaged {
+            eprintln!(
+                "Warning: device sent over {} events/s, coalescing movement until it slows down",
+                self.max
+            );
+            self.engaged = true;
+        }
+        over
+    }
+}
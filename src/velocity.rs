@@ -0,0 +1,155 @@
+use std::{fs, path::Path};
+
+// output speed against input speed, as most acceleration tools draw it, turned into a multiplier
+pub struct VelocityMap {
+    // sorted by input speed, which is never repeated
+    points: Vec<(f64, f64)>,
+}
+
+fn push(points: &mut Vec<(f64, f64)>, point: (f64, f64)) -> Result<(), String> {
+    for speed in [point.0, point.1] {
+        if !(speed.is_finite() && speed >= 0.0) {
+            return Err(format!("{} isn't a speed", speed));
+        }
+    }
+    // anything else would be an infinite multiplier when nearly standing still
+    if point.0 == 0.0 && point.1 != 0.0 {
+        return Err("an input speed of 0 has to map to 0".to_string());
+    }
+    if points.last().is_some_and(|last| last.0 >= point.0) {
+        return Err("input speeds have to increase".to_string());
+    }
+    points.push(point);
+    Ok(())
+}
+
+impl VelocityMap {
+    pub fn load(path: &Path) -> Result<VelocityMap, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        VelocityMap::parse(&contents)
+    }
+
+    // input_speed,output_speed rows, skipping blank lines and # comments
+    fn parse(contents: &str) -> Result<VelocityMap, String> {
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for (number, line) in contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+        {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (input, output) = line
+                .split_once(',')
+                .ok_or_else(|| format!("line {}: expected input_speed,output_speed", number))?;
+            let parse = |field: &str| {
+                field
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("line {}: '{}' isn't a speed", number, field.trim()))
+            };
+            push(&mut points, (parse(input)?, parse(output)?))
+                .map_err(|err| format!("line {}: {}", number, err))?;
+        }
+        VelocityMap::finish(points)
+    }
+
+    pub fn from_points(points: &[(f64, f64)]) -> Result<VelocityMap, String> {
+        let mut checked = Vec::new();
+        for (i, point) in points.iter().enumerate() {
+            push(&mut checked, *point).map_err(|err| format!("point {}: {}", i + 1, err))?;
+        }
+        VelocityMap::finish(checked)
+    }
+
+    fn finish(points: Vec<(f64, f64)>) -> Result<VelocityMap, String> {
+        // a multiplier needs a nonzero input speed to come from
+        if !points.iter().any(|point| point.0 > 0.0) {
+            return Err("needs a row with an input speed above 0".to_string());
+        }
+        Ok(VelocityMap { points })
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    pub fn multiplier(&self, speed: f64) -> f64 {
+        let first = self.points[0];
+        let last = self.points.last().unwrap();
+        // past the ends the multiplier stays at the end's, rather than the output speed, so
+        // moving faster still moves faster
+        if speed <= first.0 && first.0 > 0.0 {
+            return first.1 / first.0;
+        }
+        // a table starting from 0 has its first multiplier as the limit going toward 0
+        if speed <= 0.0 {
+            let next = self.points[1];
+            return next.1 / next.0;
+        }
+        if speed >= last.0 {
+            return last.1 / last.0;
+        }
+        let upper = self.points.partition_point(|point| point.0 <= speed);
+        let (low, high) = (self.points[upper - 1], self.points[upper]);
+        let output = low.1 + (high.1 - low.1) * (speed - low.0) / (high.0 - low.0);
+        output / speed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VelocityMap;
+
+    // between rows the output speed is interpolated and divided by the input speed, past the ends
+    // the end's multiplier stays, and standing still on a table from 0 gets the first row's
+    #[test]
+    fn interpolation() -> Result<(), String> {
+        let map = VelocityMap::parse("# input,output\n0,0\n10, 10\n\n20,40\n30,90\n")?;
+        let cases = [
+            (0.0, 1.0),
+            (5.0, 1.0),
+            (15.0, 25.0 / 15.0),
+            (20.0, 2.0),
+            (25.0, 65.0 / 25.0),
+            (30.0, 3.0),
+            (1000.0, 3.0),
+        ];
+        for (speed, expected) in cases {
+            let multiplier = map.multiplier(speed);
+            if (multiplier - expected).abs() > 1e-9 {
+                return Err(format!(
+                    "{} got {}, expected {}",
+                    speed, multiplier, expected
+                ));
+            }
+        }
+        // below a table that doesn't start from 0, its first row's
+        let map = VelocityMap::from_points(&[(4.0, 2.0), (8.0, 8.0)])?;
+        match (map.multiplier(0.0), map.multiplier(2.0)) {
+            (0.5, 0.5) => Ok(()),
+            got => Err(format!("expected 0.5 below the table, got {:?}", got)),
+        }
+    }
+
+    #[test]
+    fn invalid_rows() -> Result<(), String> {
+        let cases = [
+            ("0,5", "line 1: an input speed of 0 has to map to 0"),
+            ("1,1\n1,2", "line 2: input speeds have to increase"),
+            ("1,1\n2;3", "line 2: expected input_speed,output_speed"),
+            ("1,fast", "line 1: 'fast' isn't a speed"),
+            ("1,-1", "line 1: -1 isn't a speed"),
+            ("# nothing\n0,0", "needs a row with an input speed above 0"),
+        ];
+        for (contents, expected) in cases {
+            match VelocityMap::parse(contents) {
+                Err(err) if err == expected => {}
+                Err(err) => return Err(format!("{:?} rejected with {:?}", contents, err)),
+                Ok(_) => return Err(format!("{:?} accepted", contents)),
+            }
+        }
+        Ok(())
+    }
+}
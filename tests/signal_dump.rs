@@ -0,0 +1,33 @@
+use std::{
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+// a running test pattern prints its state on SIGUSR1 and keeps going
+#[test]
+fn signal_dump() -> Result<(), String> {
+    let exe = env!("CARGO_BIN_EXE_accelerator");
+    let child = Command::new(exe)
+        .args(["-m", "1", "-a", "0", "--test-pattern", "line", "--dry-run"])
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+    // long enough to have set up its handlers, while the pattern's still going
+    thread::sleep(Duration::from_millis(300));
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGUSR1) };
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(format!("exited with {}: {}", output.status, stderr));
+    }
+    // the settings in effect, as --print-config prints them
+    let Some((_, dump)) = stderr.split_once("state dump") else {
+        return Err(format!("no dump in {:?}", stderr));
+    };
+    for setting in ["\nm = 1.0\n", "\na = 0.0\n", "\ndry-run = true\n"] {
+        if !dump.contains(setting) {
+            return Err(format!("no {:?} in the dump {:?}", setting.trim(), dump));
+        }
+    }
+    Ok(())
+}